@@ -7,11 +7,18 @@ use std::env;
 use state::{AppContext, App};
 use systems::start;
 
-use crate::state::{project::ProjectComponent, code::CodeComponent, terminal::TerminalComponent};
+use crate::state::{project::ProjectComponent, code::CodeComponent, terminal::TerminalComponent, keymap::load_keymap};
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
-    let context = AppContext::default();
+    let mut context = AppContext::default();
+
+    //keys changed in the keymap file, what is wrong with it is shown until the first key
+    let (keymap, problems) = load_keymap();
+    context.set_keymap(keymap);
+    if !problems.is_empty() {
+        context.set_message(Some(format!("keymap: {}", problems.join("; "))));
+    }
     let app = App::new(
         ProjectComponent::new(context.active_folder().to_path_buf()),
         CodeComponent::new(),
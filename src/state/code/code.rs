@@ -1,6 +1,10 @@
-use std::{fmt::{self}, ops::Add, cmp::{min, max}};
+use std::{fmt::{self}, ops::{Add, Range}, cmp::{min, max}};
 
-use super::{code_utils::Point, code_selection::CodeSelection};
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{code_utils::Point, code_selection::CodeSelection, code_history::Operation, code_cursor::CodeCursor, code_highlight::LineEdit};
 
 
 
@@ -30,19 +34,72 @@ impl Line {
     pub fn get_string(&self) -> String {
         self.line.clone()
     }
+
+    // Number of grapheme clusters in the line, the unit used by the columns of a Point
+    pub fn len(&self) -> usize {
+        self.line.graphemes(true).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    // Byte offset where the grapheme at `column` starts, the length of the line if the column is past its end
+    pub fn byte_index(&self, column: usize) -> usize {
+        self.line
+        .grapheme_indices(true)
+        .nth(column)
+        .map(|(index, _)| index)
+        .unwrap_or(self.line.len())
+    }
+
+    // Grapheme column starting at `byte_index`, a byte inside a grapheme belongs to the next column
+    pub fn column(&self, byte_index: usize) -> usize {
+        self.line
+        .grapheme_indices(true)
+        .take_while(|(index, _)| *index < byte_index)
+        .count()
+    }
+
+    // On screen width of the graphemes before `column`
+    pub fn width(&self, column: usize) -> usize {
+        self.line
+        .graphemes(true)
+        .take(column)
+        .map(|grapheme| grapheme.width())
+        .sum()
+    }
+
+    // Grapheme column drawn at the on screen `width`, wide graphemes cover more than one cell
+    pub fn column_at_width(&self, width: usize) -> usize {
+        let mut current = 0;
+        for (column, grapheme) in self.line.graphemes(true).enumerate() {
+            current += grapheme.width();
+            if current > width {
+                return column;
+            }
+        }
+        self.len()
+    }
 }
 
+// The text is kept in a rope, lines and their numbers are derived from it on demand
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Code {
-    content: Vec<Line>,
-    cursor_displayed: bool,
+    content: Rope,
     cursor: Point,
     selection: Option<CodeSelection>,
+    // cursors added besides the main one, each with its own selection
+    cursors: Vec<CodeCursor>,
+    // edits applied since the last time they were moved into the history
+    operations: Vec<Operation>,
+    // lines changed since the last time the highlighting was updated
+    line_edits: Vec<LineEdit>,
 }
 
 impl fmt::Display for Code {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for line in &self.content {
+        for line in self.get_content() {
             writeln!(f, "{}: {}", line.number, line.line)?;
         }
         Ok(())
@@ -53,14 +110,32 @@ impl Add<Line> for Code {
     type Output = Code;
 
     fn add(mut self, line: Line) -> Code {
-        self.content.push(line);
+        self.add_line(line);
         self
     }
 }
 
+impl From<&str> for Code {
+    fn from(text: &str) -> Code {
+        Code::from(Rope::from_str(text))
+    }
+}
+
+impl From<Rope> for Code {
+    fn from(content: Rope) -> Code {
+        Code { content, cursor: Point::default(), selection: None, cursors: vec![], operations: vec![], line_edits: vec![] }
+    }
+}
+
+impl Default for Code {
+    fn default() -> Code {
+        Code { content: Rope::new(), cursor: Point::default(), selection: None, cursors: vec![], operations: vec![], line_edits: vec![] }
+    }
+}
+
 impl Code {
     pub fn new() -> Code {
-        Code { content: vec![], cursor_displayed: false, cursor: Point::default(), selection: None }
+        Code::default()
     }
 
     pub fn get_cursor(&self) -> &Point {
@@ -72,91 +147,215 @@ impl Code {
     }
 
     pub fn flush(&mut self) {
-        self.content.clear()
+        self.content = Rope::new();
+        self.operations.clear();
+        self.line_edits = vec![LineEdit::new(0, usize::MAX, 1)];
+    }
+
+    pub fn get_rope(&self) -> &Rope {
+        &self.content
+    }
+
+    // Returns the exact text of the buffer
+    pub fn get_text(&self) -> String {
+        self.content.to_string()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.content = Rope::from_str(text);
+        self.operations.clear();
+        self.line_edits = vec![LineEdit::new(0, usize::MAX, self.len_lines())];
+    }
+
+    // Returns the edits applied since the last call
+    pub fn take_operations(&mut self) -> Vec<Operation> {
+        std::mem::take(&mut self.operations)
+    }
+
+    // Returns the lines changed since the last call
+    pub fn take_line_edits(&mut self) -> Vec<LineEdit> {
+        std::mem::take(&mut self.line_edits)
+    }
+
+    // Edits applied and not yet moved into the history
+    pub fn get_operations(&self) -> &Vec<Operation> {
+        &self.operations
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.content.len_chars()
+    }
+
+    pub fn len_lines(&self) -> usize {
+        self.content.len_lines()
+    }
+
+    pub fn line_to_char(&self, number: usize) -> usize {
+        self.content.line_to_char(min(number, self.len_lines()))
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.content.char_to_line(min(char_idx, self.len_chars()))
+    }
+
+    // Length of a line in graphemes, without its line break
+    pub fn line_len(&self, number: usize) -> usize {
+        self.get_line(number).map(|line| line.len()).unwrap_or(0)
+    }
+
+    // Converts a (line, grapheme column) point into a char index of the rope, clamping it inside the buffer
+    pub fn point_to_char(&self, point: &Point) -> usize {
+        if let Some(line) = self.get_line(point.get_x()) {
+            let byte_index = line.byte_index(point.get_y());
+            self.content.line_to_char(point.get_x()) + line.line[..byte_index].chars().count()
+        } else {
+            self.len_chars()
+        }
+    }
+
+    // The nearest point inside the buffer: at most at the end of its line, a point past the last line goes to the end
+    pub fn clamp_point(&self, point: &Point) -> Point {
+        let last = self.len_lines().saturating_sub(1);
+        if point.get_x() > last {
+            return Point::new(last, self.line_len(last));
+        }
+        Point::new(point.get_x(), min(point.get_y(), self.line_len(point.get_x())))
+    }
+
+    // Converts a char index of the rope into a (line, grapheme column) point
+    pub fn char_to_point(&self, char_idx: usize) -> Point {
+        let char_idx = min(char_idx, self.len_chars());
+        let number = self.content.char_to_line(char_idx);
+        let line = self.content.line(number);
+        let byte_index = line.char_to_byte(char_idx - self.content.line_to_char(number));
+        let column = self.get_line(number).map(|line| line.column(byte_index)).unwrap_or(0);
+        Point::new(number, column)
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        let char_idx = min(char_idx, self.len_chars());
+        if !text.is_empty() {
+            self.line_edits.push(LineEdit::new(self.char_to_line(char_idx), 1, 1 + text.matches('\n').count()));
+            self.content.insert(char_idx, text);
+            self.operations.push(Operation::Insert { at: char_idx, text: text.to_string() });
+        }
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        let start = min(range.start, self.len_chars());
+        let end = min(range.end, self.len_chars());
+        if start < end {
+            let text = self.get_slice(start..end);
+            self.line_edits.push(LineEdit::new(self.char_to_line(start), 1 + text.matches('\n').count(), 1));
+            self.content.remove(start..end);
+            self.operations.push(Operation::Delete { at: start, text });
+        }
+    }
+
+    pub fn insert_at_byte(&mut self, byte_idx: usize, text: &str) {
+        let char_idx = self.content.byte_to_char(min(byte_idx, self.content.len_bytes()));
+        self.insert(char_idx, text);
     }
 
-    pub fn is_cursor_displayed(&self) -> bool {
-        self.cursor_displayed
+    pub fn remove_bytes(&mut self, range: Range<usize>) {
+        let start = self.content.byte_to_char(min(range.start, self.content.len_bytes()));
+        let end = self.content.byte_to_char(min(range.end, self.content.len_bytes()));
+        self.remove(start..end);
     }
-    
+
+    pub fn get_slice(&self, range: Range<usize>) -> String {
+        let start = min(range.start, self.len_chars());
+        let end = min(range.end, self.len_chars());
+        if start < end {
+            self.content.slice(start..end).to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    // Range of chars covered by a line, without its line break
+    fn line_range(&self, number: usize) -> Range<usize> {
+        let start = self.content.line_to_char(number);
+        let end = start + self.content.line(number).len_chars();
+        if end > start && self.content.char(end - 1) == '\n' {
+            start..end - 1
+        } else {
+            start..end
+        }
+    }
+
     pub fn remove_line(&mut self, number: usize) {
-        self.get_mut_content().remove(number);
+        if number >= self.len_lines() {
+            return;
+        }
+        let start = self.content.line_to_char(number);
+        let end = self.content.line_to_char(number + 1);
+        if end > start && self.content.char(end - 1) == '\n' {
+            self.remove(start..end);
+        } else if start > 0 {
+            //last line, the line break to remove is the one before it
+            self.remove(start - 1..end);
+        } else {
+            self.remove(start..end);
+        }
     }
 
     pub fn remove_line_at_cursor(&mut self) {
-        self.content.retain(|line| line.number != self.cursor.get_x());
+        self.remove_line(self.cursor.get_x());
     }
 
     pub fn replace_line(&mut self, number: usize, from: String, to: String) {
-        if let Some(line) = self.get_mut_content().get_mut(number) {
+        if let Some(line) = self.get_line(number) {
             let replaced_string = line.get_string().replace(from.as_str(), to.as_str());
-            line.set_string(replaced_string.clone());
+            self.set_line(number, replaced_string);
         }
     }
 
     pub fn change_line(&mut self, number: usize, new_value: String) {
-        if let Some(line) = self.content.get_mut(number) {
-            let mut new_string = line.clone().get_string();
-            new_string.push_str(new_value.as_str());    
-            line.set_string(new_string);
+        if number < self.len_lines() {
+            let end = self.line_range(number).end;
+            self.insert(end, new_value.as_str());
         }
-        
     }
 
     pub fn change_line_at_cursor(&mut self, new_value: String) {
-        for line in &mut self.content {
-            if line.number == self.cursor.get_x()+1 {
-                line.line = new_value;
-                break;
-            }
+        self.set_line(self.cursor.get_x(), new_value);
+    }
+
+    fn set_line(&mut self, number: usize, new_value: String) {
+        if number < self.len_lines() {
+            let range = self.line_range(number);
+            let start = range.start;
+            self.remove(range);
+            self.insert(start, new_value.as_str());
         }
     }
 
     pub fn add_line(&mut self, line: Line) -> &mut Code {
-        self.content.push(line);
+        if self.len_chars() == 0 {
+            self.insert(0, line.line.as_str());
+        } else {
+            let end = self.len_chars();
+            self.insert(end, ("\n".to_string() + line.line.as_str()).as_str());
+        }
         self
     }
 
-    pub fn get_line(&self, number: usize) -> Option<&Line> {
-        self.content.get(number)
-    }
-
-    pub fn set_line_number(&mut self, old_number: usize, new_number: usize) {
-        if let Some(mutable_line) = self.get_mut_content().get_mut(old_number) {
-            mutable_line.set_number(new_number);
+    pub fn get_line(&self, number: usize) -> Option<Line> {
+        if number < self.len_lines() {
+            let range = self.line_range(number);
+            Some(Line::new(number + 1, self.content.slice(range).to_string()))
+        } else {
+            None
         }
     }
 
-    pub fn get_content(&self) -> &Vec<Line> {
-        &self.content
-    }
-
-    pub fn set_cursor(&mut self) {
-        if !self.cursor_displayed {
-            if let Some(line) = self.content.get(self.cursor.get_x()) {
-                let mut line_with_cursor = line.get_string().clone();
-                line_with_cursor.insert(self.cursor.get_y(), '|');
-                self.change_line_at_cursor(line_with_cursor);
-                self.cursor_displayed = true;
-            }
-        }        
-    }
-
-    pub fn remove_cursor(&mut self) {
-        if self.cursor_displayed {
-            if let Some(line) = self.content.get(self.cursor.get_x()) {
-                let mut line_without_cursor = line.get_string().clone();
-                if line_without_cursor.len() > 0 {
-                    line_without_cursor.remove(self.cursor.get_y());
-                    self.change_line_at_cursor(line_without_cursor);
-                    self.cursor_displayed = false;
-                }
-            }
-        }
+    pub fn get_content(&self) -> Vec<Line> {
+        (0..self.len_lines())
+        .filter_map(|number| self.get_line(number))
+        .collect()
     }
 
-    
-
     pub fn flush_selection(&mut self) {
         self.selection = None;
     }
@@ -177,73 +376,94 @@ impl Code {
         self.selection = Some(CodeSelection::new(start, end));
     }
 
+    pub fn set_selection(&mut self, selection: Option<CodeSelection>) {
+        self.selection = selection;
+    }
+
     pub fn get_selection(&self) -> &Option<CodeSelection> {
         &self.selection
     }
 
-    pub fn get_mut_content(&mut self) -> &mut Vec<Line> {
-        &mut self.content
+    // Range of chars covered by the selection, ordered from the first to the last point
+    pub fn get_selection_range(&self) -> Option<Range<usize>> {
+        if let Some(selection) = &self.selection {
+            let start = min(selection.get_start(), selection.get_end());
+            let end = max(selection.get_start(), selection.get_end());
+            Some(self.point_to_char(start)..self.point_to_char(end))
+        } else {
+            None
+        }
     }
 
-    pub fn delete_selection(&mut self) {
-        let readable_selection = self.get_selection().clone();
-        let readable_code = self.get_content().clone();
-
-        if let Some(readable_selection) = readable_selection {
-
-            if readable_selection.get_start().get_x() != readable_selection.get_end().get_x() {
-
-                let start = min(readable_selection.get_start(),readable_selection.get_end());
-                let end = max(readable_selection.get_end(),readable_selection.get_start());
-                let mut is_unordered = false;
-
-                for i in start.get_x()..end.get_x()+1 {
-                    let line = readable_code.get(i);
-                    
-                    if let Some(line) = line {
-                        if i == start.get_x() {
-                            let new_value = line.get_string()[..start.get_y()].to_string();
-                            println!("Line number {} is empty: {}",line.get_number(),new_value.is_empty());
-                            if new_value.is_empty() {
-                                self.remove_line(i);
-                                is_unordered = true;
-                            } else {
-                                self.replace_line(i, line.get_string(), new_value);
-                            }
-    
-                        }
-
-                        if i > start.get_x() && i < end.get_x() {
-                            self.remove_line(i);
-                        }
-    
-                        if i == end.get_x() {
-                            let new_value = line.get_string()[end.get_y()..].to_string(); 
-                            println!("Line number {} is empty: {}",line.get_number(),new_value.is_empty());
-                            println!("*{}*",new_value);
-                            if new_value.is_empty() {
-                                self.remove_line(i);
-                            } else {
-                                self.replace_line(i, line.get_string(), line.get_string()[end.get_y()..].to_string());
-                                if is_unordered {
-                                    self.set_line_number(i,start.get_x());
-
-                                } else {
-                                    self.set_line_number(i,start.get_x()+1);
-                                }
-                            }    
-                        }
-
-                    }
-
-                }
-    
-            }
-            else {
+    // Chars of the word around `point`, letters digits and underscores
+    pub fn word_range_at(&self, point: &Point) -> Option<Range<usize>> {
+        let is_word = |char: char| char.is_alphanumeric() || char == '_';
+        let at = self.point_to_char(point);
+        let mut start = at;
+        while start > 0 && is_word(self.content.char(start - 1)) {
+            start -= 1;
+        }
+        let mut end = at;
+        while end < self.len_chars() && is_word(self.content.char(end)) {
+            end += 1;
+        }
+        if start < end {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
 
-                self.remove_line(readable_selection.get_start().get_x());
+    // Char ranges of every occurrence of `text`, without overlaps
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        if text.is_empty() {
+            return vec![];
+        }
+        let content = self.get_text();
+        let len = text.chars().count();
+        content
+        .match_indices(text)
+        .map(|(byte_index, _)| {
+            let start = self.content.byte_to_char(byte_index);
+            start..start + len
+        })
+        .collect()
+    }
 
-            }
+    // Cursors added besides the main one
+    pub fn get_cursors(&self) -> &Vec<CodeCursor> {
+        &self.cursors
+    }
+
+    pub fn set_cursors(&mut self, cursors: Vec<CodeCursor>) {
+        self.cursors = cursors;
+    }
+
+    pub fn add_cursor(&mut self, cursor: CodeCursor) {
+        let exists = cursor.get_position() == &self.cursor || self.cursors.iter().any(|other| other.get_position() == cursor.get_position());
+        if !exists {
+            self.cursors.push(cursor);
+        }
+    }
+
+    pub fn flush_cursors(&mut self) {
+        self.cursors.clear();
+    }
+
+    // The main cursor followed by the added ones
+    pub fn get_all_cursors(&self) -> Vec<CodeCursor> {
+        let mut cursors = vec![CodeCursor::new(self.cursor.clone(), self.selection.clone())];
+        cursors.extend(self.cursors.iter().cloned());
+        cursors
+    }
+
+    pub fn get_selected_text(&self) -> Option<String> {
+        self.get_selection_range().map(|range| self.get_slice(range))
+    }
+
+    pub fn delete_selection(&mut self) {
+        if let Some(range) = self.get_selection_range() {
+            self.remove(range);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,122 @@
+use super::{CodeComponent, code::Code};
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+// The search of a matching bracket gives up after this many chars, to keep big files responsive
+const MAX_SCAN: usize = 100_000;
+
+fn char_at(code: &Code, char_idx: usize) -> Option<char> {
+    code.get_rope().get_char(char_idx)
+}
+
+// Closing char typed together with an opening bracket or a quote
+pub fn closing_pair(char: char) -> Option<char> {
+    BRACKETS
+    .iter()
+    .find(|(open, _)| *open == char)
+    .map(|(_, close)| *close)
+    .or(QUOTES.contains(&char).then_some(char))
+}
+
+// Char index of the bracket matching the one at `char_idx`, looking forward from an opener and backward from a closer
+pub fn find_match(code: &Code, char_idx: usize) -> Option<usize> {
+    let char = char_at(code, char_idx)?;
+    let rope = code.get_rope();
+    let mut depth = 0;
+    if let Some((open, close)) = BRACKETS.iter().find(|(open, _)| *open == char) {
+        for (index, current) in rope.chars_at(char_idx).enumerate().take(MAX_SCAN) {
+            if current == *open {
+                depth += 1;
+            } else if current == *close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(char_idx + index);
+                }
+            }
+        }
+    } else if let Some((open, close)) = BRACKETS.iter().find(|(_, close)| *close == char) {
+        let mut chars = rope.chars_at(char_idx + 1);
+        let mut index = char_idx + 1;
+        while let Some(current) = chars.prev() {
+            index -= 1;
+            if char_idx - index > MAX_SCAN {
+                break;
+            }
+            if current == *close {
+                depth += 1;
+            } else if current == *open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+        }
+    }
+    None
+}
+
+// The bracket the cursor sits on, or the one right before it, with its match
+pub fn get_bracket_match(code: &Code) -> Option<(usize, usize)> {
+    let at = code.point_to_char(code.get_cursor());
+    find_match(code, at)
+    .map(|other| (at, other))
+    .or_else(|| at.checked_sub(1).and_then(|before| find_match(code, before).map(|other| (before, other))))
+}
+
+// Moves the cursor on the bracket matching the one under it
+pub fn handle_jump_to_match(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    if let Some((_, other)) = get_bracket_match(code) {
+        *code.get_mut_cursor() = code.char_to_point(other);
+        code.flush_selection();
+    }
+}
+
+// Types a closer over the same char right after the cursor, or an opener with its closer after it.
+// Returns false for the chars it does not handle, which are inserted as usual
+pub fn handle_pair_char(code_component: &mut CodeComponent, char: &str) -> bool {
+    let mut chars = char.chars();
+    let (Some(char), None) = (chars.next(), chars.next()) else {
+        return false;
+    };
+    let code = &mut code_component.current;
+    let at = code.point_to_char(code.get_cursor());
+    let next = char_at(code, at);
+    let previous = at.checked_sub(1).and_then(|before| char_at(code, before));
+
+    let is_closer = BRACKETS.iter().any(|(_, close)| *close == char) || QUOTES.contains(&char);
+    if is_closer && next == Some(char) {
+        *code.get_mut_cursor() = code.char_to_point(at + 1);
+        return true;
+    }
+
+    let Some(close) = closing_pair(char) else {
+        return false;
+    };
+    //pairs are added only before blanks or closers, and quotes not right after a word like in don't
+    let before_blank = next.is_none_or(|next| next.is_whitespace() || BRACKETS.iter().any(|(_, close)| *close == next));
+    let after_word = previous.is_some_and(|previous| previous.is_alphanumeric() || previous == '_');
+    if !before_blank || (QUOTES.contains(&char) && after_word) {
+        return false;
+    }
+    code.insert(at, &format!("{}{}", char, close));
+    *code.get_mut_cursor() = code.char_to_point(at + 1);
+    true
+}
+
+// Removes the closer right after the cursor too, when Backspace deletes the opener of an empty pair
+pub fn delete_empty_pair(code_component: &mut CodeComponent) -> bool {
+    let code = &mut code_component.current;
+    let at = code.point_to_char(code.get_cursor());
+    let Some(before) = at.checked_sub(1) else {
+        return false;
+    };
+    match (char_at(code, before).and_then(closing_pair), char_at(code, at)) {
+        (Some(close), Some(next)) if close == next => {
+            code.remove(before..at + 1);
+            *code.get_mut_cursor() = code.char_to_point(before);
+            true
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,235 @@
+use std::{mem, path::PathBuf};
+
+use crossterm::event::KeyEvent;
+
+use crate::state::{AppContext, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
+
+use super::{CodeComponent, code::Code, code_format::CodeFormat, code_highlight::CodeHighlighter, code_history::{CodeHistory, HistoryPopup}, code_indent::IndentStyle, code_search::CodeSearch, code_viewport::CodeViewport};
+
+// A file open in the editor with its own cursor, selection and history.
+// The buffer shown lives in the fields of the code component, the others wait here
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodeBuffer {
+    current: Code,
+    history: CodeHistory,
+    history_popup: Option<HistoryPopup>,
+    path: Option<PathBuf>,
+    format: CodeFormat,
+    saved_format: CodeFormat,
+    search: Option<CodeSearch>,
+    highlighter: CodeHighlighter,
+    indent: IndentStyle,
+    viewport: CodeViewport,
+}
+
+impl Default for CodeBuffer {
+    // A buffer without a file
+    fn default() -> CodeBuffer {
+        let code = Code::new();
+        CodeBuffer {
+            history: CodeHistory::new(code.clone()),
+            current: code,
+            history_popup: None,
+            path: None,
+            format: CodeFormat::default(),
+            saved_format: CodeFormat::default(),
+            search: None,
+            highlighter: CodeHighlighter::default(),
+            indent: IndentStyle::default(),
+            viewport: CodeViewport::default(),
+        }
+    }
+}
+
+impl CodeBuffer {
+
+    pub fn new() -> CodeBuffer {
+        CodeBuffer::default()
+    }
+
+    pub fn get_path(&self) -> &Option<PathBuf> {
+        &self.path
+    }
+
+    pub fn is_modified(&self) -> bool {
+        !self.history.is_saved() || self.format != self.saved_format
+    }
+
+    pub fn get_history(&self) -> &CodeHistory {
+        &self.history
+    }
+
+    pub fn get_current(&self) -> &Code {
+        &self.current
+    }
+
+    pub fn get_highlighter(&self) -> &CodeHighlighter {
+        &self.highlighter
+    }
+}
+
+// Name shown in the tab bar and in the picker for a buffer
+pub fn buffer_name(path: &Option<PathBuf>) -> String {
+    path
+    .as_ref()
+    .and_then(|path| path.file_name())
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or("untitled".to_string())
+}
+
+// Moves the buffer shown by the component out of it
+fn park(code_component: &mut CodeComponent) -> CodeBuffer {
+    let empty = CodeBuffer::new();
+    CodeBuffer {
+        current: mem::replace(&mut code_component.current, empty.current),
+        history: mem::replace(&mut code_component.history, empty.history),
+        history_popup: code_component.history_popup.take(),
+        path: code_component.path.take(),
+        format: mem::take(&mut code_component.format),
+        saved_format: mem::take(&mut code_component.saved_format),
+        search: code_component.search.take(),
+        highlighter: mem::take(&mut code_component.highlighter),
+        indent: code_component.indent,
+        viewport: code_component.viewport.clone(),
+    }
+}
+
+// Shows a buffer in the component, in the viewport size of the panel
+fn load(code_component: &mut CodeComponent, mut buffer: CodeBuffer) {
+    buffer.viewport.set_size(code_component.viewport.get_width(), code_component.viewport.get_height());
+    code_component.current = buffer.current;
+    code_component.history = buffer.history;
+    code_component.history_popup = buffer.history_popup;
+    code_component.path = buffer.path;
+    code_component.format = buffer.format;
+    code_component.saved_format = buffer.saved_format;
+    code_component.search = buffer.search;
+    code_component.highlighter = buffer.highlighter;
+    code_component.indent = buffer.indent;
+    code_component.viewport = buffer.viewport;
+}
+
+// Shows the buffer at `index` of the tab bar, keeping the one shown before as it is
+pub fn switch_buffer(code_component: &mut CodeComponent, index: usize) {
+    if index == code_component.active || index >= code_component.buffers.len() {
+        return;
+    }
+    let active = code_component.active;
+    code_component.buffers[active] = park(code_component);
+    let buffer = mem::replace(&mut code_component.buffers[index], CodeBuffer::new());
+    load(code_component, buffer);
+    code_component.active = index;
+}
+
+// Adds an empty buffer after the one shown and shows it
+pub fn open_empty_buffer(code_component: &mut CodeComponent) {
+    let active = code_component.active;
+    code_component.buffers[active] = park(code_component);
+    code_component.buffers.insert(active + 1, CodeBuffer::new());
+    code_component.panes.buffer_inserted(active + 1);
+    code_component.active = active + 1;
+    code_component.viewport.reset();
+}
+
+// Closes the buffer shown, dropping its changes, and shows the next one. Closing the last buffer leaves an empty one
+pub fn close_buffer(code_component: &mut CodeComponent) {
+    let active = code_component.active;
+    code_component.buffers.remove(active);
+    code_component.panes.buffer_removed(active, code_component.buffers.len().max(1));
+    let buffer = if code_component.buffers.is_empty() {
+        code_component.buffers.push(CodeBuffer::new());
+        CodeBuffer::new()
+    } else {
+        code_component.active = active.min(code_component.buffers.len() - 1);
+        mem::replace(&mut code_component.buffers[code_component.active], CodeBuffer::new())
+    };
+    load(code_component, buffer);
+}
+
+// Moves the buffer shown one place left or right in the tab bar
+pub fn move_buffer(code_component: &mut CodeComponent, right: bool) {
+    let active = code_component.active;
+    let target = if right { active + 1 } else { active.wrapping_sub(1) };
+    if target < code_component.buffers.len() {
+        code_component.buffers.swap(active, target);
+        code_component.panes.buffers_swapped(active, target);
+        code_component.active = target;
+    }
+}
+
+// Shows the next buffer of the tab bar, or the previous one, going around at the ends
+pub fn cycle_buffer(code_component: &mut CodeComponent, next: bool) {
+    let count = code_component.buffers.len();
+    let index = if next { (code_component.active + 1) % count } else { (code_component.active + count - 1) % count };
+    switch_buffer(code_component, index);
+}
+
+// Query typed in the buffer picker and the match chosen among the buffers whose name contains it
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BufferPicker {
+    query: String,
+    selected: usize,
+}
+
+impl BufferPicker {
+
+    pub fn new() -> BufferPicker {
+        BufferPicker::default()
+    }
+
+    pub fn get_query(&self) -> &String {
+        &self.query
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    // Indexes of the buffers whose name contains the query, ignoring the case
+    pub fn matches(&self, names: &[String]) -> Vec<usize> {
+        let query = self.query.to_lowercase();
+        names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().contains(&query))
+        .map(|(index, _)| index)
+        .collect()
+    }
+}
+
+// Keys of the buffer picker, resolved in its keymap scope: keys without an action filter the buffers
+pub fn handle_buffer_picker(code_component: &mut CodeComponent, context: &mut AppContext, key: KeyEvent) {
+    let names = code_component.get_buffer_names();
+    let Some(picker) = &mut code_component.buffer_picker else {
+        return;
+    };
+    let matches = picker.matches(&names);
+    match context.resolve_key(KeyScope::BufferPicker, key) {
+        KeyMatch::Action(Action::MoveUp) if !matches.is_empty() => {
+            picker.selected = (picker.selected + matches.len() - 1) % matches.len();
+        },
+        KeyMatch::Action(Action::MoveDown) if !matches.is_empty() => {
+            picker.selected = (picker.selected + 1) % matches.len();
+        },
+        KeyMatch::Action(Action::DeleteBackward) => {
+            picker.query.pop();
+            picker.selected = 0;
+        },
+        KeyMatch::Action(Action::Confirm) => {
+            if let Some(index) = matches.get(picker.selected).copied() {
+                code_component.buffer_picker = None;
+                switch_buffer(code_component, index);
+            }
+        },
+        KeyMatch::Action(Action::Leave) => {
+            code_component.buffer_picker = None;
+        },
+        KeyMatch::Unbound => {
+            if let Some(char) = typed_char(&key) {
+                picker.query.push(char);
+                picker.selected = 0;
+            }
+        },
+        _ => {}
+    }
+}
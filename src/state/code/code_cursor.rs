@@ -0,0 +1,249 @@
+use std::ops::Range;
+
+use super::{CodeComponent, code::Code, code_history::Operation, code_selection::CodeSelection, code_utils::Point};
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CodeCursor {
+    position: Point,
+    selection: Option<CodeSelection>,
+}
+
+impl CodeCursor {
+
+    pub fn new(position: Point, selection: Option<CodeSelection>) -> CodeCursor {
+        CodeCursor {
+            position,
+            selection
+        }
+    }
+
+    pub fn get_position(&self) -> &Point {
+        &self.position
+    }
+
+    pub fn get_selection(&self) -> &Option<CodeSelection> {
+        &self.selection
+    }
+
+}
+
+// A cursor as char indexes of the rope, so it can follow the edits made at the other cursors
+#[derive(Clone)]
+struct CharCursor {
+    position: usize,
+    selection: Option<(usize, usize)>,
+}
+
+impl CharCursor {
+
+    fn from_cursor(code: &Code, cursor: &CodeCursor) -> CharCursor {
+        CharCursor {
+            position: code.point_to_char(cursor.get_position()),
+            selection: cursor.get_selection().as_ref().map(|selection| (code.point_to_char(selection.get_start()), code.point_to_char(selection.get_end()))),
+        }
+    }
+
+    fn to_cursor(&self, code: &Code) -> CodeCursor {
+        CodeCursor::new(
+            code.char_to_point(self.position),
+            self.selection.map(|(start, end)| CodeSelection::new(code.char_to_point(start), code.char_to_point(end))),
+        )
+    }
+
+    fn map(&mut self, operation: &Operation) {
+        self.position = map_position(self.position, operation);
+        if let Some((start, end)) = self.selection {
+            self.selection = Some((map_position(start, operation), map_position(end, operation)));
+        }
+    }
+}
+
+// Moves a char index after an edit made before or around it
+fn map_position(position: usize, operation: &Operation) -> usize {
+    let len = operation.get_text().chars().count();
+    match operation {
+        Operation::Insert { at, .. } if position >= *at => position + len,
+        Operation::Delete { at, .. } if position >= *at + len => position - len,
+        Operation::Delete { at, .. } if position > *at => *at,
+        _ => position,
+    }
+}
+
+// Runs `handler` once for every cursor, in the order they appear in the text.
+// Each cursor is made the main one while the handler runs, the others follow its edits.
+pub fn for_each_cursor<F: FnMut(&mut CodeComponent)>(code_component: &mut CodeComponent, mut handler: F) {
+    if code_component.current.get_cursors().is_empty() {
+        handler(code_component);
+        return;
+    }
+
+    let code = &code_component.current;
+    let mut cursors: Vec<(bool, CharCursor)> = code
+    .get_all_cursors()
+    .iter()
+    .enumerate()
+    .map(|(index, cursor)| (index == 0, CharCursor::from_cursor(code, cursor)))
+    .collect();
+    cursors.sort_by_key(|(_, cursor)| cursor.position);
+
+    for index in 0..cursors.len() {
+        let current = cursors[index].1.to_cursor(&code_component.current);
+        *code_component.current.get_mut_cursor() = current.get_position().clone();
+        code_component.current.set_selection(current.get_selection().clone());
+
+        let applied = code_component.current.get_operations().len();
+        handler(code_component);
+
+        let code = &code_component.current;
+        for operation in code.get_operations()[applied..].iter() {
+            for (other, (_, cursor)) in cursors.iter_mut().enumerate() {
+                if other != index {
+                    cursor.map(operation);
+                }
+            }
+        }
+        let moved = CodeCursor::new(code.get_cursor().clone(), code.get_selection().clone());
+        cursors[index].1 = CharCursor::from_cursor(code, &moved);
+    }
+
+    //cursors that reached the same place become one
+    let mut positions: Vec<usize> = vec![];
+    cursors.retain(|(main, cursor)| {
+        let duplicate = positions.contains(&cursor.position) && !main;
+        positions.push(cursor.position);
+        !duplicate
+    });
+    set_cursors(code_component, cursors);
+}
+
+// Makes the cursor flagged as main the cursor of the code and keeps the others as added cursors
+fn set_cursors(code_component: &mut CodeComponent, cursors: Vec<(bool, CharCursor)>) {
+    let code = &mut code_component.current;
+    let mut others = vec![];
+    let mut main_position = None;
+    for (main, cursor) in cursors {
+        let cursor = cursor.to_cursor(code);
+        if main {
+            *code.get_mut_cursor() = cursor.get_position().clone();
+            code.set_selection(cursor.get_selection().clone());
+            main_position = Some(cursor.get_position().clone());
+        } else {
+            others.push(cursor);
+        }
+    }
+    others.retain(|cursor| Some(cursor.get_position()) != main_position.as_ref());
+    code.set_cursors(others);
+}
+
+// Selected text of every cursor in the order they appear in the text
+pub fn get_selected_texts(code: &Code) -> Vec<String> {
+    let mut ranges: Vec<Range<usize>> = code
+    .get_all_cursors()
+    .iter()
+    .filter_map(|cursor| cursor.get_selection().as_ref())
+    .map(|selection| {
+        let start = code.point_to_char(selection.get_start().min(selection.get_end()));
+        let end = code.point_to_char(selection.get_start().max(selection.get_end()));
+        start..end
+    })
+    .filter(|range| !range.is_empty())
+    .collect();
+    ranges.sort_by_key(|range| range.start);
+    ranges.into_iter().map(|range| code.get_slice(range)).collect()
+}
+
+// Adds a cursor on the line above the first cursor, or below the last one, on the same screen column
+pub fn handle_add_cursor_vertical(code_component: &mut CodeComponent, below: bool) {
+    let code = &code_component.current;
+    let cursors = code.get_all_cursors();
+    let positions = cursors.iter().map(|cursor| cursor.get_position());
+    let from = if below { positions.max() } else { positions.min() };
+    if let Some(from) = from {
+        let target = if below {
+            from.get_x() + 1
+        } else if from.get_x() > 0 {
+            from.get_x() - 1
+        } else {
+            return;
+        };
+        if let (Some(line), Some(target_line)) = (code.get_line(from.get_x()), code.get_line(target)) {
+            let column = target_line.column_at_width(line.width(from.get_y()));
+            let cursor = CodeCursor::new(Point::new(target, column), None);
+            code_component.current.add_cursor(cursor);
+        }
+    }
+}
+
+// Selects the word under the cursor, or adds a cursor on the next occurrence of the selected text
+pub fn handle_add_next_occurrence(code_component: &mut CodeComponent) {
+    let code = &code_component.current;
+    let Some(text) = code.get_selected_text().filter(|text| !text.is_empty()) else {
+        select_word(&mut code_component.current);
+        return;
+    };
+
+    let selected: Vec<Range<usize>> = code
+    .get_all_cursors()
+    .iter()
+    .filter_map(|cursor| cursor.get_selection().clone())
+    .map(|selection| code.point_to_char(selection.get_start().min(selection.get_end()))..code.point_to_char(selection.get_start().max(selection.get_end())))
+    .collect();
+    let last = code.get_cursors().last().map(|cursor| cursor.get_position().clone()).unwrap_or(code.get_cursor().clone());
+    let after = code.point_to_char(&last);
+
+    //the first occurrence after the last added cursor, starting again from the top of the text
+    let matches = code.find_all(text.as_str());
+    let next = matches
+    .iter()
+    .filter(|range| range.start >= after)
+    .chain(matches.iter().filter(|range| range.start < after))
+    .find(|range| !selected.contains(range));
+
+    if let Some(range) = next {
+        let start = code.char_to_point(range.start);
+        let end = code.char_to_point(range.end);
+        code_component.current.add_cursor(CodeCursor::new(end.clone(), Some(CodeSelection::new(start, end))));
+    }
+}
+
+// Puts a cursor on every occurrence of the selected text, or of the word under the cursor
+pub fn handle_add_all_occurrences(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    if code.get_selected_text().filter(|text| !text.is_empty()).is_none() {
+        select_word(code);
+    }
+    let Some(text) = code.get_selected_text().filter(|text| !text.is_empty()) else {
+        return;
+    };
+
+    let main = code.get_selection_range().map(|range| range.start).unwrap_or(0);
+    let mut cursors: Vec<CodeCursor> = code
+    .find_all(text.as_str())
+    .into_iter()
+    .map(|range| {
+        let start = code.char_to_point(range.start);
+        let end = code.char_to_point(range.end);
+        CodeCursor::new(end.clone(), Some(CodeSelection::new(start, end)))
+    })
+    .collect();
+
+    //the occurrence selected before stays the main cursor
+    let main_index = cursors.iter().position(|cursor| {
+        cursor.get_selection().as_ref().is_some_and(|selection| code.point_to_char(selection.get_start()) == main)
+    }).unwrap_or(0);
+    if !cursors.is_empty() {
+        let main_cursor = cursors.remove(main_index);
+        *code.get_mut_cursor() = main_cursor.get_position().clone();
+        code.set_selection(main_cursor.get_selection().clone());
+        code.set_cursors(cursors);
+    }
+}
+
+fn select_word(code: &mut Code) {
+    if let Some(range) = code.word_range_at(&code.get_cursor().clone()) {
+        let start = code.char_to_point(range.start);
+        let end = code.char_to_point(range.end);
+        *code.get_mut_cursor() = end.clone();
+        code.create_selection(start, end);
+    }
+}
@@ -0,0 +1,116 @@
+const BOM: char = '\u{feff}';
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    pub fn toggle(&self) -> LineEnding {
+        match self {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        }
+    }
+}
+
+// How the text of a file is written on disk, the buffer always uses "\n" without BOM and final newline
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CodeFormat {
+    line_ending: LineEnding,
+    bom: bool,
+    final_newline: bool,
+    // the file uses both line endings, the buffer writes only `line_ending`
+    mixed_line_endings: bool,
+}
+
+impl CodeFormat {
+
+    pub fn new(line_ending: LineEnding, bom: bool, final_newline: bool) -> CodeFormat {
+        CodeFormat {
+            line_ending,
+            bom,
+            final_newline,
+            mixed_line_endings: false,
+        }
+    }
+
+    // Detects the format of the text of a file and returns it with the text for the buffer.
+    // A file mixing line endings takes the most used one and is marked as mixed.
+    pub fn detect(text: &str) -> (CodeFormat, String) {
+        let (bom, text) = match text.strip_prefix(BOM) {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        let line_ending = if crlf > lf { LineEnding::Crlf } else { LineEnding::Lf };
+
+        let mut text = text.replace("\r\n", "\n");
+        let final_newline = text.ends_with('\n');
+        if final_newline {
+            text.pop();
+        }
+        let mut format = CodeFormat::new(line_ending, bom, final_newline);
+        format.mixed_line_endings = crlf > 0 && lf > 0;
+        (format, text)
+    }
+
+    // The format the buffer writes, with a single line ending
+    pub fn unified(&self) -> CodeFormat {
+        CodeFormat::new(self.line_ending, self.bom, self.final_newline)
+    }
+
+    // Text to write on disk for the text of the buffer
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len() + 4);
+        if self.bom {
+            result.push(BOM);
+        }
+        match self.line_ending {
+            LineEnding::Lf => result.push_str(text),
+            LineEnding::Crlf => result.push_str(&text.replace('\n', "\r\n")),
+        }
+        if self.final_newline {
+            result.push_str(self.line_ending.as_str());
+        }
+        result
+    }
+
+    pub fn get_line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        self.final_newline
+    }
+
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+}
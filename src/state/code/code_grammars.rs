@@ -0,0 +1,633 @@
+use super::code_highlight::{Grammar, LineState, Token, TokenKind};
+
+// Reads a line one char at a time, collecting the tokens found with their byte ranges
+struct Scanner<'a> {
+    line: &'a str,
+    position: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Scanner<'a> {
+
+    fn new(line: &'a str) -> Scanner<'a> {
+        Scanner {
+            line,
+            position: 0,
+            tokens: vec![]
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn previous(&self) -> Option<char> {
+        self.line[..self.position].chars().next_back()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.rest().starts_with(prefix)
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.line.len()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.position = (self.position + bytes).min(self.line.len());
+    }
+
+    fn eat_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.line[start..self.position]
+    }
+
+    fn skip_to_end(&mut self) {
+        self.position = self.line.len();
+    }
+
+    // Moves after `pattern`, or to the end of the line if it is not there. Returns if it was found
+    fn skip_past(&mut self, pattern: &str) -> bool {
+        match self.rest().find(pattern) {
+            Some(index) => {
+                self.advance(index + pattern.len());
+                true
+            },
+            None => {
+                self.skip_to_end();
+                false
+            }
+        }
+    }
+
+    // Moves after the closing quote of a string, skipping the escaped chars. Returns if the string was closed
+    fn skip_string(&mut self, quote: char, escapes: bool) -> bool {
+        while let Some(char) = self.bump() {
+            if char == '\\' && escapes {
+                self.bump();
+            } else if char == quote {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn push(&mut self, start: usize, kind: TokenKind) {
+        if start < self.position {
+            self.tokens.push(Token::new(start..self.position, kind));
+        }
+    }
+
+    fn finish(self, state: LineState) -> (Vec<Token>, LineState) {
+        (self.tokens, state)
+    }
+}
+
+fn is_identifier(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+// Digits with their prefixes, suffixes and separators, a dot only when a digit follows it
+fn skip_number(scanner: &mut Scanner) {
+    while let Some(char) = scanner.peek() {
+        if char.is_alphanumeric() || char == '_' || (char == '.' && scanner.peek_nth(1).is_some_and(|next| next.is_ascii_digit())) {
+            scanner.bump();
+        } else {
+            break;
+        }
+    }
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for", "if", "impl", "in",
+    "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+const RUST_PRIMITIVES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32", "f64", "bool", "char", "str",
+];
+
+const RUST_STRING: LineState = 1;
+// raw strings keep the number of # closing them, block comments their nesting depth
+const RUST_RAW_STRING: LineState = 0x100;
+const RUST_COMMENT: LineState = 0x10000;
+
+#[derive(Debug)]
+pub struct RustGrammar;
+
+impl RustGrammar {
+
+    // Skips a block comment already opened `depth` times, returns the depth left at the end of the line
+    fn skip_comment(scanner: &mut Scanner, mut depth: u32) -> u32 {
+        while !scanner.is_done() && depth > 0 {
+            if scanner.starts_with("/*") {
+                depth += 1;
+                scanner.advance(2);
+            } else if scanner.starts_with("*/") {
+                depth -= 1;
+                scanner.advance(2);
+            } else {
+                scanner.bump();
+            }
+        }
+        depth
+    }
+
+    fn skip_raw_string(scanner: &mut Scanner, hashes: usize) -> bool {
+        scanner.skip_past(&format!("\"{}", "#".repeat(hashes)))
+    }
+
+    // Number of # of a raw string starting at the scanner, with the length of its opening
+    fn raw_string_start(scanner: &Scanner) -> Option<(usize, usize)> {
+        let prefix = if scanner.starts_with("br") { 2 } else if scanner.starts_with("r") { 1 } else { return None };
+        let rest = &scanner.rest()[prefix..];
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        rest[hashes..].starts_with('"').then_some((hashes, prefix + hashes + 1))
+    }
+}
+
+impl Grammar for RustGrammar {
+
+    fn get_name(&self) -> &'static str {
+        "Rust"
+    }
+
+    fn get_extensions(&self) -> &'static [&'static str] {
+        &["rs"]
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+        let mut scanner = Scanner::new(line);
+
+        //the end of what the previous line left open
+        if state >= RUST_COMMENT {
+            let depth = Self::skip_comment(&mut scanner, state - RUST_COMMENT);
+            scanner.push(0, TokenKind::Comment);
+            if depth > 0 {
+                return scanner.finish(RUST_COMMENT + depth);
+            }
+        } else if state >= RUST_RAW_STRING {
+            let closed = Self::skip_raw_string(&mut scanner, (state - RUST_RAW_STRING) as usize);
+            scanner.push(0, TokenKind::String);
+            if !closed {
+                return scanner.finish(state);
+            }
+        } else if state == RUST_STRING {
+            let closed = scanner.skip_string('"', true);
+            scanner.push(0, TokenKind::String);
+            if !closed {
+                return scanner.finish(state);
+            }
+        }
+
+        while let Some(char) = scanner.peek() {
+            let start = scanner.position;
+            if scanner.starts_with("//") {
+                scanner.skip_to_end();
+                scanner.push(start, TokenKind::Comment);
+            } else if scanner.starts_with("/*") {
+                scanner.advance(2);
+                let depth = Self::skip_comment(&mut scanner, 1);
+                scanner.push(start, TokenKind::Comment);
+                if depth > 0 {
+                    return scanner.finish(RUST_COMMENT + depth);
+                }
+            } else if let Some((hashes, opening)) = Self::raw_string_start(&scanner) {
+                scanner.advance(opening);
+                let closed = Self::skip_raw_string(&mut scanner, hashes);
+                scanner.push(start, TokenKind::String);
+                if !closed {
+                    return scanner.finish(RUST_RAW_STRING + hashes as u32);
+                }
+            } else if char == '"' || scanner.starts_with("b\"") {
+                scanner.skip_past("\"");
+                let closed = scanner.skip_string('"', true);
+                scanner.push(start, TokenKind::String);
+                if !closed {
+                    return scanner.finish(RUST_STRING);
+                }
+            } else if char == '\'' {
+                //a char literal closes right after its char, a lifetime does not
+                scanner.bump();
+                let is_char = match scanner.peek() {
+                    Some('\\') => true,
+                    Some(_) => scanner.peek_nth(1) == Some('\''),
+                    None => false,
+                };
+                if is_char {
+                    scanner.skip_string('\'', true);
+                    scanner.push(start, TokenKind::String);
+                } else {
+                    scanner.eat_while(is_identifier);
+                    scanner.push(start, TokenKind::Variable);
+                }
+            } else if char.is_ascii_digit() {
+                skip_number(&mut scanner);
+                scanner.push(start, TokenKind::Number);
+            } else if char.is_alphabetic() || char == '_' {
+                let word = scanner.eat_while(is_identifier);
+                let next = scanner.rest().trim_start().chars().next();
+                let kind = if RUST_KEYWORDS.contains(&word) {
+                    Some(TokenKind::Keyword)
+                } else if word == "true" || word == "false" {
+                    Some(TokenKind::Constant)
+                } else if scanner.peek() == Some('!') && scanner.peek_nth(1) != Some('=') {
+                    scanner.bump();
+                    Some(TokenKind::Function)
+                } else if RUST_PRIMITIVES.contains(&word) {
+                    Some(TokenKind::Type)
+                } else if word.len() > 1 && word.chars().all(|char| char.is_uppercase() || char.is_ascii_digit() || char == '_') {
+                    Some(TokenKind::Constant)
+                } else if word.starts_with(char::is_uppercase) {
+                    Some(TokenKind::Type)
+                } else if next == Some('(') {
+                    Some(TokenKind::Function)
+                } else {
+                    None
+                };
+                if let Some(kind) = kind {
+                    scanner.push(start, kind);
+                }
+            } else if scanner.starts_with("#[") || scanner.starts_with("#![") {
+                let mut depth = 0;
+                while let Some(char) = scanner.bump() {
+                    if char == '[' {
+                        depth += 1;
+                    } else if char == ']' {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                scanner.push(start, TokenKind::Attribute);
+            } else {
+                scanner.bump();
+            }
+        }
+        scanner.finish(0)
+    }
+}
+
+const TOML_BASIC_STRING: LineState = 1;
+const TOML_LITERAL_STRING: LineState = 2;
+
+#[derive(Debug)]
+pub struct TomlGrammar;
+
+impl TomlGrammar {
+
+    // The key of the line or of an inline table, before its =
+    fn key_end(scanner: &Scanner) -> Option<usize> {
+        let rest = scanner.rest();
+        let mut quote = None;
+        for (index, char) in rest.char_indices() {
+            match (quote, char) {
+                (Some(open), char) if char == open => quote = None,
+                (Some(_), _) => {},
+                (None, '"') | (None, '\'') => quote = Some(char),
+                (None, '=') => return Some(rest[..index].trim_end().len()),
+                (None, char) if char.is_alphanumeric() || "_-. ".contains(char) => {},
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+impl Grammar for TomlGrammar {
+
+    fn get_name(&self) -> &'static str {
+        "TOML"
+    }
+
+    fn get_extensions(&self) -> &'static [&'static str] {
+        &["toml"]
+    }
+
+    fn get_file_names(&self) -> &'static [&'static str] {
+        &["Cargo.lock"]
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+        let mut scanner = Scanner::new(line);
+        if state == TOML_BASIC_STRING || state == TOML_LITERAL_STRING {
+            let closed = scanner.skip_past(if state == TOML_BASIC_STRING { "\"\"\"" } else { "'''" });
+            scanner.push(0, TokenKind::String);
+            if !closed {
+                return scanner.finish(state);
+            }
+        } else {
+            scanner.eat_while(char::is_whitespace);
+            let start = scanner.position;
+            if scanner.starts_with("[") {
+                let end = scanner.rest().find(']').map(|index| index + 1).unwrap_or(scanner.rest().len());
+                let end = if scanner.rest()[end..].starts_with(']') { end + 1 } else { end };
+                scanner.advance(end);
+                scanner.push(start, TokenKind::Heading);
+            }
+        }
+
+        let mut expects_key = state == 0;
+        while let Some(char) = scanner.peek() {
+            if expects_key {
+                scanner.eat_while(char::is_whitespace);
+                let start = scanner.position;
+                if let Some(end) = Self::key_end(&scanner) {
+                    scanner.advance(end);
+                    scanner.push(start, TokenKind::Property);
+                }
+                expects_key = false;
+                continue;
+            }
+            let start = scanner.position;
+            if char == '#' {
+                scanner.skip_to_end();
+                scanner.push(start, TokenKind::Comment);
+            } else if scanner.starts_with("\"\"\"") || scanner.starts_with("'''") {
+                let (delimiter, open_state) = if char == '"' { ("\"\"\"", TOML_BASIC_STRING) } else { ("'''", TOML_LITERAL_STRING) };
+                scanner.advance(3);
+                let closed = scanner.skip_past(delimiter);
+                scanner.push(start, TokenKind::String);
+                if !closed {
+                    return scanner.finish(open_state);
+                }
+            } else if char == '"' || char == '\'' {
+                scanner.bump();
+                scanner.skip_string(char, char == '"');
+                scanner.push(start, TokenKind::String);
+            } else if char.is_ascii_digit() || ((char == '-' || char == '+') && scanner.peek_nth(1).is_some_and(|next| next.is_ascii_digit())) {
+                scanner.bump();
+                scanner.eat_while(|char| char.is_alphanumeric() || "_.:-+".contains(char));
+                scanner.push(start, TokenKind::Number);
+            } else if char.is_alphabetic() {
+                let word = scanner.eat_while(is_identifier);
+                if ["true", "false", "inf", "nan"].contains(&word) {
+                    scanner.push(start, TokenKind::Constant);
+                }
+            } else {
+                scanner.bump();
+                //the keys of an inline table follow its braces and commas
+                expects_key = char == '{' || (char == ',' && line[..start].contains('{'));
+            }
+        }
+        scanner.finish(0)
+    }
+}
+
+const MARKDOWN_FENCE: LineState = 1;
+
+#[derive(Debug)]
+pub struct MarkdownGrammar;
+
+impl MarkdownGrammar {
+
+    fn highlight_inline(scanner: &mut Scanner) {
+        while let Some(char) = scanner.peek() {
+            let start = scanner.position;
+            if char == '\\' {
+                scanner.bump();
+                scanner.bump();
+            } else if char == '`' {
+                scanner.bump();
+                if scanner.skip_past("`") {
+                    scanner.push(start, TokenKind::String);
+                }
+            } else if scanner.starts_with("**") || scanner.starts_with("__") {
+                let delimiter = &scanner.rest()[..2];
+                if let Some(end) = scanner.rest()[2..].find(delimiter) {
+                    scanner.advance(end + 4);
+                    scanner.push(start, TokenKind::Emphasis);
+                } else {
+                    scanner.advance(2);
+                }
+            } else if (char == '*' || char == '_') && !scanner.previous().is_some_and(char::is_alphanumeric) {
+                let closing = scanner.rest()[1..].find(char).filter(|end| *end > 0);
+                if let Some(end) = closing {
+                    scanner.advance(end + 2);
+                    scanner.push(start, TokenKind::Emphasis);
+                } else {
+                    scanner.bump();
+                }
+            } else if char == '[' || scanner.starts_with("![") {
+                let rest = scanner.rest();
+                let link = rest.find("](").and_then(|middle| rest[middle..].find(')').map(|end| middle + end + 1));
+                if let Some(end) = link {
+                    scanner.advance(end);
+                    scanner.push(start, TokenKind::Link);
+                } else {
+                    scanner.bump();
+                }
+            } else if scanner.starts_with("<http") {
+                if scanner.skip_past(">") {
+                    scanner.push(start, TokenKind::Link);
+                }
+            } else {
+                scanner.bump();
+            }
+        }
+    }
+}
+
+impl Grammar for MarkdownGrammar {
+
+    fn get_name(&self) -> &'static str {
+        "Markdown"
+    }
+
+    fn get_extensions(&self) -> &'static [&'static str] {
+        &["md", "markdown"]
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+        let mut scanner = Scanner::new(line);
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+
+        if state == MARKDOWN_FENCE {
+            scanner.skip_to_end();
+            if is_fence {
+                scanner.push(0, TokenKind::Keyword);
+                return scanner.finish(0);
+            }
+            scanner.push(0, TokenKind::String);
+            return scanner.finish(MARKDOWN_FENCE);
+        }
+        if is_fence {
+            scanner.skip_to_end();
+            scanner.push(0, TokenKind::Keyword);
+            return scanner.finish(MARKDOWN_FENCE);
+        }
+
+        scanner.eat_while(char::is_whitespace);
+        let start = scanner.position;
+        let hashes = trimmed.len() - trimmed.trim_start_matches('#').len();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].chars().next().is_none_or(char::is_whitespace) {
+            scanner.skip_to_end();
+            scanner.push(start, TokenKind::Heading);
+        } else if trimmed.starts_with('>') {
+            scanner.skip_to_end();
+            scanner.push(start, TokenKind::Comment);
+        } else if trimmed.len() >= 3 && ["-", "*", "_"].iter().any(|rule| trimmed.trim_end().chars().all(|char| rule.starts_with(char))) {
+            scanner.skip_to_end();
+            scanner.push(start, TokenKind::Keyword);
+        } else {
+            //list items start with a bullet or a number
+            let digits = trimmed.len() - trimmed.trim_start_matches(|char: char| char.is_ascii_digit()).len();
+            let marker = if ["- ", "* ", "+ "].iter().any(|bullet| trimmed.starts_with(bullet)) {
+                1
+            } else if digits > 0 && (trimmed[digits..].starts_with(". ") || trimmed[digits..].starts_with(") ")) {
+                digits + 1
+            } else {
+                0
+            };
+            scanner.advance(marker);
+            scanner.push(start, TokenKind::Keyword);
+            Self::highlight_inline(&mut scanner);
+        }
+        scanner.finish(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonGrammar;
+
+impl Grammar for JsonGrammar {
+
+    fn get_name(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn get_extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn highlight_line(&self, line: &str, _state: LineState) -> (Vec<Token>, LineState) {
+        let mut scanner = Scanner::new(line);
+        while let Some(char) = scanner.peek() {
+            let start = scanner.position;
+            if char == '"' {
+                scanner.bump();
+                scanner.skip_string('"', true);
+                //a string followed by : is the key of an object
+                let kind = if scanner.rest().trim_start().starts_with(':') { TokenKind::Property } else { TokenKind::String };
+                scanner.push(start, kind);
+            } else if char.is_ascii_digit() || char == '-' {
+                scanner.bump();
+                scanner.eat_while(|char| char.is_ascii_digit() || "eE.+-".contains(char));
+                scanner.push(start, TokenKind::Number);
+            } else if char.is_alphabetic() {
+                let word = scanner.eat_while(char::is_alphanumeric);
+                if ["true", "false", "null"].contains(&word) {
+                    scanner.push(start, TokenKind::Constant);
+                }
+            } else {
+                scanner.bump();
+            }
+        }
+        scanner.finish(0)
+    }
+}
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "in", "while", "until", "do", "done", "case", "esac", "function", "select", "return",
+    "exit", "local", "export", "readonly", "declare", "unset", "source", "alias", "break", "continue", "shift", "trap", "eval", "exec", "set",
+];
+
+const SHELL_DOUBLE_STRING: LineState = 1;
+const SHELL_SINGLE_STRING: LineState = 2;
+
+#[derive(Debug)]
+pub struct ShellGrammar;
+
+impl Grammar for ShellGrammar {
+
+    fn get_name(&self) -> &'static str {
+        "Shell"
+    }
+
+    fn get_extensions(&self) -> &'static [&'static str] {
+        &["sh", "bash", "zsh"]
+    }
+
+    fn get_file_names(&self) -> &'static [&'static str] {
+        &[".bashrc", ".bash_profile", ".profile", ".zshrc"]
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Token>, LineState) {
+        let mut scanner = Scanner::new(line);
+        if state == SHELL_DOUBLE_STRING || state == SHELL_SINGLE_STRING {
+            let closed = if state == SHELL_DOUBLE_STRING { scanner.skip_string('"', true) } else { scanner.skip_string('\'', false) };
+            scanner.push(0, TokenKind::String);
+            if !closed {
+                return scanner.finish(state);
+            }
+        }
+
+        while let Some(char) = scanner.peek() {
+            let start = scanner.position;
+            if char == '#' && scanner.previous().is_none_or(char::is_whitespace) {
+                scanner.skip_to_end();
+                scanner.push(start, TokenKind::Comment);
+            } else if char == '\\' {
+                scanner.bump();
+                scanner.bump();
+            } else if char == '"' || char == '\'' {
+                scanner.bump();
+                let closed = scanner.skip_string(char, char == '"');
+                scanner.push(start, TokenKind::String);
+                if !closed {
+                    return scanner.finish(if char == '"' { SHELL_DOUBLE_STRING } else { SHELL_SINGLE_STRING });
+                }
+            } else if scanner.starts_with("${") {
+                scanner.skip_past("}");
+                scanner.push(start, TokenKind::Variable);
+            } else if char == '$' {
+                scanner.bump();
+                if scanner.peek().is_some_and(|char| "@*#?$!-".contains(char) || char.is_ascii_digit()) {
+                    scanner.bump();
+                } else {
+                    scanner.eat_while(is_identifier);
+                }
+                scanner.push(start, TokenKind::Variable);
+            } else if is_identifier(char) {
+                let word = scanner.eat_while(|char| is_identifier(char) || char == '-' || char == '.');
+                let kind = if SHELL_KEYWORDS.contains(&word) {
+                    Some(TokenKind::Keyword)
+                } else if word.chars().all(|char| char.is_ascii_digit()) {
+                    Some(TokenKind::Number)
+                } else if scanner.peek() == Some('=') {
+                    Some(TokenKind::Variable)
+                } else if scanner.rest().trim_start().starts_with("()") {
+                    Some(TokenKind::Function)
+                } else {
+                    None
+                };
+                if let Some(kind) = kind {
+                    scanner.push(start, kind);
+                }
+            } else {
+                scanner.bump();
+            }
+        }
+        scanner.finish(0)
+    }
+}
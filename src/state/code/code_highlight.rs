@@ -0,0 +1,213 @@
+use std::{fmt::Debug, ops::Range, path::Path, sync::Arc};
+
+use super::{code::Code, code_grammars::{RustGrammar, TomlGrammar, MarkdownGrammar, JsonGrammar, ShellGrammar}};
+
+// What a token of the code is, the ui picks a style for each kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    Function,
+    String,
+    Number,
+    Constant,
+    Comment,
+    Property,
+    Variable,
+    Attribute,
+    Heading,
+    Emphasis,
+    Link,
+}
+
+// A styled part of a line, range are the bytes of the line it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    range: Range<usize>,
+    kind: TokenKind,
+}
+
+impl Token {
+
+    pub fn new(range: Range<usize>, kind: TokenKind) -> Token {
+        Token {
+            range,
+            kind
+        }
+    }
+
+    pub fn get_range(&self) -> &Range<usize> {
+        &self.range
+    }
+
+    pub fn get_kind(&self) -> TokenKind {
+        self.kind
+    }
+}
+
+// What a grammar carries from a line to the next one, like being inside a block comment.
+// 0 is the state of the first line, the other values are up to each grammar
+pub type LineState = u32;
+
+// A language that can be highlighted one line at a time.
+// Lines are highlighted again only when they are edited or the state they start from changes
+pub trait Grammar: Debug + Send + Sync {
+    fn get_name(&self) -> &'static str;
+
+    // Extensions of the files using the grammar, without the dot
+    fn get_extensions(&self) -> &'static [&'static str];
+
+    // Whole file names using the grammar, like .bashrc
+    fn get_file_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn highlight_line(&self, line: &str, state: LineState) -> (Vec<Token>, LineState);
+}
+
+// The grammars known by the editor, new ones can be registered next to the builtin ones
+#[derive(Debug, Clone)]
+pub struct GrammarRegistry {
+    grammars: Vec<Arc<dyn Grammar>>,
+}
+
+impl Default for GrammarRegistry {
+    fn default() -> GrammarRegistry {
+        let mut registry = GrammarRegistry { grammars: vec![] };
+        registry.register(Arc::new(RustGrammar));
+        registry.register(Arc::new(TomlGrammar));
+        registry.register(Arc::new(MarkdownGrammar));
+        registry.register(Arc::new(JsonGrammar));
+        registry.register(Arc::new(ShellGrammar));
+        registry
+    }
+}
+
+impl GrammarRegistry {
+
+    // A grammar registered later wins over the previous ones for the same files
+    pub fn register(&mut self, grammar: Arc<dyn Grammar>) {
+        self.grammars.insert(0, grammar);
+    }
+
+    pub fn for_path(&self, path: &Path) -> Option<Arc<dyn Grammar>> {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+        self.grammars
+        .iter()
+        .find(|grammar| grammar.get_file_names().contains(&name.as_str()) || grammar.get_extensions().contains(&extension.as_str()))
+        .cloned()
+    }
+}
+
+// Lines replaced by an edit of the code: `removed` lines from `line` became `added` lines.
+// Replacing the whole text removes usize::MAX lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    line: usize,
+    removed: usize,
+    added: usize,
+}
+
+impl LineEdit {
+
+    pub fn new(line: usize, removed: usize, added: usize) -> LineEdit {
+        LineEdit {
+            line,
+            removed,
+            added
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HighlightedLine {
+    start: LineState,
+    end: LineState,
+    tokens: Vec<Token>,
+    dirty: bool,
+}
+
+impl HighlightedLine {
+
+    fn dirty() -> HighlightedLine {
+        HighlightedLine {
+            start: 0,
+            end: 0,
+            tokens: vec![],
+            dirty: true
+        }
+    }
+}
+
+// Tokens of every line of the code for its grammar, kept in sync with the edits of the code
+#[derive(Debug, Clone, Default)]
+pub struct CodeHighlighter {
+    grammar: Option<Arc<dyn Grammar>>,
+    lines: Vec<HighlightedLine>,
+}
+
+impl PartialEq for CodeHighlighter {
+    fn eq(&self, other: &CodeHighlighter) -> bool {
+        self.get_grammar_name() == other.get_grammar_name() && self.lines == other.lines
+    }
+}
+
+impl Eq for CodeHighlighter {}
+
+impl CodeHighlighter {
+
+    pub fn new(grammar: Option<Arc<dyn Grammar>>) -> CodeHighlighter {
+        CodeHighlighter {
+            grammar,
+            lines: vec![]
+        }
+    }
+
+    pub fn get_grammar_name(&self) -> Option<&'static str> {
+        self.grammar.as_ref().map(|grammar| grammar.get_name())
+    }
+
+    // Tokens of a line, empty for lines not highlighted yet
+    pub fn get_tokens(&self, line: usize) -> &[Token] {
+        self.lines.get(line).map(|line| line.tokens.as_slice()).unwrap_or_default()
+    }
+
+    // Marks as edited the lines touched by the edits of the code, keeping the tokens of the others
+    pub fn apply_edits(&mut self, edits: &[LineEdit]) {
+        for edit in edits {
+            let start = edit.line.min(self.lines.len());
+            let end = start.saturating_add(edit.removed).min(self.lines.len());
+            self.lines.splice(start..end, (0..edit.added).map(|_| HighlightedLine::dirty()));
+        }
+    }
+
+    // Highlights the edited lines, and the next ones while the state they start from changed.
+    // Returns how many lines were highlighted
+    pub fn refresh(&mut self, code: &Code) -> usize {
+        let Some(grammar) = &self.grammar else {
+            return 0;
+        };
+        if self.lines.len() != code.len_lines() {
+            self.lines = (0..code.len_lines()).map(|_| HighlightedLine::dirty()).collect();
+        }
+
+        let mut highlighted = 0;
+        let mut state = 0;
+        for (number, line) in self.lines.iter_mut().enumerate() {
+            if line.dirty || line.start != state {
+                let text = code.get_line(number).map(|line| line.get_string()).unwrap_or_default();
+                let (tokens, end) = grammar.highlight_line(&text, state);
+                *line = HighlightedLine {
+                    start: state,
+                    end,
+                    tokens,
+                    dirty: false
+                };
+                highlighted += 1;
+            }
+            state = line.end;
+        }
+        highlighted
+    }
+}
@@ -1,147 +1,608 @@
-use super::code::{Line, Code};
+use std::{collections::{HashMap, HashSet}, time::{SystemTime, Duration}, path::{Path, PathBuf}, fs::{self, File}, io::{self, Write}, env};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Change {
-    number: usize,
-    from: String,
-    to: String,
-}
+use ropey::Rope;
+use serde::{Serialize, Deserialize};
+
+use super::{code::Code, code_selection::CodeSelection, code_utils::Point};
 
-trait ChangeBuilder {
-    fn create_change_with_strings(number: usize, from: String, to: String) -> Change;
-    fn create_change_with_line(line: Line, to: String) -> Change;
+// Number of changes between two snapshots of the buffer
+const CHECKPOINT_INTERVAL: usize = 32;
+// Maximum pause between two keystrokes grouped in the same change
+const GROUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// A single edit of the buffer, positions are char indexes of the rope
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Operation {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
 }
 
-impl ChangeBuilder for Change {
-    fn create_change_with_strings(number: usize, from: String, to: String) -> Change {
-        Change { number, from, to }
+impl Operation {
+
+    pub fn get_at(&self) -> usize {
+        match self {
+            Operation::Insert { at, .. } | Operation::Delete { at, .. } => *at,
+        }
     }
 
-    fn create_change_with_line(line: Line, to: String) -> Change {
-        Change {
-            number: line.get_number(),
-            from: line.get_string(),
-            to,
+    pub fn get_text(&self) -> &str {
+        match self {
+            Operation::Insert { text, .. } | Operation::Delete { text, .. } => text,
+        }
+    }
+
+    pub fn invert(&self) -> Operation {
+        match self {
+            Operation::Insert { at, text } => Operation::Delete { at: *at, text: text.clone() },
+            Operation::Delete { at, text } => Operation::Insert { at: *at, text: text.clone() },
+        }
+    }
+
+    pub fn apply(&self, code: &mut Code) {
+        match self {
+            Operation::Insert { at, text } => code.insert(*at, text),
+            Operation::Delete { at, text } => code.remove(*at..*at + text.chars().count()),
+        }
+    }
+
+    fn apply_to_rope(&self, rope: &mut Rope) {
+        //clamped like the edits of Code, a history read from the cache could not fit the text
+        let at = self.get_at().min(rope.len_chars());
+        match self {
+            Operation::Insert { text, .. } => rope.insert(at, text),
+            Operation::Delete { text, .. } => rope.remove(at..(at + text.chars().count()).min(rope.len_chars())),
+        }
+    }
+
+    // Merges the operation typed right after this one, if they are consecutive keystrokes of the same word
+    fn merge(&self, next: &Operation) -> Option<Operation> {
+        match (self, next) {
+            (Operation::Insert { at, text }, Operation::Insert { at: next_at, text: next_text }) => {
+                let continues = *next_at == *at + text.chars().count();
+                let single_line = !text.contains('\n') && !next_text.contains('\n');
+                //a new word starts a new change
+                let word_break = text.ends_with(char::is_whitespace) && !next_text.starts_with(char::is_whitespace);
+                if continues && single_line && !word_break {
+                    Some(Operation::Insert { at: *at, text: text.clone() + next_text })
+                } else {
+                    None
+                }
+            },
+            (Operation::Delete { at, text }, Operation::Delete { at: next_at, text: next_text }) => {
+                let single_line = !text.contains('\n') && !next_text.contains('\n');
+                if !single_line {
+                    None
+                } else if *next_at + next_text.chars().count() == *at {
+                    //backspace
+                    Some(Operation::Delete { at: *next_at, text: next_text.clone() + text })
+                } else if *next_at == *at {
+                    //forward delete
+                    Some(Operation::Delete { at: *at, text: text.clone() + next_text })
+                } else {
+                    None
+                }
+            },
+            _ => None,
         }
     }
 }
 
+// A step of the history: the operations applied by one command and the cursor around them
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Change {
+    operations: Vec<Operation>,
+    cursor_before: Point,
+    selection_before: Option<CodeSelection>,
+    cursor_after: Point,
+    selection_after: Option<CodeSelection>,
+    timestamp: SystemTime,
+}
+
 impl Change {
 
-    pub fn get_number(&self) -> usize {
-        self.number
+    pub fn new(operations: Vec<Operation>, cursor_before: Point, selection_before: Option<CodeSelection>, cursor_after: Point, selection_after: Option<CodeSelection>) -> Change {
+        Change {
+            operations,
+            cursor_before,
+            selection_before,
+            cursor_after,
+            selection_after,
+            timestamp: SystemTime::now(),
+        }
     }
 
-    pub fn get_from(&self) -> &str {
-        &self.from
+    pub fn get_operations(&self) -> &Vec<Operation> {
+        &self.operations
     }
 
-    pub fn get_to(&self) -> &str {
-        &self.to
+    pub fn get_cursor_before(&self) -> &Point {
+        &self.cursor_before
     }
 
-    pub fn get_old_line(&self) -> Line {
-        Line::new(
-            self.get_number(),
-            self.get_from().to_string()
-        )
+    pub fn get_selection_before(&self) -> &Option<CodeSelection> {
+        &self.selection_before
     }
 
-    pub fn get_new_line(&self) -> Line {
-        Line::new(
-            self.get_number(),
-            self.get_to().to_string()
-        )
+    pub fn get_cursor_after(&self) -> &Point {
+        &self.cursor_after
+    }
 
+    pub fn get_selection_after(&self) -> &Option<CodeSelection> {
+        &self.selection_after
     }
 
-    pub fn create_change_with_strings(number: usize, from: String, to: String) -> Change {
-        Change { number, from, to }
+    pub fn get_timestamp(&self) -> SystemTime {
+        self.timestamp
     }
 
-    pub fn create_change_with_line(line: Line, to: String) -> Change {
-        Change {
-            number: line.get_number(),
-            from: line.get_string(),
-            to,
+    // Tries to absorb the next change, used to undo consecutive typing in one step
+    fn group(&mut self, next: &Change) -> bool {
+        let elapsed = next.timestamp.duration_since(self.timestamp).unwrap_or_default();
+        if self.operations.len() != 1 || next.operations.len() != 1 || elapsed > GROUP_TIMEOUT {
+            return false;
+        }
+        if let Some(merged) = self.operations[0].merge(&next.operations[0]) {
+            self.operations[0] = merged;
+            self.cursor_after = next.cursor_after.clone();
+            self.selection_after = next.selection_after.clone();
+            self.timestamp = next.timestamp;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Short description of the change shown in the history tree
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for operation in &self.operations {
+            let sign = match operation {
+                Operation::Insert { .. } => '+',
+                Operation::Delete { .. } => '-',
+            };
+            let text: String = operation.get_text().escape_debug().take(24).collect();
+            if !summary.is_empty() {
+                summary.push(' ');
+            }
+            summary = summary + &format!("{}\"{}\"", sign, text);
+        }
+        summary
+    }
+
+    fn apply_to_rope(&self, rope: &mut Rope) {
+        for operation in &self.operations {
+            operation.apply_to_rope(rope);
         }
     }
+}
 
+// A state of the buffer in the undo tree, reached from its parent applying `change`
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct HistoryNode {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // child followed by redo
+    active_child: usize,
+    change: Option<Change>,
+    depth: usize,
+    timestamp: SystemTime,
 }
 
+impl HistoryNode {
+
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn get_children(&self) -> &Vec<usize> {
+        &self.children
+    }
+
+    pub fn get_active_child(&self) -> Option<usize> {
+        self.children.get(self.active_child).copied()
+    }
+
+    pub fn get_change(&self) -> &Option<Change> {
+        &self.change
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get_timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+// Undo tree of the buffer, editing after an undo starts a new branch instead of discarding the undone changes
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CodeHistory {
-    initial: Code,
-    changes: Vec<Change>,
-    version: u16,
+    initial: Rope,
+    // nodes in the order they were created, the root is the text the history started from
+    nodes: Vec<HistoryNode>,
+    // snapshots of the buffer for the nodes whose depth is a multiple of CHECKPOINT_INTERVAL
+    checkpoints: HashMap<usize, Rope>,
+    current: usize,
+    // node matching the text of the file on disk
+    saved: usize,
+    grouping: bool,
+}
+
+// What is written in the cache to restore the history of a file in a later session
+#[derive(Serialize, Deserialize)]
+struct StoredHistory {
+    hash: u64,
+    initial: String,
+    nodes: Vec<HistoryNode>,
+    saved: usize,
 }
 
 impl CodeHistory {
     pub fn new(code: Code) -> CodeHistory {
+        let root = HistoryNode {
+            parent: None,
+            children: vec![],
+            active_child: 0,
+            change: None,
+            depth: 0,
+            //the text the history started from is older than any change
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
         CodeHistory {
-            initial: code.clone(),
-            changes: vec![],
-            version: 0,
+            initial: code.get_rope().clone(),
+            nodes: vec![root],
+            checkpoints: HashMap::new(),
+            current: 0,
+            saved: 0,
+            grouping: false,
         }
     }
 
-    pub fn new_with_changes(code: Code, changes: Vec<Change>) -> CodeHistory {
-        CodeHistory {
-            initial: code.clone(),
-            changes,
-            version: 0,
+    // Writes the history in `path`, bound to the hash of the text last saved on disk
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let stored = StoredHistory {
+            hash: content_hash(&self.get_code_at(self.saved).get_text()),
+            initial: self.initial.to_string(),
+            nodes: self.nodes.clone(),
+            saved: self.saved,
+        };
+        let json = serde_json::to_string(&stored).map_err(io::Error::other)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())
     }
 
-    pub fn add_change(&mut self, change: Change) {
-        self.changes.push(change);
+    // Reads the history stored in `path`, if it was stored for the same text of `code`
+    pub fn restore(path: &Path, code: &Code) -> Option<CodeHistory> {
+        let json = fs::read_to_string(path).ok()?;
+        let stored: StoredHistory = serde_json::from_str(&json).ok()?;
+        if stored.hash != content_hash(&code.get_text()) {
+            return None;
+        }
+        //a damaged file must not make the editor panic
+        let count = stored.nodes.len();
+        let valid = stored.saved < count && stored.nodes.iter().enumerate().all(|(id, node)| {
+            node.parent.is_none_or(|parent| parent < id) && node.children.iter().all(|child| *child > id && *child < count)
+        });
+        if !valid {
+            return None;
+        }
+
+        let mut history = CodeHistory {
+            initial: Rope::from_str(&stored.initial),
+            nodes: stored.nodes,
+            checkpoints: HashMap::new(),
+            current: stored.saved,
+            saved: stored.saved,
+            grouping: false,
+        };
+        //parents come before their children, so each snapshot is built from the previous ones
+        for id in 0..count {
+            let depth = history.nodes[id].depth;
+            if depth > 0 && depth.is_multiple_of(CHECKPOINT_INTERVAL) {
+                let rope = history.get_code_at(id).get_rope().clone();
+                history.checkpoints.insert(id, rope);
+            }
+        }
+        if history.get_current_code().get_text() != code.get_text() {
+            return None;
+        }
+        Some(history)
     }
 
-    pub fn add_change_at_position(&mut self, position: u16, change: Change) {
-        if (position as usize) <= self.changes.len() {
-            self.changes.insert(position as usize, change);
+    // Records the change applied to `code` as a child of the current state
+    pub fn add_change(&mut self, change: Change, code: &Code) {
+        let current = self.current;
+        let is_leaf = self.nodes[current].children.is_empty();
+        //the saved change must keep matching the text on disk
+        let grouped = self.grouping && is_leaf && current != self.saved && match &mut self.nodes[current].change {
+            Some(last) => last.group(&change),
+            None => false,
+        };
+
+        if grouped {
+            let node = &mut self.nodes[current];
+            node.timestamp = change.get_timestamp();
+            //the snapshot of the grouped change is outdated
+            if let Some(checkpoint) = self.checkpoints.get_mut(&current) {
+                *checkpoint = code.get_rope().clone();
+            }
+        } else {
+            let id = self.nodes.len();
+            let depth = self.nodes[current].depth + 1;
+            self.nodes.push(HistoryNode {
+                parent: Some(current),
+                children: vec![],
+                active_child: 0,
+                timestamp: change.get_timestamp(),
+                change: Some(change),
+                depth,
+            });
+            let parent = &mut self.nodes[current];
+            parent.children.push(id);
+            parent.active_child = parent.children.len() - 1;
+            if depth.is_multiple_of(CHECKPOINT_INTERVAL) {
+                self.checkpoints.insert(id, code.get_rope().clone());
+            }
+            self.current = id;
         }
+        self.grouping = true;
     }
 
+    // Stops the current group, the next change will be undone on its own
+    pub fn close_group(&mut self) {
+        self.grouping = false;
+    }
 
-    pub fn get_current_code(&self) -> Code {
-        let mut code = self.initial.clone();
-        for i in 0..self.version as usize {
-            if i < self.changes.len() {
-                let line_number = self.changes[i].get_number();
-                code.change_line(line_number, self.changes[i].get_to().to_string());
+    // Reverts the last change on `code`, restoring the cursor it had before
+    pub fn undo(&mut self, code: &mut Code) -> bool {
+        self.grouping = false;
+        let node = &self.nodes[self.current];
+        if let (Some(parent), Some(change)) = (node.parent, &node.change) {
+            for operation in change.get_operations().iter().rev() {
+                operation.invert().apply(code);
+            }
+            code.take_operations();
+            *code.get_mut_cursor() = change.get_cursor_before().clone();
+            code.set_selection(change.get_selection_before().clone());
+            self.current = parent;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Applies again the change of the active branch on `code`, restoring the cursor it had after
+    pub fn redo(&mut self, code: &mut Code) -> bool {
+        self.grouping = false;
+        if let Some(child) = self.nodes[self.current].get_active_child() {
+            self.apply_child(child, code);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn apply_child(&mut self, child: usize, code: &mut Code) {
+        if let Some(change) = &self.nodes[child].change {
+            for operation in change.get_operations() {
+                operation.apply(code);
             }
+            code.take_operations();
+            *code.get_mut_cursor() = change.get_cursor_after().clone();
+            code.set_selection(change.get_selection_after().clone());
+        }
+        let parent = &mut self.nodes[self.current];
+        if let Some(index) = parent.children.iter().position(|id| *id == child) {
+            parent.active_child = index;
         }
-        code
+        self.current = child;
     }
 
-    pub fn remove_change(&mut self) {
-        if self.version > 0 {
-            self.version -= 1;
+    // Selects the next branch followed by redo, returns false if there is nothing to choose
+    pub fn next_branch(&mut self) -> bool {
+        let node = &mut self.nodes[self.current];
+        if node.children.len() < 2 {
+            return false;
         }
+        node.active_child = (node.active_child + 1) % node.children.len();
+        true
     }
 
-    pub fn use_previous(&mut self) {
-        if self.version > 0 {
-            self.version -= 1;
+    // Selects the previous branch followed by redo, returns false if there is nothing to choose
+    pub fn previous_branch(&mut self) -> bool {
+        let node = &mut self.nodes[self.current];
+        if node.children.len() < 2 {
+            return false;
         }
+        node.active_child = (node.active_child + node.children.len() - 1) % node.children.len();
+        true
     }
 
-    pub fn use_next(&mut self) {
-        if self.version < self.changes.len() as u16 {
-            self.version += 1;
+    // Moves `code` to any state of the tree, undoing up to the common ancestor and redoing down to `target`
+    pub fn jump_to(&mut self, target: usize, code: &mut Code) -> bool {
+        if target >= self.nodes.len() {
+            return false;
+        }
+        self.grouping = false;
+        let ancestors: HashSet<usize> = self.get_path(target).into_iter().collect();
+        while !ancestors.contains(&self.current) {
+            self.undo(code);
+        }
+        let path = self.get_path(target);
+        if let Some(start) = path.iter().position(|id| *id == self.current) {
+            for child in path[start + 1..].iter() {
+                self.apply_child(*child, code);
+            }
         }
+        true
     }
 
-    pub fn use_last(&mut self) {
-        self.version = self.changes.len() as u16;
+    // Moves `code` to the last state reached before `time`, the first one if the history is younger
+    pub fn jump_to_time(&mut self, time: SystemTime, code: &mut Code) -> bool {
+        let target = self.nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.timestamp <= time)
+        .max_by_key(|(_, node)| node.timestamp)
+        .map(|(id, _)| id)
+        .unwrap_or(0);
+        self.jump_to(target, code)
     }
 
-    pub fn reset(&mut self) {
-        self.version = 0;
+    // Ids of the nodes from the root to `node`, both included
+    pub fn get_path(&self, node: usize) -> Vec<usize> {
+        let mut path = vec![];
+        let mut next = Some(node);
+        while let Some(id) = next {
+            path.push(id);
+            next = self.nodes[id].parent;
+        }
+        path.reverse();
+        path
     }
 
+    // Nodes of the tree in depth first order, each with the number of branches it is nested in
+    pub fn get_tree_rows(&self) -> Vec<(usize, usize)> {
+        let mut rows = vec![];
+        let mut stack = vec![(0, 0)];
+        while let Some((id, indent)) = stack.pop() {
+            rows.push((id, indent));
+            let children = &self.nodes[id].children;
+            for (index, child) in children.iter().enumerate().rev() {
+                stack.push((*child, if index == 0 { indent } else { indent + 1 }));
+            }
+        }
+        rows
+    }
+
+    // Rebuilds the text of a state of the tree starting from the closest snapshot
+    pub fn get_code_at(&self, node: usize) -> Code {
+        let path = self.get_path(node.min(self.nodes.len() - 1));
+        let start = path.iter().rposition(|id| self.checkpoints.contains_key(id));
+        let (start, mut rope) = match start {
+            Some(start) => (start, self.checkpoints[&path[start]].clone()),
+            None => (0, self.initial.clone()),
+        };
+
+        for id in &path[start + 1..] {
+            if let Some(change) = &self.nodes[*id].change {
+                change.apply_to_rope(&mut rope);
+            }
+        }
+        Code::from(rope)
+    }
+
+    pub fn get_current_code(&self) -> Code {
+        self.get_code_at(self.current)
+    }
+
+    pub fn get_current(&self) -> usize {
+        self.current
+    }
+
+    // Remembers the current state as the one written on disk
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current;
+    }
+
+    pub fn get_saved(&self) -> usize {
+        self.saved
+    }
+
+    pub fn is_saved(&self) -> bool {
+        self.saved == self.current
+    }
+
+    // Number of changes applied to reach the current state
+    pub fn get_version(&self) -> usize {
+        self.nodes[self.current].depth
+    }
+
+    pub fn get_node(&self, node: usize) -> Option<&HistoryNode> {
+        self.nodes.get(node)
+    }
+
+    pub fn get_nodes(&self) -> &Vec<HistoryNode> {
+        &self.nodes
+    }
+
+    // Changes applied from the first state to the current one
     pub fn get_changes(&self) -> Vec<Change> {
-        self.changes.clone()
+        self.get_path(self.current)
+        .iter()
+        .filter_map(|id| self.nodes[*id].change.clone())
+        .collect()
+    }
+}
+
+// FNV-1a hash of a text, stable between sessions unlike the hasher of the standard library
+pub fn content_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Folder of the cache of the editor: $XDG_CACHE_HOME/noce or ~/.cache/noce
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(cache) if !cache.is_empty() => PathBuf::from(cache),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("noce"))
+}
+
+// File where the history of `file` is kept, in a folder of the cache for each project
+pub fn history_cache_path(cache_dir: &Path, project: &Path, file: &Path) -> PathBuf {
+    let project_key = format!("{:016x}", content_hash(&project.to_string_lossy()));
+    let file_key = format!("{:016x}", content_hash(&file.to_string_lossy()));
+    cache_dir.join(project_key).join("undo").join(file_key + ".json")
+}
+
+// Parses a time like "30s", "5m" or "2h" (plain numbers are seconds)
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let (number, unit) = match input.char_indices().last() {
+        Some((index, unit)) if unit.is_alphabetic() => (&input[..index], unit),
+        _ => (input, 's'),
+    };
+    let number: u64 = number.trim().parse().ok()?;
+    //times too large to count in seconds are not valid
+    let seconds = match unit {
+        's' => Some(number),
+        'm' => number.checked_mul(60),
+        'h' => number.checked_mul(60 * 60),
+        _ => None,
+    }?;
+    Some(Duration::from_secs(seconds))
+}
+
+// State of the undo tree popup: the node previewed and the time typed to jump back
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HistoryPopup {
+    selected: usize,
+    input: String,
+}
+
+impl HistoryPopup {
+    pub fn new(selected: usize) -> HistoryPopup {
+        HistoryPopup { selected, input: String::new() }
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, selected: usize) {
+        self.selected = selected;
+    }
+
+    pub fn get_input(&self) -> &String {
+        &self.input
+    }
+
+    pub fn get_mut_input(&mut self) -> &mut String {
+        &mut self.input
     }
 }
@@ -0,0 +1,227 @@
+use std::{cmp::{min, max}, collections::HashMap, ops::RangeInclusive};
+
+use super::{CodeComponent, code::Code, code_brackets::find_match, code_utils::Point};
+
+// Endings of a line after which the next line is indented one more level
+const OPENERS: [&str; 5] = ["{", "(", "[", ":", "=>"];
+const CLOSERS: [char; 3] = ['}', ')', ']'];
+
+// What one level of indentation is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Spaces(usize),
+    Tabs,
+}
+
+impl Default for IndentStyle {
+    fn default() -> IndentStyle {
+        IndentStyle::Spaces(4)
+    }
+}
+
+impl IndentStyle {
+
+    // The style used by most of the indented lines of a text, None if no line is indented.
+    // The width of spaces is the most common step between the indentation of a line and the next one
+    pub fn detect(text: &str) -> Option<IndentStyle> {
+        let mut tabs = 0;
+        let mut spaces = 0;
+        let mut steps: HashMap<usize, usize> = HashMap::new();
+        let mut previous = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                tabs += 1;
+                continue;
+            }
+            let width = line.len() - line.trim_start_matches(' ').len();
+            if width > 0 {
+                spaces += 1;
+            }
+            if width > previous && width - previous <= 8 {
+                *steps.entry(width - previous).or_default() += 1;
+            }
+            previous = width;
+        }
+
+        if tabs == 0 && spaces == 0 {
+            None
+        } else if tabs > spaces {
+            Some(IndentStyle::Tabs)
+        } else {
+            //the smaller step wins a tie, 2 and 4 spaces steps are both common in a 2 spaces file
+            steps
+            .into_iter()
+            .max_by_key(|(step, count)| (*count, usize::MAX - step))
+            .map(|(step, _)| IndentStyle::Spaces(step))
+        }
+    }
+
+    // Text of one level of indentation
+    pub fn unit(&self) -> String {
+        match self {
+            IndentStyle::Spaces(width) => " ".repeat(*width),
+            IndentStyle::Tabs => "\t".to_string(),
+        }
+    }
+
+    // Columns of one level of indentation, a tab takes `tab_width` columns
+    pub fn width(&self, tab_width: usize) -> usize {
+        match self {
+            IndentStyle::Spaces(width) => *width,
+            IndentStyle::Tabs => tab_width,
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        match self {
+            IndentStyle::Spaces(width) => format!("{} spaces", width),
+            IndentStyle::Tabs => "Tabs".to_string(),
+        }
+    }
+}
+
+// Whitespace at the start of a line
+pub fn line_indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// The line up to the cursor asks the next one to be indented
+pub fn opens_block(before: &str) -> bool {
+    let before = before.trim_end();
+    OPENERS.iter().any(|opener| before.ends_with(opener))
+}
+
+// Text inserted by Enter at a column of a line, with how many of its chars go before the cursor.
+// The new line keeps the indentation and goes one level deeper after an opener,
+// an opener closed right after the cursor moves its closer on a third line
+pub fn enter_text(before: &str, after: &str, unit: &str) -> (String, usize) {
+    let indent = line_indent(before);
+    let mut text = format!("\n{}", indent);
+    if opens_block(before) {
+        text.push_str(unit);
+        let cursor = text.chars().count();
+        let closed = before
+        .trim_end()
+        .chars()
+        .last()
+        .and_then(|open| ['{', '(', '['].iter().position(|opener| *opener == open))
+        .is_some_and(|index| after.trim_start().starts_with(CLOSERS[index]));
+        if closed {
+            text.push('\n');
+            text.push_str(indent);
+        }
+        return (text, cursor);
+    }
+    let cursor = text.chars().count();
+    (text, cursor)
+}
+
+// Moves a closer typed at the start of a line, at char index `at`, to the indentation of the line of its opener,
+// or one level back if it has no opener
+pub fn dedent_closer(code_component: &mut CodeComponent, at: usize) {
+    let unit = code_component.indent.unit();
+    let code = &mut code_component.current;
+    if !code.get_rope().get_char(at).is_some_and(|char| CLOSERS.contains(&char)) {
+        return;
+    }
+    let line_start = code.line_to_char(code.char_to_line(at));
+    let indent = code.get_slice(line_start..at);
+    if !indent.trim().is_empty() {
+        return;
+    }
+
+    let target = match find_match(code, at) {
+        Some(open) => code.get_line(code.char_to_line(open)).map(|line| line_indent(&line.get_string()).to_string()).unwrap_or_default(),
+        None => indent.strip_suffix(unit.as_str()).unwrap_or(&indent).to_string(),
+    };
+    if target != indent {
+        code.remove(line_start..at);
+        code.insert(line_start, &target);
+        *code.get_mut_cursor() = code.char_to_point(line_start + target.chars().count() + 1);
+    }
+}
+
+// Lines covered by the selection, a selection ending at the start of a line leaves that line out
+pub fn selected_lines(code: &Code) -> Option<RangeInclusive<usize>> {
+    let selection = code.get_selection().as_ref()?;
+    let start = min(selection.get_start(), selection.get_end());
+    let end = max(selection.get_start(), selection.get_end());
+    if start == end {
+        return None;
+    }
+    let last = if end.get_y() == 0 && end.get_x() > start.get_x() { end.get_x() - 1 } else { end.get_x() };
+    Some(start.get_x()..=last)
+}
+
+// Moves a point of a line whose indentation changed by `delta` columns, a point at the start of the line stays there
+fn shift_point(point: &Point, line: usize, delta: isize) -> Point {
+    if point.get_x() != line || point.get_y() == 0 {
+        return point.clone();
+    }
+    Point::new(point.get_x(), point.get_y().saturating_add_signed(delta))
+}
+
+// Adds (or removes, with `indent` false) one level of indentation to some lines,
+// keeping the cursor and the selection on the same text
+fn change_indent(code_component: &mut CodeComponent, lines: RangeInclusive<usize>, indent: bool) {
+    let unit = code_component.indent.unit();
+    let width = code_component.indent.width(code_component.tab_width).max(1);
+    let code = &mut code_component.current;
+    let mut cursor = code.get_cursor().clone();
+    let mut selection = code.get_selection().clone();
+    for number in lines {
+        let Some(line) = code.get_line(number).map(|line| line.get_string()) else {
+            continue;
+        };
+        let start = code.line_to_char(number);
+        let delta = if indent {
+            if line.trim().is_empty() {
+                continue;
+            }
+            code.insert(start, &unit);
+            unit.chars().count() as isize
+        } else {
+            //a tab, or the spaces of one level at most
+            let leading = line_indent(&line);
+            let removed = if leading.starts_with('\t') {
+                1
+            } else {
+                leading.chars().take_while(|char| *char == ' ').count().min(width)
+            };
+            code.remove(start..start + removed);
+            -(removed as isize)
+        };
+        cursor = shift_point(&cursor, number, delta);
+        if let Some(selection) = &mut selection {
+            let start = shift_point(selection.get_start(), number, delta);
+            let end = shift_point(selection.get_end(), number, delta);
+            selection.set_start(start);
+            selection.set_end(end);
+        }
+    }
+    *code.get_mut_cursor() = cursor;
+    code.set_selection(selection);
+}
+
+// Tab indents the selected lines, or inserts one level of indentation at the cursor
+pub fn handle_tab(code_component: &mut CodeComponent) {
+    if let Some(lines) = selected_lines(&code_component.current) {
+        change_indent(code_component, lines, true);
+        return;
+    }
+    let unit = code_component.indent.unit();
+    let code = &mut code_component.current;
+    code.flush_selection();
+    let at = code.point_to_char(code.get_cursor());
+    code.insert(at, &unit);
+    *code.get_mut_cursor() = code.char_to_point(at + unit.chars().count());
+}
+
+// Shift+Tab removes one level of indentation from the selected lines, or from the line of the cursor
+pub fn handle_back_tab(code_component: &mut CodeComponent) {
+    let lines = selected_lines(&code_component.current).unwrap_or_else(|| {
+        let line = code_component.current.get_cursor().get_x();
+        line..=line
+    });
+    change_indent(code_component, lines, false);
+}
@@ -0,0 +1,163 @@
+use super::{CodeComponent, code::Code, code_indent::{line_indent, selected_lines}, code_selection::CodeSelection, code_utils::Point};
+
+// Char index right after the last char of a line, before its line break
+fn line_end(code: &Code, number: usize) -> usize {
+    code.line_to_char(number) + code.get_line(number).map(|line| line.get_string().chars().count()).unwrap_or(0)
+}
+
+fn line_text(code: &Code, number: usize) -> String {
+    code.get_line(number).map(|line| line.get_string()).unwrap_or_default()
+}
+
+// First and last line the commands work on: the selected lines, or the line of the cursor
+fn target_lines(code: &Code) -> (usize, usize) {
+    selected_lines(code)
+    .map(|lines| (*lines.start(), *lines.end()))
+    .unwrap_or((code.get_cursor().get_x(), code.get_cursor().get_x()))
+}
+
+// Replaces the text of the lines from `first` to `last` with other lines
+fn replace_lines(code: &mut Code, first: usize, last: usize, lines: &[String]) {
+    let start = code.line_to_char(first);
+    let end = line_end(code, last);
+    code.remove(start..end);
+    code.insert(start, &lines.join("\n"));
+}
+
+// Moves a point `lines` lines down, or up with a negative value
+fn shift_line(point: &Point, lines: isize) -> Point {
+    Point::new(point.get_x().saturating_add_signed(lines), point.get_y())
+}
+
+// Places a copy of the selection after it and selects the copy, without a selection the line is copied below
+pub fn handle_duplicate(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    if let Some(range) = code.get_selection_range().filter(|range| !range.is_empty()) {
+        let text = code.get_slice(range.clone());
+        code.insert(range.end, &text);
+        let end = code.char_to_point(range.end + text.chars().count());
+        code.create_selection(code.char_to_point(range.end), end.clone());
+        *code.get_mut_cursor() = end;
+        return;
+    }
+    let number = code.get_cursor().get_x();
+    let text = line_text(code, number);
+    let start = code.line_to_char(number);
+    code.insert(start, &format!("{}\n", text));
+    let cursor = shift_line(code.get_cursor(), 1);
+    *code.get_mut_cursor() = cursor;
+}
+
+// Swaps the target lines with the one above them, or below them with `down`
+pub fn handle_move_lines(code_component: &mut CodeComponent, down: bool) {
+    let code = &mut code_component.current;
+    let (first, last) = target_lines(code);
+    if (!down && first == 0) || (down && last + 1 >= code.len_lines()) {
+        return;
+    }
+    let (from, to) = if down { (first, last + 1) } else { (first - 1, last) };
+    let mut lines: Vec<String> = (from..=to).map(|number| line_text(code, number)).collect();
+    if down {
+        lines.rotate_right(1);
+    } else {
+        lines.rotate_left(1);
+    }
+    replace_lines(code, from, to, &lines);
+
+    let offset = if down { 1 } else { -1 };
+    let cursor = shift_line(code.get_cursor(), offset);
+    *code.get_mut_cursor() = cursor;
+    let selection = code.get_selection().clone().map(|selection| CodeSelection::new(shift_line(selection.get_start(), offset), shift_line(selection.get_end(), offset)));
+    code.set_selection(selection);
+}
+
+// Deletes the target lines with their line breaks
+pub fn handle_delete_lines(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    let (first, last) = target_lines(code);
+    let column = code.get_cursor().get_y();
+    let mut line = first;
+    if last + 1 < code.len_lines() {
+        code.remove(code.line_to_char(first)..code.line_to_char(last + 1));
+    } else if first > 0 {
+        //the last line has no line break, the one before it goes away instead
+        code.remove(line_end(code, first - 1)..code.len_chars());
+        line = first - 1;
+    } else {
+        code.remove(0..code.len_chars());
+    }
+    code.flush_selection();
+    *code.get_mut_cursor() = code.char_to_point(code.point_to_char(&Point::new(line, column)));
+}
+
+// Joins the next line, or all the selected lines, to the line of the cursor with a single space.
+// The cursor goes where the last line was joined
+pub fn handle_join_lines(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    let (first, mut last) = target_lines(code);
+    if first == last {
+        last += 1;
+    }
+    if last >= code.len_lines() {
+        return;
+    }
+    let mut joined = line_text(code, first);
+    let mut column = joined.chars().count();
+    for number in first + 1..=last {
+        let next = line_text(code, number);
+        let next = next.trim_start();
+        column = joined.chars().count();
+        if next.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() && !joined.ends_with(char::is_whitespace) {
+            joined.push(' ');
+        }
+        joined.push_str(next);
+    }
+    replace_lines(code, first, last, &[joined]);
+    code.flush_selection();
+    *code.get_mut_cursor() = code.char_to_point(code.line_to_char(first) + column);
+}
+
+// Adds an empty line below the line of the cursor, or above it, with its indentation and moves the cursor there
+pub fn handle_insert_line(code_component: &mut CodeComponent, above: bool) {
+    let code = &mut code_component.current;
+    let number = code.get_cursor().get_x();
+    let text = line_text(code, number);
+    let indent = line_indent(&text);
+    let at = if above {
+        let start = code.line_to_char(number);
+        code.insert(start, &format!("{}\n", indent));
+        start + indent.chars().count()
+    } else {
+        let end = line_end(code, number);
+        code.insert(end, &format!("\n{}", indent));
+        end + 1 + indent.chars().count()
+    };
+    code.flush_selection();
+    *code.get_mut_cursor() = code.char_to_point(at);
+}
+
+// Rearranges the selected lines and selects all of them, nothing happens without a selection on more lines
+fn rearrange_lines<F: FnOnce(&mut Vec<String>)>(code_component: &mut CodeComponent, rearrange: F) {
+    let code = &mut code_component.current;
+    let Some(lines) = selected_lines(code).filter(|lines| lines.start() < lines.end()) else {
+        return;
+    };
+    let (first, last) = (*lines.start(), *lines.end());
+    let mut texts: Vec<String> = lines.map(|number| line_text(code, number)).collect();
+    rearrange(&mut texts);
+    replace_lines(code, first, last, &texts);
+    let end = code.char_to_point(line_end(code, last));
+    code.create_selection(Point::new(first, 0), end.clone());
+    *code.get_mut_cursor() = end;
+}
+
+pub fn handle_sort_lines(code_component: &mut CodeComponent) {
+    rearrange_lines(code_component, |lines| lines.sort());
+}
+
+pub fn handle_reverse_lines(code_component: &mut CodeComponent) {
+    rearrange_lines(code_component, |lines| lines.reverse());
+}
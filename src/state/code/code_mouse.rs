@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+use crate::state::AppContext;
+
+use super::{CodeComponent, code_navigation::extend_cursor, code_panes::focus_pane, code_utils::Point};
+
+// Clicks on the same cell closer than this make a double or a triple click
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+
+// Rows scrolled by a step of the wheel
+const WHEEL_ROWS: isize = 3;
+
+// Last click on the code, to tell single, double and triple clicks apart
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MouseClicks {
+    last: Option<(Instant, u16, u16)>,
+    count: usize,
+}
+
+impl MouseClicks {
+
+    // Counts a click, after a triple click the next one is a single click again
+    fn register(&mut self, column: u16, row: u16) -> usize {
+        let now = Instant::now();
+        let repeated = matches!(self.last, Some((at, last_column, last_row)) if now.duration_since(at) < MULTI_CLICK && (last_column, last_row) == (column, row));
+        self.count = if repeated { self.count % 3 + 1 } else { 1 };
+        self.last = Some((now, column, row));
+        self.count
+    }
+}
+
+// Point of the focused pane drawn on a cell, the cells outside its lines count as the nearest ones
+fn point_under(code_component: &CodeComponent, column: u16, row: u16) -> Point {
+    let area = code_component.panes.get_area(code_component.panes.get_focused());
+    let row = row.clamp(area.get_y(), area.get_y() + area.get_height().saturating_sub(1)) - area.get_y();
+    let cell = column.saturating_sub(area.get_x());
+    code_component.viewport.point_at(&code_component.current, code_component.tab_width, row as usize, cell as usize)
+}
+
+// Selects the word under a point, or just moves the cursor there between words
+fn select_word(code_component: &mut CodeComponent, point: Point) {
+    let code = &mut code_component.current;
+    let Some(range) = code.word_range_at(&point) else {
+        extend_cursor(code_component, point, false);
+        return;
+    };
+    let end = code.char_to_point(range.end);
+    code.create_selection(code.char_to_point(range.start), end.clone());
+    *code.get_mut_cursor() = end;
+}
+
+// Selects the line of a point with its line break
+fn select_line(code_component: &mut CodeComponent, point: Point) {
+    let code = &mut code_component.current;
+    let line = point.get_x();
+    let end = if line + 1 < code.len_lines() { Point::new(line + 1, 0) } else { Point::new(line, code.line_len(line)) };
+    code.create_selection(Point::new(line, 0), end.clone());
+    *code.get_mut_cursor() = end;
+}
+
+// A click focuses the pane under the mouse and places the cursor, Shift+click selects up to there.
+// Double and triple clicks select a word and a line, dragging selects from the cursor and the wheel scrolls the pane
+pub fn handle_mouse(code_component: &mut CodeComponent, context: &mut AppContext, mouse: MouseEvent) {
+    if code_component.history_popup.is_some() || code_component.buffer_picker.is_some() {
+        return;
+    }
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            let Some(pane) = code_component.panes.pane_at(mouse.column, mouse.row) else {
+                return;
+            };
+            focus_pane(code_component, pane);
+            context.set_active_file(code_component.path.clone());
+            context.set_active_file_changed(false);
+            code_component.current.flush_cursors();
+            let point = point_under(code_component, mouse.column, mouse.row);
+            match code_component.clicks.register(mouse.column, mouse.row) {
+                2 => select_word(code_component, point),
+                3 => select_line(code_component, point),
+                _ => extend_cursor(code_component, point, mouse.modifiers.contains(KeyModifiers::SHIFT)),
+            }
+        },
+        MouseEventKind::Drag(MouseButton::Left) => {
+            let point = point_under(code_component, mouse.column, mouse.row);
+            extend_cursor(code_component, point, true);
+        },
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+            let Some(pane) = code_component.panes.pane_at(mouse.column, mouse.row) else {
+                return;
+            };
+            let rows = if mouse.kind == MouseEventKind::ScrollUp { -WHEEL_ROWS } else { WHEEL_ROWS };
+            let tab_width = code_component.tab_width;
+            if pane == code_component.panes.get_focused() {
+                code_component.viewport.scroll(&code_component.current, tab_width, rows);
+            } else {
+                let (code, _, _) = code_component.get_pane_view(pane);
+                code_component.panes.get_mut_viewport(pane).scroll(&code, tab_width, rows);
+            }
+        },
+        _ => {}
+    }
+}
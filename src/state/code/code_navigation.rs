@@ -0,0 +1,173 @@
+use crossterm::event::{Event, KeyModifiers};
+
+use super::{CodeComponent, code::Code, code_indent::line_indent, code_utils::{Point, vertical_column}};
+
+// Kinds of chars a word movement stops between
+#[derive(PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn char_class(char: char) -> CharClass {
+    if char.is_whitespace() {
+        CharClass::Blank
+    } else if char.is_alphanumeric() || char == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn is_shift(event: &Event) -> bool {
+    matches!(event, Event::Key(key) if key.modifiers.contains(KeyModifiers::SHIFT))
+}
+
+// Moves the cursor to `target`, with Shift the selection grows from where it started (or from the cursor) to it
+pub fn move_cursor(code_component: &mut CodeComponent, target: Point, event: &Event) {
+    extend_cursor(code_component, target, is_shift(event));
+}
+
+// Moves the cursor to `target`, growing the selection to it with `extend`
+pub fn extend_cursor(code_component: &mut CodeComponent, target: Point, extend: bool) {
+    let code = &mut code_component.current;
+    let target = code.char_to_point(code.point_to_char(&target));
+    if extend {
+        let anchor = code.get_selection().as_ref().map(|selection| selection.get_start().clone()).unwrap_or(code.get_cursor().clone());
+        if anchor == target {
+            code.flush_selection();
+        } else {
+            code.create_selection(anchor, target.clone());
+        }
+    } else {
+        code.flush_selection();
+    }
+    *code.get_mut_cursor() = target;
+}
+
+// Char index after the blanks and the next word (or run of punctuation) from `char_idx`
+pub fn next_word(code: &Code, char_idx: usize) -> usize {
+    let mut chars = code.get_rope().chars_at(char_idx.min(code.len_chars())).peekable();
+    let mut index = char_idx;
+    while chars.next_if(|char| char_class(*char) == CharClass::Blank).is_some() {
+        index += 1;
+    }
+    if let Some(class) = chars.peek().map(|char| char_class(*char)) {
+        while chars.next_if(|char| char_class(*char) == class).is_some() {
+            index += 1;
+        }
+    }
+    index
+}
+
+// Char index at the start of the word (or run of punctuation) before `char_idx`, skipping the blanks before it
+pub fn previous_word(code: &Code, char_idx: usize) -> usize {
+    let mut chars = code.get_rope().chars_at(char_idx.min(code.len_chars()));
+    let mut index = char_idx.min(code.len_chars());
+    let mut class = None;
+    while let Some(char) = chars.prev() {
+        let current = char_class(char);
+        match &class {
+            None if current == CharClass::Blank => {},
+            None => class = Some(current),
+            Some(class) if *class != current => break,
+            Some(_) => {},
+        }
+        index -= 1;
+    }
+    index
+}
+
+pub fn handle_word_right(code_component: &mut CodeComponent, event: Event) {
+    let code = &code_component.current;
+    let target = code.char_to_point(next_word(code, code.point_to_char(code.get_cursor())));
+    move_cursor(code_component, target, &event);
+}
+
+pub fn handle_word_left(code_component: &mut CodeComponent, event: Event) {
+    let code = &code_component.current;
+    let target = code.char_to_point(previous_word(code, code.point_to_char(code.get_cursor())));
+    move_cursor(code_component, target, &event);
+}
+
+// Goes to the first non blank char of the line, or to its start when the cursor is already there
+pub fn handle_home(code_component: &mut CodeComponent, event: Event) {
+    let code = &code_component.current;
+    let cursor = code.get_cursor().clone();
+    let line = code.get_line(cursor.get_x()).unwrap_or_default();
+    let indent = line.column(line_indent(&line.get_string()).len());
+    let column = if cursor.get_y() == indent { 0 } else { indent };
+    move_cursor(code_component, Point::new(cursor.get_x(), column), &event);
+}
+
+pub fn handle_end(code_component: &mut CodeComponent, event: Event) {
+    let code = &code_component.current;
+    let line = code.get_cursor().get_x();
+    let target = Point::new(line, code.line_len(line));
+    move_cursor(code_component, target, &event);
+}
+
+pub fn handle_code_start(code_component: &mut CodeComponent, event: Event) {
+    move_cursor(code_component, Point::default(), &event);
+}
+
+pub fn handle_code_end(code_component: &mut CodeComponent, event: Event) {
+    let code = &code_component.current;
+    let target = code.char_to_point(code.len_chars());
+    move_cursor(code_component, target, &event);
+}
+
+// Moves the cursor by the lines shown in the viewport, on the same on screen column like the arrows do
+pub fn handle_page(code_component: &mut CodeComponent, event: Event, down: bool) {
+    let code = &code_component.current;
+    let cursor = code.get_cursor().clone();
+    let page = code_component.get_page_height().max(1);
+    let last = code.len_lines().saturating_sub(1);
+    let target = if down {
+        if cursor.get_x() == last {
+            Point::new(last, code.line_len(last))
+        } else {
+            let line = (cursor.get_x() + page).min(last);
+            Point::new(line, vertical_column(code, &cursor, line))
+        }
+    } else if cursor.get_x() == 0 {
+        Point::default()
+    } else {
+        let line = cursor.get_x().saturating_sub(page);
+        Point::new(line, vertical_column(code, &cursor, line))
+    };
+    move_cursor(code_component, target, &event);
+}
+
+// Up and Down while wrapping lines go to the row drawn above or below the cursor, which can be on the same line.
+// Past the first or the last row the cursor goes to the start or the end of the code
+pub fn handle_visual_vertical(code_component: &mut CodeComponent, event: Event, down: bool) {
+    let code = &code_component.current;
+    let target = code_component
+    .viewport
+    .visual_vertical(code, code.get_cursor(), code_component.tab_width, down)
+    .unwrap_or_else(|| if down { code.char_to_point(code.len_chars()) } else { Point::default() });
+    move_cursor(code_component, target, &event);
+}
+
+// Delete removes the selection, or the grapheme after the cursor joining the next line at the end of one
+pub fn handle_forward_delete(code_component: &mut CodeComponent) {
+    let code = &mut code_component.current;
+    if let Some(range) = code.get_selection_range() {
+        code.delete_selection();
+        *code.get_mut_cursor() = code.char_to_point(range.start);
+        code.flush_selection();
+        return;
+    }
+    let cursor = code.get_cursor().clone();
+    let at = code.point_to_char(&cursor);
+    let next = if cursor.get_y() < code.line_len(cursor.get_x()) {
+        code.point_to_char(&Point::new(cursor.get_x(), cursor.get_y() + 1))
+    } else {
+        at + 1
+    };
+    if next <= code.len_chars() && next > at {
+        code.remove(at..next);
+    }
+}
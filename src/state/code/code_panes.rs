@@ -0,0 +1,378 @@
+use crate::state::ScreenArea;
+
+use super::{CodeComponent, code::Code, code_buffers::switch_buffer, code_selection::CodeSelection, code_utils::Point, code_viewport::CodeViewport};
+
+// How a split places its two parts: side by side, or one above the other
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+// Where the focus goes, or the divider moves, from the focused pane
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PaneDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl PaneDirection {
+
+    // Splits whose parts are placed along the direction
+    fn axis(&self) -> SplitDirection {
+        match self {
+            PaneDirection::Left | PaneDirection::Right => SplitDirection::Horizontal,
+            PaneDirection::Up | PaneDirection::Down => SplitDirection::Vertical,
+        }
+    }
+
+    // The direction goes from the first part of a split to the second one
+    fn forward(&self) -> bool {
+        matches!(self, PaneDirection::Right | PaneDirection::Down)
+    }
+}
+
+// Panes of the code panel: a single pane, or a split in two layouts where the first one takes `ratio` percent of the space
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PaneLayout {
+    Pane(usize),
+    Split {
+        direction: SplitDirection,
+        ratio: u16,
+        first: Box<PaneLayout>,
+        second: Box<PaneLayout>,
+    },
+}
+
+impl PaneLayout {
+
+    // Parts taken from the top of the layout down to a pane, true for the second part of a split
+    fn path_to(&self, pane: usize) -> Option<Vec<bool>> {
+        match self {
+            PaneLayout::Pane(index) => (*index == pane).then(Vec::new),
+            PaneLayout::Split { first, second, .. } => {
+                for (part, layout) in [(false, first), (true, second)] {
+                    if let Some(mut path) = layout.path_to(pane) {
+                        path.insert(0, part);
+                        return Some(path);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    // Splits the pane in two, the new pane takes the second part
+    fn split(&mut self, pane: usize, new_pane: usize, direction: SplitDirection) {
+        match self {
+            PaneLayout::Pane(index) if *index == pane => {
+                *self = PaneLayout::Split {
+                    direction,
+                    ratio: 50,
+                    first: Box::new(PaneLayout::Pane(pane)),
+                    second: Box::new(PaneLayout::Pane(new_pane)),
+                };
+            },
+            PaneLayout::Pane(_) => {},
+            PaneLayout::Split { first, second, .. } => {
+                first.split(pane, new_pane, direction);
+                second.split(pane, new_pane, direction);
+            }
+        }
+    }
+
+    // Removes a pane giving its space to the other part of its split
+    fn remove(&mut self, pane: usize) {
+        if let PaneLayout::Split { first, second, .. } = self {
+            if **first == PaneLayout::Pane(pane) {
+                *self = (**second).clone();
+            } else if **second == PaneLayout::Pane(pane) {
+                *self = (**first).clone();
+            } else {
+                first.remove(pane);
+                second.remove(pane);
+            }
+        }
+    }
+
+    // The panes after a removed one take the index before
+    fn renumber_after(&mut self, pane: usize) {
+        match self {
+            PaneLayout::Pane(index) if *index > pane => *index -= 1,
+            PaneLayout::Pane(_) => {},
+            PaneLayout::Split { first, second, .. } => {
+                first.renumber_after(pane);
+                second.renumber_after(pane);
+            }
+        }
+    }
+
+    // The pane on the side of the layout where `direction` comes from
+    fn edge(&self, direction: PaneDirection) -> usize {
+        match self {
+            PaneLayout::Pane(index) => *index,
+            PaneLayout::Split { direction: split, first, second, .. } => {
+                if *split == direction.axis() && !direction.forward() {
+                    second.edge(direction)
+                } else {
+                    first.edge(direction)
+                }
+            }
+        }
+    }
+
+    // The pane getting the space of the given one when it is removed
+    fn sibling(&self, pane: usize) -> Option<usize> {
+        match self {
+            PaneLayout::Pane(_) => None,
+            PaneLayout::Split { first, second, .. } => {
+                if **first == PaneLayout::Pane(pane) {
+                    Some(second.edge(PaneDirection::Right))
+                } else if **second == PaneLayout::Pane(pane) {
+                    Some(first.edge(PaneDirection::Left))
+                } else {
+                    first.sibling(pane).or_else(|| second.sibling(pane))
+                }
+            }
+        }
+    }
+
+    // The pane next to the given one in a direction
+    fn neighbour(&self, pane: usize, direction: PaneDirection) -> Option<usize> {
+        let path = self.path_to(pane)?;
+        let mut layout = self;
+        let mut target = None;
+        for part in path {
+            let PaneLayout::Split { direction: split, first, second, .. } = layout else {
+                break;
+            };
+            //the deepest split along the direction with the pane on the side the direction leaves from
+            if *split == direction.axis() && part != direction.forward() {
+                target = Some(if part { first } else { second });
+            }
+            layout = if part { second } else { first };
+        }
+        target.map(|layout| layout.edge(direction))
+    }
+
+    // Moves by `step` percent the divider of the deepest split along the direction holding the pane
+    fn resize(&mut self, pane: usize, direction: PaneDirection, step: u16) {
+        let Some(path) = self.path_to(pane) else {
+            return;
+        };
+        let mut layout = self;
+        let mut target: Option<&mut u16> = None;
+        for part in path {
+            let PaneLayout::Split { direction: split, ratio, first, second } = layout else {
+                break;
+            };
+            if *split == direction.axis() {
+                target = Some(ratio);
+            }
+            layout = if part { second } else { first };
+        }
+        if let Some(ratio) = target {
+            *ratio = if direction.forward() { *ratio + step } else { ratio.saturating_sub(step) }.clamp(10, 90);
+        }
+    }
+}
+
+// Cursor, selection and scroll of a pane, kept while another pane has the focus
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PaneView {
+    cursor: Point,
+    selection: Option<CodeSelection>,
+    viewport: CodeViewport,
+}
+
+impl PaneView {
+
+    fn of(code_component: &CodeComponent) -> PaneView {
+        PaneView {
+            cursor: code_component.current.get_cursor().clone(),
+            selection: code_component.current.get_selection().clone(),
+            viewport: code_component.viewport.clone(),
+        }
+    }
+
+    // Puts the cursor and the selection in the code, inside it if it got shorter since
+    pub fn apply(&self, code: &mut Code) {
+        let clamp = |code: &Code, point: &Point| code.char_to_point(code.point_to_char(point));
+        *code.get_mut_cursor() = clamp(code, &self.cursor);
+        let selection = self.selection.as_ref().map(|selection| CodeSelection::new(clamp(code, selection.get_start()), clamp(code, selection.get_end())));
+        code.set_selection(selection);
+        code.flush_cursors();
+    }
+
+    pub fn get_viewport(&self) -> &CodeViewport {
+        &self.viewport
+    }
+}
+
+// A pane shows a buffer, the focused one shows the buffer in the fields of the code component
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CodePane {
+    buffer: usize,
+    view: PaneView,
+    // cells taken by the pane and the ones where its lines were drawn
+    frame: ScreenArea,
+    area: ScreenArea,
+}
+
+// The panes the code panel is split in and the one with the focus
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodePanes {
+    layout: PaneLayout,
+    panes: Vec<CodePane>,
+    focused: usize,
+}
+
+impl Default for CodePanes {
+    fn default() -> CodePanes {
+        CodePanes {
+            layout: PaneLayout::Pane(0),
+            panes: vec![CodePane::default()],
+            focused: 0,
+        }
+    }
+}
+
+impl CodePanes {
+
+    pub fn get_layout(&self) -> &PaneLayout {
+        &self.layout
+    }
+
+    pub fn get_focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn len(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.panes.is_empty()
+    }
+
+    // Index of the buffer shown by a pane, for the focused pane only up to the last time it lost the focus
+    pub fn get_buffer(&self, pane: usize) -> usize {
+        self.panes[pane].buffer
+    }
+
+    pub fn get_view(&self, pane: usize) -> &PaneView {
+        &self.panes[pane].view
+    }
+
+    pub fn get_area(&self, pane: usize) -> ScreenArea {
+        self.panes[pane].area
+    }
+
+    // Keeps where a pane and its lines are drawn, the viewport of a pane without the focus takes the size of the lines
+    pub fn set_area(&mut self, pane: usize, frame: ScreenArea, area: ScreenArea) {
+        self.panes[pane].frame = frame;
+        self.panes[pane].area = area;
+        if pane != self.focused {
+            self.panes[pane].view.viewport.set_size(area.get_width() as usize, area.get_height() as usize);
+        }
+    }
+
+    // Pane drawn on a cell of the screen
+    pub fn pane_at(&self, column: u16, row: u16) -> Option<usize> {
+        self.panes.iter().position(|pane| pane.frame.contains(column, row))
+    }
+
+    pub fn get_mut_viewport(&mut self, pane: usize) -> &mut CodeViewport {
+        &mut self.panes[pane].view.viewport
+    }
+
+    // A buffer was opened at `index` of the tab bar
+    pub fn buffer_inserted(&mut self, index: usize) {
+        for pane in &mut self.panes {
+            if pane.buffer >= index {
+                pane.buffer += 1;
+            }
+        }
+    }
+
+    // The buffer at `index` was closed, its panes show the buffer taking its place from the start
+    pub fn buffer_removed(&mut self, index: usize, count: usize) {
+        for pane in &mut self.panes {
+            if pane.buffer > index {
+                pane.buffer -= 1;
+            } else if pane.buffer == index {
+                pane.buffer = index.min(count.saturating_sub(1));
+                pane.view = PaneView { viewport: pane.view.viewport.clone(), ..PaneView::default() };
+                pane.view.viewport.reset();
+            }
+        }
+    }
+
+    pub fn buffers_swapped(&mut self, first: usize, second: usize) {
+        for pane in &mut self.panes {
+            if pane.buffer == first {
+                pane.buffer = second;
+            } else if pane.buffer == second {
+                pane.buffer = first;
+            }
+        }
+    }
+}
+
+// Gives the focus to a pane, showing its buffer with its cursor and scroll
+pub fn focus_pane(code_component: &mut CodeComponent, pane: usize) {
+    let focused = code_component.panes.focused;
+    if pane == focused || pane >= code_component.panes.len() {
+        return;
+    }
+    code_component.panes.panes[focused].buffer = code_component.active;
+    code_component.panes.panes[focused].view = PaneView::of(code_component);
+    code_component.panes.focused = pane;
+    let CodePane { buffer, view, .. } = code_component.panes.panes[pane].clone();
+    switch_buffer(code_component, buffer);
+    view.apply(&mut code_component.current);
+    code_component.viewport = view.viewport;
+    code_component.history.close_group();
+}
+
+// Splits the focused pane in two showing the same buffer, the new pane gets the focus
+pub fn handle_split(code_component: &mut CodeComponent, direction: SplitDirection) {
+    let focused = code_component.panes.focused;
+    let new_pane = code_component.panes.len();
+    code_component.panes.panes.push(CodePane {
+        buffer: code_component.active,
+        view: PaneView::of(code_component),
+        frame: code_component.panes.panes[focused].frame,
+        area: code_component.panes.panes[focused].area,
+    });
+    code_component.panes.layout.split(focused, new_pane, direction);
+    focus_pane(code_component, new_pane);
+}
+
+// Closes the focused pane, the focus goes to the pane taking its space. The buffer stays open
+pub fn handle_close_pane(code_component: &mut CodeComponent) {
+    let focused = code_component.panes.focused;
+    let Some(next) = code_component.panes.layout.sibling(focused) else {
+        return;
+    };
+    focus_pane(code_component, next);
+    code_component.panes.panes.remove(focused);
+    code_component.panes.layout.remove(focused);
+    code_component.panes.layout.renumber_after(focused);
+    code_component.panes.focused = if next > focused { next - 1 } else { next };
+}
+
+// Gives the focus to the pane next to the focused one in a direction
+pub fn handle_focus_pane(code_component: &mut CodeComponent, direction: PaneDirection) {
+    if let Some(pane) = code_component.panes.layout.neighbour(code_component.panes.focused, direction) {
+        focus_pane(code_component, pane);
+    }
+}
+
+// Moves the divider next to the focused pane in a direction
+pub fn handle_resize_pane(code_component: &mut CodeComponent, direction: PaneDirection) {
+    let focused = code_component.panes.focused;
+    code_component.panes.layout.resize(focused, direction, 5);
+}
@@ -0,0 +1,384 @@
+use std::ops::Range;
+
+use crossterm::event::KeyEvent;
+use regex::{Regex, RegexBuilder};
+
+use crate::state::{AppContext, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
+
+use super::{CodeComponent, code::Code};
+
+// State of the search prompt of the code, matches are char ranges of the rope
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CodeSearch {
+    query: String,
+    regex: bool,
+    case_sensitive: bool,
+    whole_word: bool,
+    matches: Vec<Range<usize>>,
+    current: Option<usize>,
+    // where the cursor was when the prompt opened, typing searches from there
+    origin: usize,
+    error: Option<String>,
+    // text replacing the matches, the prompt is a find and replace one when it is set
+    replacement: Option<String>,
+    editing_replacement: bool,
+    // chars the search is limited to, and the selection that can become the scope
+    scope: Option<Range<usize>>,
+    opened_selection: Option<Range<usize>>,
+}
+
+impl CodeSearch {
+
+    pub fn new(query: String, origin: usize) -> CodeSearch {
+        CodeSearch {
+            query,
+            origin,
+            ..CodeSearch::default()
+        }
+    }
+
+    pub fn get_query(&self) -> &String {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+    }
+
+    pub fn is_regex(&self) -> bool {
+        self.regex
+    }
+
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub fn is_whole_word(&self) -> bool {
+        self.whole_word
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.regex = !self.regex;
+    }
+
+    pub fn toggle_case_sensitive(&mut self) {
+        self.case_sensitive = !self.case_sensitive;
+    }
+
+    pub fn toggle_whole_word(&mut self) {
+        self.whole_word = !self.whole_word;
+    }
+
+    pub fn get_matches(&self) -> &Vec<Range<usize>> {
+        &self.matches
+    }
+
+    // Index of the selected match
+    pub fn get_current(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn get_current_match(&self) -> Option<Range<usize>> {
+        self.current.and_then(|current| self.matches.get(current).cloned())
+    }
+
+    // Message of an invalid regex
+    pub fn get_error(&self) -> &Option<String> {
+        &self.error
+    }
+
+    pub fn get_replacement(&self) -> &Option<String> {
+        &self.replacement
+    }
+
+    pub fn set_replacement(&mut self, replacement: Option<String>) {
+        self.replacement = replacement;
+    }
+
+    pub fn is_editing_replacement(&self) -> bool {
+        self.editing_replacement && self.replacement.is_some()
+    }
+
+    pub fn get_scope(&self) -> &Option<Range<usize>> {
+        &self.scope
+    }
+
+    pub fn set_scope(&mut self, scope: Option<Range<usize>>) {
+        self.scope = scope;
+    }
+
+    // Limits the search to the selection the prompt was opened with, or to the whole code again
+    pub fn toggle_scope(&mut self) {
+        if self.scope.is_some() {
+            self.scope = None;
+        } else {
+            self.scope = self.opened_selection.clone();
+        }
+    }
+
+    // Text replacing each match in the scope, regex replacements can refer to capture groups as $1 or ${name}
+    pub fn get_replacements(&self, code: &Code) -> Vec<(Range<usize>, String)> {
+        let (Some(replacement), Ok(regex)) = (&self.replacement, self.build_regex()) else {
+            return vec![];
+        };
+        let text = code.get_text();
+        let rope = code.get_rope();
+        regex
+        .captures_iter(&text)
+        .filter_map(|captures| {
+            let found = captures.get(0)?;
+            let range = rope.byte_to_char(found.start())..rope.byte_to_char(found.end());
+            if found.is_empty() || !self.in_scope(&range) {
+                return None;
+            }
+            let mut result = String::new();
+            if self.regex {
+                captures.expand(replacement, &mut result);
+            } else {
+                result.push_str(replacement);
+            }
+            Some((range, result))
+        })
+        .collect()
+    }
+
+    fn in_scope(&self, range: &Range<usize>) -> bool {
+        self.scope.as_ref().is_none_or(|scope| range.start >= scope.start && range.end <= scope.end)
+    }
+
+    // Regex for the query with the options of the prompt, plain text is escaped
+    pub fn build_regex(&self) -> Result<Regex, regex::Error> {
+        let mut pattern = if self.regex { self.query.clone() } else { regex::escape(&self.query) };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        RegexBuilder::new(&pattern)
+        .case_insensitive(!self.case_sensitive)
+        .multi_line(true)
+        .build()
+    }
+
+    // Searches again the whole code, selecting the first match after the origin
+    pub fn update(&mut self, code: &Code) {
+        self.matches.clear();
+        self.current = None;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        match self.build_regex() {
+            Ok(regex) => {
+                let text = code.get_text();
+                let rope = code.get_rope();
+                self.matches = regex
+                .find_iter(&text)
+                .filter(|found| !found.is_empty())
+                .map(|found| rope.byte_to_char(found.start())..rope.byte_to_char(found.end()))
+                .filter(|found| self.in_scope(found))
+                .collect();
+                self.current = self.first_after(self.origin);
+            },
+            Err(error) => {
+                self.error = Some(error.to_string());
+            }
+        }
+    }
+
+    // First match starting at or after `char_idx`, the first of the code if there are none after it
+    fn first_after(&self, char_idx: usize) -> Option<usize> {
+        if self.matches.is_empty() {
+            None
+        } else {
+            Some(self.matches.iter().position(|found| found.start >= char_idx).unwrap_or(0))
+        }
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = Some(self.current.map(|current| (current + 1) % self.matches.len()).unwrap_or(0));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            let len = self.matches.len();
+            self.current = Some(self.current.map(|current| (current + len - 1) % len).unwrap_or(len - 1));
+        }
+    }
+
+    // Keeps the search going from `char_idx`, used after the code changed
+    pub fn set_origin(&mut self, char_idx: usize) {
+        self.origin = char_idx;
+    }
+}
+
+// Opens the search prompt, starting from the selected text if there is one on a single line
+pub fn handle_open_search(code_component: &mut CodeComponent) {
+    let code = &code_component.current;
+    let query = code.get_selected_text().filter(|text| !text.contains('\n')).unwrap_or_default();
+    let origin = code.get_selection_range().map(|range| range.start).unwrap_or(code.point_to_char(code.get_cursor()));
+    let mut search = CodeSearch::new(query, origin);
+    search.opened_selection = code.get_selection_range().filter(|range| !range.is_empty());
+    search.update(code);
+    code_component.search = Some(search);
+    select_current_match(code_component);
+}
+
+// Opens the find and replace prompt, a selection over more lines becomes the scope of the replacements
+pub fn handle_open_replace(code_component: &mut CodeComponent) {
+    let multiline = code_component.current.get_selected_text().is_some_and(|text| text.contains('\n'));
+    if code_component.search.is_none() {
+        handle_open_search(code_component);
+    }
+    if let Some(search) = &mut code_component.search {
+        if multiline {
+            search.scope = search.opened_selection.clone();
+            search.update(&code_component.current);
+        }
+        if search.replacement.is_none() {
+            search.replacement = Some(String::new());
+        }
+        search.editing_replacement = !search.query.is_empty();
+    }
+}
+
+// Replaces the selected match and moves to the next one
+pub fn handle_replace_one(code_component: &mut CodeComponent) {
+    let Some(search) = &code_component.search else {
+        return;
+    };
+    let Some(current) = search.get_current_match() else {
+        return;
+    };
+    let replacement = search.get_replacements(&code_component.current).into_iter().find(|(range, _)| *range == current);
+    if let Some((range, text)) = replacement {
+        replace_ranges(code_component, vec![(range.clone(), text.clone())]);
+        if let Some(search) = &mut code_component.search {
+            search.origin = range.start + text.chars().count();
+            search.update(&code_component.current);
+        }
+        select_current_match(code_component);
+    }
+}
+
+// Replaces every match in the scope, the edits of one call are undone together
+pub fn handle_replace_all(code_component: &mut CodeComponent) {
+    let Some(search) = &code_component.search else {
+        return;
+    };
+    let replacements = search.get_replacements(&code_component.current);
+    let count = replacements.len();
+    if count == 0 {
+        return;
+    }
+    //the cursor goes after the last replacement, shifted by the length changes of the ones before it
+    let last = replacements.last().map(|(range, text)| range.start + text.chars().count()).unwrap_or(0);
+    let delta: isize = replacements[..count - 1].iter().map(|(range, text)| text.chars().count() as isize - range.len() as isize).sum();
+    replace_ranges(code_component, replacements);
+
+    let code = &mut code_component.current;
+    code.flush_selection();
+    let at = code.char_to_point(last.checked_add_signed(delta).unwrap_or(0).min(code.len_chars()));
+    *code.get_mut_cursor() = at;
+    if let Some(search) = &mut code_component.search {
+        search.update(code);
+    }
+}
+
+// Applies the replacements from the last one, so the char ranges of the previous ones stay valid
+fn replace_ranges(code_component: &mut CodeComponent, mut replacements: Vec<(Range<usize>, String)>) {
+    replacements.sort_by_key(|(range, _)| range.start);
+    let code = &mut code_component.current;
+    let mut delta: isize = 0;
+    for (range, text) in replacements.iter().rev() {
+        code.remove(range.clone());
+        code.insert(range.start, text);
+        delta += text.chars().count() as isize - range.len() as isize;
+    }
+    //the scope grows or shrinks with the text replaced inside it
+    if let Some(search) = &mut code_component.search {
+        if let Some(scope) = &mut search.scope {
+            scope.end = scope.end.saturating_add_signed(delta);
+        }
+    }
+}
+
+// Keys of the search prompt, resolved in its keymap scope: keys without an action are typed in the field edited.
+// Confirming on the replacement replaces the current match, on the query it goes to the next one
+pub fn handle_search(code_component: &mut CodeComponent, context: &mut AppContext, key: KeyEvent) {
+    let Some(search) = &mut code_component.search else {
+        return;
+    };
+    let editing_replacement = search.is_editing_replacement();
+    match context.resolve_key(KeyScope::CodeSearch, key) {
+        KeyMatch::Action(Action::Leave) => {
+            code_component.search = None;
+            return;
+        },
+        KeyMatch::Action(Action::Replace) => {
+            handle_open_replace(code_component);
+            return;
+        },
+        KeyMatch::Action(Action::SwitchField) if search.replacement.is_some() => {
+            search.editing_replacement = !search.editing_replacement;
+        },
+        KeyMatch::Action(Action::Confirm) if editing_replacement => {
+            handle_replace_one(code_component);
+            return;
+        },
+        KeyMatch::Action(Action::ReplaceAll) if search.replacement.is_some() => {
+            handle_replace_all(code_component);
+            return;
+        },
+        KeyMatch::Action(Action::ToggleInSelection) => {
+            search.toggle_scope();
+            search.update(&code_component.current);
+        },
+        KeyMatch::Action(Action::Confirm | Action::NextMatch) => search.next(),
+        KeyMatch::Action(Action::PreviousMatch) => search.previous(),
+        KeyMatch::Action(Action::ToggleCase) => {
+            search.toggle_case_sensitive();
+            search.update(&code_component.current);
+        },
+        KeyMatch::Action(Action::ToggleWholeWord) => {
+            search.toggle_whole_word();
+            search.update(&code_component.current);
+        },
+        KeyMatch::Action(Action::ToggleRegex) => {
+            search.toggle_regex();
+            search.update(&code_component.current);
+        },
+        KeyMatch::Action(Action::DeleteBackward) if editing_replacement => {
+            if let Some(replacement) = &mut search.replacement {
+                replacement.pop();
+            }
+        },
+        KeyMatch::Action(Action::DeleteBackward) => {
+            search.query.pop();
+            search.update(&code_component.current);
+        },
+        KeyMatch::Unbound => match (typed_char(&key), &mut search.replacement) {
+            (Some(char), Some(replacement)) if editing_replacement => replacement.push(char),
+            (Some(char), _) => {
+                search.query.push(char);
+                search.update(&code_component.current);
+            },
+            _ => {}
+        },
+        _ => {}
+    }
+    select_current_match(code_component);
+}
+
+// Selects the current match in the code, with the cursor at its end
+fn select_current_match(code_component: &mut CodeComponent) {
+    let found = code_component.search.as_ref().and_then(|search| search.get_current_match());
+    if let Some(found) = found {
+        let code = &mut code_component.current;
+        let start = code.char_to_point(found.start);
+        let end = code.char_to_point(found.end);
+        *code.get_mut_cursor() = end.clone();
+        code.create_selection(start, end);
+    }
+}
@@ -1,7 +1,9 @@
 
+use serde::{Serialize, Deserialize};
+
 use crate::state::code::code_utils::Point;
 
-#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CodeSelection {
     start: Point,
     end: Point
@@ -1,12 +1,14 @@
-use std::{error::Error, fs::{OpenOptions, File}, io::Write, cmp::{min, max}};
+use std::{error::Error, fs::{self, File}, io::{self, Write}, path::Path, process, time::SystemTime, cmp::min};
 
 use clipboard::{ClipboardContext, ClipboardProvider};
-use crossterm::event::{Event, KeyModifiers};
+use crossterm::event::{Event, KeyModifiers, KeyEvent};
 
-use crate::state::AppContext;
+use crate::state::{AppContext, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
 
-use super::{CodeComponent, code_selection::CodeSelection, code::Line};
-#[derive(Default, Debug, Clone, PartialEq, Eq)]
+use serde::{Serialize, Deserialize};
+
+use super::{CodeComponent, code_selection::CodeSelection, code::Code, code_history::parse_duration, code_cursor::{for_each_cursor, get_selected_texts}, code_brackets::{handle_pair_char, delete_empty_pair}, code_indent::{enter_text, dedent_closer}};
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Point {
     x: usize,
     y: usize
@@ -68,8 +70,8 @@ impl Point {
     pub fn move_up(&mut self, exceed: bool, limit: usize) {
         if !exceed && self.x > 0 {
             self.x -=1;
-            if self.y > limit - 1 {
-                self.y = limit - 1;
+            if self.y > limit.saturating_sub(1) {
+                self.y = limit.saturating_sub(1);
             }
         } else if exceed {
             self.y = 0;
@@ -79,9 +81,9 @@ impl Point {
     pub fn move_left(&mut self, exceed: bool, limit: usize) {
         if self.y > 0 {
             self.y -= 1;
-        } else if exceed && self.y == 0 {
+        } else if exceed && self.x > 0 {
             self.x -= 1;
-            self.y = limit - 1;
+            self.y = limit.saturating_sub(1);
         }
     }
 
@@ -114,6 +116,28 @@ impl Point {
     }
 }
 
+// Pulls the cursor and the selection back inside the buffer after a move,
+// a selection left without any text is removed
+fn keep_in_buffer(code: &mut Code) {
+    let cursor = code.clamp_point(code.get_cursor());
+    *code.get_mut_cursor() = cursor;
+    if let Some(selection) = code.get_selection().clone() {
+        let start = code.clamp_point(selection.get_start());
+        let end = code.clamp_point(selection.get_end());
+        if start == end {
+            code.flush_selection();
+        } else {
+            code.create_selection(start, end);
+        }
+    }
+}
+
+// Column of the line `target` drawn under the cursor
+pub fn vertical_column(code: &Code, cursor: &Point, target: usize) -> usize {
+    let width = code.get_line(cursor.get_x()).map(|line| line.width(cursor.get_y())).unwrap_or(0);
+    code.get_line(target).map(|line| line.column_at_width(width)).unwrap_or(0)
+}
+
 pub fn handle_up(code_component: &mut CodeComponent, event: Event) {
     let readable_current_code = code_component.get_current().clone();
     let readable_cursor = readable_current_code.get_cursor().clone();
@@ -129,12 +153,12 @@ pub fn handle_up(code_component: &mut CodeComponent, event: Event) {
     }
 
     if let Some(current) = readable_current_code.get_line(readable_cursor.get_x()) {
-        current_size = current.get_string().len();
+        current_size = current.len();
     } 
 
     if readable_current_code.get_cursor().get_x() > 0 {
        if let Some(upper) = readable_current_code.get_line(readable_cursor.get_x()-1) {
-            upper_size = upper.get_string().len();
+            upper_size = upper.len();
        } 
     }
 
@@ -201,7 +225,10 @@ pub fn handle_up(code_component: &mut CodeComponent, event: Event) {
         } else if !is_selecting && !is_shift {
 
             if readable_cursor.get_x() > 0 {
-                mutable_code.get_mut_cursor().move_up(false, upper_size);
+                //keep the cursor on the same on screen column, wide graphemes cover more than one cell
+                let column = vertical_column(&readable_current_code, &readable_cursor, readable_cursor.get_x() - 1);
+                mutable_code.get_mut_cursor().set_x(readable_cursor.get_x() - 1);
+                mutable_code.get_mut_cursor().set_y(column);
             } else {
                 mutable_code.get_mut_cursor().move_up(true, upper_size);
             }
@@ -210,6 +237,7 @@ pub fn handle_up(code_component: &mut CodeComponent, event: Event) {
 
     }
 
+    keep_in_buffer(code_component.get_mut_current());
 }
 
 pub fn handle_down(code_component: &mut CodeComponent, event: Event) {
@@ -221,7 +249,7 @@ pub fn handle_down(code_component: &mut CodeComponent, event: Event) {
     let mut is_shift = false;
     let mut current_size = 0;
     let mut lower_size = 0;
-    let nlines = readable_current_code.get_content().len();
+    let nlines = readable_current_code.len_lines();
 
     if let Some(selection) = readable_current_code.get_selection() {
         is_selecting = true;
@@ -229,11 +257,11 @@ pub fn handle_down(code_component: &mut CodeComponent, event: Event) {
     }
 
     if let Some(current) = readable_current_code.get_line(readable_cursor.get_x()) {
-        current_size = current.get_string().len();
+        current_size = current.len();
     }
 
     if let Some(lower) = readable_current_code.get_line(readable_cursor.get_x() + 1) {
-        lower_size = lower.get_string().len();
+        lower_size = lower.len();
     }
 
     if let Event::Key(key) = event {
@@ -295,7 +323,10 @@ pub fn handle_down(code_component: &mut CodeComponent, event: Event) {
         } else if !is_selecting && !is_shift {
 
             if readable_cursor.get_x() < nlines - 1 {
-                mutable_code.get_mut_cursor().move_down(false, current_size, lower_size);
+                //keep the cursor on the same on screen column, wide graphemes cover more than one cell
+                let column = vertical_column(&readable_current_code, &readable_cursor, readable_cursor.get_x() + 1);
+                mutable_code.get_mut_cursor().set_x(readable_cursor.get_x() + 1);
+                mutable_code.get_mut_cursor().set_y(column);
             } else {
                 mutable_code.get_mut_cursor().move_down(true, current_size, lower_size);
             }
@@ -304,6 +335,7 @@ pub fn handle_down(code_component: &mut CodeComponent, event: Event) {
 
     }
 
+    keep_in_buffer(code_component.get_mut_current());
 }
 
 pub fn handle_left(code_component: &mut CodeComponent, event: Event) {
@@ -321,12 +353,12 @@ pub fn handle_left(code_component: &mut CodeComponent, event: Event) {
     }
 
     if let Some(current) = readable_current_code.get_line(readable_cursor.get_x()) {
-        current_size = current.get_string().len();
+        current_size = current.len();
     }
 
     if readable_current_code.get_cursor().get_x() > 0 {
        if let Some(upper) = readable_current_code.get_line(readable_cursor.get_x()-1) {
-            upper_size = upper.get_string().len();
+            upper_size = upper.len();
        } 
     }
 
@@ -366,7 +398,7 @@ pub fn handle_left(code_component: &mut CodeComponent, event: Event) {
 
             if start.get_x() > end.get_x() {
                 mutable_code.get_mut_cursor().set_x(end.get_x());
-                mutable_code.get_mut_cursor().set_y(end.get_y()-1);    
+                mutable_code.get_mut_cursor().set_y(end.get_y().saturating_sub(1));    
             } else if start.get_x() < end.get_x() {
                 mutable_code.get_mut_cursor().set_x(start.get_x());
                 mutable_code.get_mut_cursor().set_y(start.get_y());    
@@ -376,7 +408,7 @@ pub fn handle_left(code_component: &mut CodeComponent, event: Event) {
                     mutable_code.get_mut_cursor().set_y(start.get_y());
                 } else if start.get_y() > end.get_y() {
                     mutable_code.get_mut_cursor().set_x(end.get_x());
-                    mutable_code.get_mut_cursor().set_y(end.get_y()-1);
+                    mutable_code.get_mut_cursor().set_y(end.get_y().saturating_sub(1));
                 }
             }
             mutable_code.flush_selection();
@@ -394,12 +426,14 @@ pub fn handle_left(code_component: &mut CodeComponent, event: Event) {
             if readable_cursor.get_x() == 0 {
                 mutable_code.get_mut_cursor().move_left(false, upper_size);
             } else {
-                mutable_code.get_mut_cursor().move_left(true, upper_size);
+                //the cursor can also stand after the last char of the upper line
+                mutable_code.get_mut_cursor().move_left(true, upper_size + 1);
             }
         }
 
 
     }
+    keep_in_buffer(code_component.get_mut_current());
 }
 
 pub fn handle_right(code_component: &mut CodeComponent, event: Event) {
@@ -416,7 +450,7 @@ pub fn handle_right(code_component: &mut CodeComponent, event: Event) {
     }
 
     if let Some(current) = readable_current_code.get_line(readable_cursor.get_x()) {
-        current_size = current.get_string().len();
+        current_size = current.len();
     }
 
     if let Event::Key(key) = event {
@@ -484,7 +518,7 @@ pub fn handle_right(code_component: &mut CodeComponent, event: Event) {
 
         } else if !is_selecting && !is_shift {
 
-            if readable_cursor.get_x() == readable_current_code.get_content().len() - 1 {
+            if readable_cursor.get_x() == readable_current_code.len_lines() - 1 {
                 mutable_code.get_mut_cursor().move_right(false, current_size);
             } else {
                 mutable_code.get_mut_cursor().move_right(true, current_size);
@@ -493,75 +527,37 @@ pub fn handle_right(code_component: &mut CodeComponent, event: Event) {
 
 
     }
+    keep_in_buffer(code_component.get_mut_current());
 }
 
 pub fn handle_cut (code_component: &mut CodeComponent) {
-    let cut;
-    if let Some(selection) = code_component.get_current().get_selection() {
-        if selection.get_start() != selection.get_end() {
-            let mut code: Vec<String> = vec![];
-            for i in selection.get_start().get_x()..selection.get_end().get_x() {
-                if let Some(line) = code_component.get_current().get_line(i) {
-                    let selected_line: String;
-                    if line.get_number() == selection.get_start().get_x() {
-                        selected_line = line.get_string()[selection.get_start().get_y()..].to_string();
-                        if let Some(current_string) = code.get(line.get_number()) {
-                            let new_string: String = current_string.replace(&selected_line, "").clone();
-                            code.push(new_string.clone());    
-                        }
-                    } else if line.get_number() == selection.get_end().get_x() {
-                        selected_line = line.get_string()[..selection.get_end().get_y()].to_string();
-                        if let Some(current_string) = code.get(line.get_number()) {
-                            let new_string: String = current_string.replace(&selected_line, "").clone();
-                            code.push(new_string.clone());    
-                        }
-                    } else {
-                        code.push(line.get_string());
-                    }
-                }
+    let cut = get_selected_texts(code_component.get_current());
+    if !cut.is_empty() {
+        let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
+        if let Ok(mut context) =  clipboard {
+            let _ = context.set_contents(cut.join("\n"));
+        } 
+
+        for_each_cursor(code_component, |code_component| {
+            let mutable_code = code_component.get_mut_current();
+            if let Some(range) = mutable_code.get_selection_range() {
+                let start = mutable_code.char_to_point(range.start);
+                mutable_code.delete_selection();
+                *mutable_code.get_mut_cursor() = start;
             }
-            cut = code.join("\n").to_string();
-            let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
-            if let Ok(mut context) =  clipboard {
-                let _ = context.set_contents(cut);
-            } 
-
-            code_component.get_mut_current().flush_selection();
-        }
+            mutable_code.flush_selection();
+        });
     }
 }
 
 pub fn handle_copy(code_component: &mut CodeComponent) {
-    let copy;
-    if let Some(selection) = code_component.get_current().get_selection() {
-        if selection.get_start() != selection.get_end() {
-            let mut code: Vec<String> = vec![];
-            for i in selection.get_start().get_x()..selection.get_end().get_x() {
-                if let Some(line) = code_component.get_current().get_line(i) {
-                    let selected_line: String;
-                    if line.get_number() == selection.get_start().get_x() {
-                        selected_line = line.get_string()[selection.get_start().get_y()..].to_string();
-                        if let Some(current_string) = code.get(line.get_number()) {
-                            let new_string: String = current_string.replace(&selected_line, "").clone();
-                            code.push(new_string.clone());    
-                        }
-                    } else if line.get_number() == selection.get_end().get_x() {
-                        selected_line = line.get_string()[..selection.get_end().get_y()].to_string();
-                        if let Some(current_string) = code.get(line.get_number()) {
-                            let new_string: String = current_string.replace(&selected_line, "").clone();
-                            code.push(new_string.clone());    
-                        }
-                    } else {
-                        code.push(line.get_string());
-                    }
-                }
-            }
-            copy = code.join("\n").to_string();
-            let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
-            if let Ok(mut context) =  clipboard {
-                let _ = context.set_contents(copy);
-            } 
-        }
+    //the text selected by each cursor goes on its own line
+    let copy = get_selected_texts(code_component.get_current());
+    if !copy.is_empty() {
+        let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
+        if let Ok(mut context) =  clipboard {
+            let _ = context.set_contents(copy.join("\n"));
+        } 
     }
 }
 
@@ -569,142 +565,215 @@ pub fn handle_paste(code_component: &mut CodeComponent) {
     let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
     if let Ok(mut context) =  clipboard {
         if let Ok(contents) = context.get_contents() {
-            contents.split("\n").for_each(|line| {
-                let number = code_component.current.get_content().into_iter().map(|line| line.get_number()).max().take().unwrap() + 1;
-                let line = Line::new(number, line.to_string());
-                let _ = code_component.current.add_line(line);
-            });                                
+            paste_text(code_component, contents);
         }
     }
 }
 
-pub fn handle_save(code_component: &mut CodeComponent, context: &mut AppContext) {
-    code_component.history.use_last();
-    let code = code_component.history.get_current_code();
-    let utf8_code = code.to_string().chars().map(|char| char as u8).fold(vec![], |mut vec, char| {
-        vec.push(char);
-        vec
+// Pastes `contents` at every cursor, one line each if there are as many lines as cursors
+pub fn paste_text(code_component: &mut CodeComponent, contents: String) {
+    let cursors = code_component.get_current().get_cursors().len() + 1;
+    let lines: Vec<&str> = contents.split('\n').collect();
+    let mut pieces: Vec<String> = if cursors > 1 && lines.len() == cursors {
+        lines.iter().map(|line| line.to_string()).collect()
+    } else {
+        vec![contents.clone(); cursors]
+    };
+    pieces.reverse();
+
+    for_each_cursor(code_component, |code_component| {
+        let contents = pieces.pop().unwrap_or_default();
+        let mutable_code = code_component.get_mut_current();
+        if let Some(range) = mutable_code.get_selection_range() {
+            let start = mutable_code.char_to_point(range.start);
+            mutable_code.delete_selection();
+            *mutable_code.get_mut_cursor() = start;
+            mutable_code.flush_selection();
+        }
+        let at = mutable_code.point_to_char(mutable_code.get_cursor());
+        mutable_code.insert(at, contents.as_str());
+        let end = mutable_code.char_to_point(at + contents.chars().count());
+        *mutable_code.get_mut_cursor() = end;
     });
-    if let Some(path) = context.active_file() {
-        if path.is_file() {
-            let f = OpenOptions::new().append(true).open(path);
-            if let Ok(mut file) = f {
-                let _ = file.write_all(&utf8_code);
-            }    
+}
+
+pub fn handle_save(code_component: &mut CodeComponent, context: &mut AppContext) {
+    let Some(path) = code_component.get_path().clone().or(context.active_file().clone()) else {
+        context.set_message(Some("Nothing to save, open a file first".to_string()));
+        return;
+    };
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    let text = code_component.format.apply(code_component.current.get_text().as_str());
+    match write_atomic(&path, text.as_bytes()) {
+        Ok(()) => {
+            code_component.mark_saved();
+            code_component.store_history(context.project_root());
+            context.set_message(Some(format!("Saved {}", name)));
+        },
+        Err(error) => {
+            context.set_message(Some(format!("Could not save {}: {}", name, error)));
         }
-    } else if let Some(path) = context.active_file() {
-        let f = File::create(path);
-        if let Ok(mut file) = f {
-            let _ = file.write_all(&utf8_code);
+    }
+}
+
+// Replaces the file at `path` with `contents` without ever leaving it half written:
+// the bytes go to a temporary file in the same folder which is synced and renamed over the original
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    //a link is kept, the file it points to is replaced
+    let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    let folder = path.parent().filter(|folder| !folder.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().ok_or(io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let temporary = folder.join(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+
+    let result = (|| {
+        let mut file = File::create(&temporary)?;
+        file.write_all(contents)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
         }
-    } 
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+        //the rename is durable once the folder is synced too
+        if let Ok(folder) = File::open(folder) {
+            let _ = folder.sync_all();
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
 
+// Switches the line endings written on save between LF and CRLF
+pub fn handle_convert_line_ending(code_component: &mut CodeComponent, context: &mut AppContext) {
+    let line_ending = code_component.format.get_line_ending().toggle();
+    code_component.format.set_line_ending(line_ending);
+    context.set_message(Some(format!("Line endings: {}", line_ending.get_name())));
 }
 
 pub fn handle_undo (code_component: &mut CodeComponent) {
-    code_component.history.use_previous();
-    let code = code_component.history.get_current_code();
-    code_component.current = code.clone();
+    //the history restores only the main cursor
+    code_component.current.flush_cursors();
+    code_component.history.undo(&mut code_component.current);
 }
 
 pub fn handle_redo(code_component: &mut CodeComponent) {
-    code_component.history.use_next();
-    let code = code_component.history.get_current_code();
-    code_component.current = code.clone();
+    code_component.current.flush_cursors();
+    code_component.history.redo(&mut code_component.current);
+}
+
+// Keys of the undo tree popup, resolved in its keymap scope: moving previews a state,
+// confirming restores it or jumps back by the time typed
+pub fn handle_history_popup(code_component: &mut CodeComponent, context: &mut AppContext, key: KeyEvent) {
+    let Some(popup) = &mut code_component.history_popup else {
+        return;
+    };
+    let rows = code_component.history.get_tree_rows();
+    let row = rows.iter().position(|(id, _)| *id == popup.get_selected()).unwrap_or(0);
+    match context.resolve_key(KeyScope::HistoryPopup, key) {
+        KeyMatch::Action(Action::MoveUp) => {
+            popup.set_selected(rows[row.saturating_sub(1)].0);
+        },
+        KeyMatch::Action(Action::MoveDown) => {
+            popup.set_selected(rows[min(row + 1, rows.len() - 1)].0);
+        },
+        KeyMatch::Action(Action::ShowHistory | Action::Leave) => {
+            code_component.history_popup = None;
+        },
+        KeyMatch::Action(Action::DeleteBackward) => {
+            popup.get_mut_input().pop();
+        },
+        KeyMatch::Action(Action::Confirm) => {
+            if popup.get_input().is_empty() {
+                let selected = popup.get_selected();
+                code_component.history.jump_to(selected, &mut code_component.current);
+                code_component.history_popup = None;
+            } else if let Some(duration) = parse_duration(popup.get_input()) {
+                let time = SystemTime::now().checked_sub(duration).unwrap_or(SystemTime::UNIX_EPOCH);
+                code_component.history.jump_to_time(time, &mut code_component.current);
+                code_component.history_popup = None;
+            } else {
+                popup.get_mut_input().clear();
+            }
+        },
+        KeyMatch::Unbound => {
+            if let Some(char) = typed_char(&key).filter(char::is_ascii_alphanumeric) {
+                popup.get_mut_input().push(char);
+            }
+        },
+        _ => {}
+    }
 }
 
 pub fn handle_char(code_component: &mut CodeComponent, char: String) {
-    code_component.current.remove_cursor();
-    if let Some(current_line) = code_component.current.get_line(code_component.current.get_cursor().get_x()) {
-        code_component.current.change_line_at_cursor(current_line.get_string()[..code_component.current.get_cursor().get_y()].to_string() + &char.to_string() + &current_line.get_string()[code_component.current.get_cursor().get_y()..].to_string());    
+    //typing replaces the selected text
+    if let Some(range) = code_component.current.get_selection_range() {
+        code_component.current.delete_selection();
+        *code_component.current.get_mut_cursor() = code_component.current.char_to_point(range.start);
+        code_component.current.flush_selection();
+    }
+    if handle_pair_char(code_component, char.as_str()) {
+        return;
     }
+    let at = code_component.current.point_to_char(code_component.current.get_cursor());
+    code_component.current.insert(at, char.as_str());
     
-    let y = code_component.current.get_cursor().get_y();
-    code_component.current.get_mut_cursor().set_y(y+1);
+    //a combining char can join the grapheme before the cursor, so the column is computed again
+    let cursor = code_component.current.char_to_point(at + char.chars().count());
+    *code_component.current.get_mut_cursor() = cursor;
+    if char.chars().count() == 1 {
+        dedent_closer(code_component, at);
+    }
 }
 
 pub fn handle_delete(code_component: &mut CodeComponent) {
-    let readable_code = code_component.get_current().clone();
-    let readable_selection = readable_code.get_selection();
-    let readable_cursor = readable_code.get_cursor().clone();
-    let mut upper_size = 0;
+    if code_component.current.get_selection_range().is_none() && delete_empty_pair(code_component) {
+        return;
+    }
     let mutable_code = code_component.get_mut_current();    
 
-    if let Some(readable_selection) = readable_selection {
+    if let Some(range) = mutable_code.get_selection_range() {
         
+        let start = mutable_code.char_to_point(range.start);
         mutable_code.delete_selection();
-
-        if readable_selection.get_start().get_x() > readable_selection.get_end().get_x() {
-            mutable_code.get_mut_cursor().set_x(readable_selection.get_end().get_x());
-            mutable_code.get_mut_cursor().set_y(readable_selection.get_end().get_y()-1);    
-        } else if readable_selection.get_start().get_x() < readable_selection.get_end().get_x() {
-            mutable_code.get_mut_cursor().set_x(readable_selection.get_start().get_x());
-            mutable_code.get_mut_cursor().set_y(readable_selection.get_start().get_y());    
-        } else if readable_selection.get_start().get_x() == readable_selection.get_end().get_x() {
-            if readable_selection.get_start().get_y() < readable_selection.get_end().get_y() {
-                mutable_code.get_mut_cursor().set_x(readable_selection.get_start().get_x());
-                mutable_code.get_mut_cursor().set_y(readable_selection.get_start().get_y());
-            } else if readable_selection.get_start().get_y() > readable_selection.get_end().get_y() {
-                mutable_code.get_mut_cursor().set_x(readable_selection.get_end().get_x());
-                mutable_code.get_mut_cursor().set_y(readable_selection.get_end().get_y()-1);
-            }
-        }
-
+        *mutable_code.get_mut_cursor() = start;
         mutable_code.flush_selection();
 
-
-        let start = min(readable_selection.get_start(),readable_selection.get_end());
-        let end = max(readable_selection.get_start(),readable_selection.get_end());
-
-        for i in start.get_x()..mutable_code.get_content().len() {
-            if let Some(line) = mutable_code.get_mut_content().get_mut(i) {
-                line.set_number(line.get_number()+start.get_x()+1-end.get_x());
-            }
-        }
     } else {
 
-        if readable_cursor.get_x() > 0 {
-            if let Some(upper_line) = readable_code.get_line(readable_cursor.get_x() - 1) {
-                upper_size = upper_line.get_string().len();
-            }
+        let cursor = mutable_code.get_cursor().clone();
+        let at = mutable_code.point_to_char(&cursor);
+        if cursor.get_y() > 0 {
+            //remove the whole grapheme before the cursor
+            let previous = mutable_code.point_to_char(&Point::new(cursor.get_x(), cursor.get_y() - 1));
+            mutable_code.remove(previous..at);
+            mutable_code.get_mut_cursor().set_y(cursor.get_y() - 1);
+        } else if cursor.get_x() > 0 {
+            //join the line with the upper one
+            let upper_size = mutable_code.line_len(cursor.get_x() - 1);
+            mutable_code.remove(at - 1..at);
+            *mutable_code.get_mut_cursor() = Point::new(cursor.get_x() - 1, upper_size);
         }
-        if let Some(current_line) = readable_code.get_line(readable_cursor.get_x()).clone() {
-            let line = current_line.get_string().clone();
-            if readable_cursor.get_y() == 0 && readable_cursor.get_x() > 0 {
-                mutable_code.get_mut_cursor().move_left(true, upper_size);
-                mutable_code.change_line(readable_cursor.get_x()-1, line);
-                mutable_code.remove_line(readable_cursor.get_x());
-                for i in readable_cursor.get_x()..readable_code.get_content().len() {
-                    mutable_code.set_line_number(i,i-1);
-                }    
-            } else if readable_cursor.get_y() > 0 {
-                let mut new_string = line[..readable_cursor.get_y()-1].to_string();
-                new_string.push_str(&line[readable_cursor.get_y()..]);
-                mutable_code.replace_line(current_line.get_number(), line, new_string.clone());
-                mutable_code.get_mut_cursor().move_left(false, new_string.len());
-            }
-        }    
-    
 
     }
 }
 
 pub fn handle_enter(code_component: &mut CodeComponent) {
-    let code = code_component.get_current().clone();
+    let unit = code_component.indent.unit();
     let mut_code = code_component.get_mut_current();
 
-    if let Some(current_line) = code.get_content().get(code.get_cursor().get_x()) {
-        let new_current_string = current_line.get_string()[..code.get_cursor().get_y()].to_string().clone();
-        let new_generated_string = current_line.get_string()[code.get_cursor().get_y()..].to_string().clone();
-        
-        mut_code.replace_line(code.get_cursor().get_x(),current_line.get_string(), new_current_string.clone());
-        mut_code.get_mut_cursor().move_right(true, current_line.get_string().len() - new_current_string.len());
-        mut_code.get_mut_content().insert(code.get_cursor().get_x()+1, Line::new(code.get_cursor().get_x()+1, new_generated_string));
-        for number in current_line.get_number()+1.. code.get_content().len() {
-            if let Some(line) = mut_code.get_mut_content().get_mut(number) {
-                line.set_number(number + 1);
-            }
-        }
+    if mut_code.get_cursor().get_x() < mut_code.len_lines() {
+        let at = mut_code.point_to_char(mut_code.get_cursor());
+        let line = mut_code.get_line(mut_code.get_cursor().get_x()).unwrap_or_default();
+        let text = line.get_string();
+        let (before, after) = text.split_at(line.byte_index(mut_code.get_cursor().get_y()));
+        //the new line keeps the indentation of this one, one level deeper after an opener
+        let (text, cursor) = enter_text(before, after, &unit);
+        mut_code.insert(at, &text);
+        *mut_code.get_mut_cursor() = mut_code.char_to_point(at + cursor);
     }
 
-}
\ No newline at end of file
+}
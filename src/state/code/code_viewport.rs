@@ -0,0 +1,321 @@
+use std::{iter, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{code::Code, code_utils::Point};
+
+// Text drawn for every grapheme of a line, hard tabs become the spaces up to the next multiple of `tab_width` cells
+pub fn display_graphemes(line: &str, tab_width: usize) -> Vec<String> {
+    let tab_width = tab_width.max(1);
+    let mut cells = 0;
+    line
+    .graphemes(true)
+    .map(|grapheme| {
+        let text = if grapheme == "\t" { " ".repeat(tab_width - cells % tab_width) } else { grapheme.to_string() };
+        cells += text.width();
+        text
+    })
+    .collect()
+}
+
+// Columns of the graphemes starting every row of a line wrapped at `width` cells, the first row starts at 0
+pub fn wrap_columns(graphemes: &[String], width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut cells = 0;
+    for (column, grapheme) in graphemes.iter().enumerate() {
+        let grapheme_width = grapheme.width();
+        if cells > 0 && cells + grapheme_width > width {
+            starts.push(column);
+            cells = 0;
+        }
+        cells += grapheme_width;
+    }
+    starts
+}
+
+// Cells taken by the graphemes before `column`
+fn cells_before(graphemes: &[String], column: usize) -> usize {
+    graphemes.iter().take(column).map(|grapheme| grapheme.width()).sum()
+}
+
+// Part of the code shown by the code panel: the first line and row drawn, the first cell when lines are not wrapped,
+// and the size of the panel. The cursor is kept `scroll_off` rows (and cells) away from the borders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeViewport {
+    top: usize,
+    // row of the first line where the panel starts, only when wrapping
+    top_row: usize,
+    left: usize,
+    width: usize,
+    height: usize,
+    scroll_off: usize,
+    wrap: bool,
+}
+
+impl Default for CodeViewport {
+    fn default() -> CodeViewport {
+        CodeViewport {
+            top: 0,
+            top_row: 0,
+            left: 0,
+            width: 80,
+            height: 20,
+            scroll_off: 3,
+            wrap: false
+        }
+    }
+}
+
+impl CodeViewport {
+
+    pub fn get_top(&self) -> usize {
+        self.top
+    }
+
+    pub fn get_top_row(&self) -> usize {
+        self.top_row
+    }
+
+    pub fn get_left(&self) -> usize {
+        self.left
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+    }
+
+    pub fn get_scroll_off(&self) -> usize {
+        self.scroll_off
+    }
+
+    pub fn set_scroll_off(&mut self, scroll_off: usize) {
+        self.scroll_off = scroll_off;
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.left = 0;
+        self.top_row = 0;
+    }
+
+    // Goes back to the start of the code
+    pub fn reset(&mut self) {
+        self.top = 0;
+        self.top_row = 0;
+        self.left = 0;
+    }
+
+    // Columns of the graphemes drawn on every row of a line: the rows of the wrapped line,
+    // or the columns inside the panel when lines are scrolled horizontally
+    pub fn rows(&self, graphemes: &[String]) -> Vec<Range<usize>> {
+        if self.wrap {
+            let starts = wrap_columns(graphemes, self.width);
+            let ends = starts.iter().skip(1).copied().chain([graphemes.len()]);
+            return starts.iter().copied().zip(ends).map(|(start, end)| start..end).collect();
+        }
+        let mut cells = 0;
+        let mut first = graphemes.len();
+        let mut last = graphemes.len();
+        for (column, grapheme) in graphemes.iter().enumerate() {
+            if cells >= self.left && first == graphemes.len() {
+                first = column;
+            }
+            cells += grapheme.width();
+            if cells > self.left + self.width {
+                last = column;
+                break;
+            }
+        }
+        iter::once(first.min(last)..last).collect()
+    }
+
+    // Row of a line where a column is drawn, a column starting a row belongs to it and the end of the line to the last row
+    pub fn row_of(&self, graphemes: &[String], column: usize) -> usize {
+        if !self.wrap {
+            return 0;
+        }
+        wrap_columns(graphemes, self.width).iter().rposition(|start| *start <= column).unwrap_or(0)
+    }
+
+    fn row_count(&self, code: &Code, line: usize, tab_width: usize) -> usize {
+        if !self.wrap {
+            return 1;
+        }
+        let text = code.get_line(line).map(|line| line.get_string()).unwrap_or_default();
+        wrap_columns(&display_graphemes(&text, tab_width), self.width).len()
+    }
+
+    // Line and row `rows` rows before the given one, stopping at the start of the code
+    fn back(&self, code: &Code, tab_width: usize, (mut line, mut row): (usize, usize), rows: usize) -> (usize, usize) {
+        for _ in 0..rows {
+            if row > 0 {
+                row -= 1;
+            } else if line > 0 {
+                line -= 1;
+                row = self.row_count(code, line, tab_width) - 1;
+            } else {
+                break;
+            }
+        }
+        (line, row)
+    }
+
+    // Rows drawn after the given one, counting up to `limit`
+    fn rows_after(&self, code: &Code, tab_width: usize, (line, row): (usize, usize), limit: usize) -> usize {
+        let mut rows = self.row_count(code, line, tab_width) - 1 - row;
+        let mut next = line + 1;
+        while rows < limit && next < code.len_lines() {
+            rows += self.row_count(code, next, tab_width);
+            next += 1;
+        }
+        rows.min(limit)
+    }
+
+    // Scrolls `rows` rows down, or up with a negative value, leaving the cursor where it is.
+    // The last line can go up to the top of the panel
+    pub fn scroll(&mut self, code: &Code, tab_width: usize, rows: isize) {
+        let position = (self.top, self.top_row);
+        (self.top, self.top_row) = if rows < 0 {
+            self.back(code, tab_width, position, rows.unsigned_abs())
+        } else {
+            self.forward(code, tab_width, position, rows as usize)
+        };
+    }
+
+    // Line and row `rows` rows after the given one, stopping at the last row of the code
+    fn forward(&self, code: &Code, tab_width: usize, (mut line, mut row): (usize, usize), rows: usize) -> (usize, usize) {
+        for _ in 0..rows {
+            if row + 1 < self.row_count(code, line, tab_width) {
+                row += 1;
+            } else if line + 1 < code.len_lines() {
+                line += 1;
+                row = 0;
+            } else {
+                break;
+            }
+        }
+        (line, row)
+    }
+
+    // Point drawn at a row and a cell of the panel, past the last row it is the end of the code
+    pub fn point_at(&self, code: &Code, tab_width: usize, row: usize, cell: usize) -> Point {
+        if self.rows_after(code, tab_width, (self.top, self.top_row), row) < row {
+            return code.char_to_point(code.len_chars());
+        }
+        let (line, row) = self.forward(code, tab_width, (self.top, self.top_row), row);
+        let graphemes = display_graphemes(&code.get_line(line).map(|line| line.get_string()).unwrap_or_default(), tab_width);
+        let rows = self.rows(&graphemes);
+        let range = rows[row.min(rows.len() - 1)].clone();
+        //the column starting the next row is drawn there, so rows but the last one end before it
+        let end = if row + 1 < rows.len() { range.end.saturating_sub(1).max(range.start) } else { range.end };
+        let mut column = range.start;
+        let mut cells = 0;
+        while column < end && cells + graphemes[column].width() <= cell {
+            cells += graphemes[column].width();
+            column += 1;
+        }
+        Point::new(line, column)
+    }
+
+    // Scrolls the least needed to show the cursor with `scroll_off` rows and cells around it
+    pub fn follow(&mut self, code: &Code, tab_width: usize) {
+        let cursor = code.get_cursor();
+        let text = code.get_line(cursor.get_x()).map(|line| line.get_string()).unwrap_or_default();
+        let graphemes = display_graphemes(&text, tab_width);
+        let position = (cursor.get_x(), self.row_of(&graphemes, cursor.get_y()));
+        let scroll_off = self.scroll_off.min(self.height.saturating_sub(1) / 2);
+        //past the end of the code there is nothing to keep visible below the cursor
+        let bottom_off = self.rows_after(code, tab_width, position, scroll_off);
+
+        //lines removed since the last time can leave the top past the end
+        let last = code.len_lines().saturating_sub(1);
+        if self.top > last {
+            self.top = last;
+        }
+        self.top_row = self.top_row.min(self.row_count(code, self.top, tab_width) - 1);
+
+        if position < (self.top, self.top_row) {
+            (self.top, self.top_row) = self.back(code, tab_width, position, scroll_off);
+        } else if position.0 - self.top > self.height {
+            (self.top, self.top_row) = self.back(code, tab_width, position, self.height - 1 - bottom_off);
+        } else {
+            let mut distance = 0;
+            let mut current = (self.top, self.top_row);
+            while current < position {
+                let rows = self.row_count(code, current.0, tab_width);
+                if current.0 == position.0 {
+                    distance += position.1 - current.1;
+                    break;
+                }
+                distance += rows - current.1;
+                current = (current.0 + 1, 0);
+            }
+            if distance < scroll_off {
+                (self.top, self.top_row) = self.back(code, tab_width, position, scroll_off);
+            } else if distance + bottom_off >= self.height {
+                (self.top, self.top_row) = self.back(code, tab_width, position, self.height - 1 - bottom_off);
+            }
+        }
+
+        if self.wrap {
+            self.left = 0;
+            return;
+        }
+        let cell = cells_before(&graphemes, cursor.get_y());
+        let side_off = scroll_off.min(self.width.saturating_sub(1) / 2);
+        if cell < self.left + side_off {
+            self.left = cell.saturating_sub(side_off);
+        } else if cell + side_off >= self.left + self.width {
+            self.left = cell + side_off + 1 - self.width;
+        }
+    }
+
+    // Point drawn one row above (or below) the cursor at the same on screen column, None past the start or the end of the code
+    pub fn visual_vertical(&self, code: &Code, cursor: &Point, tab_width: usize, down: bool) -> Option<Point> {
+        let line_graphemes = |line: usize| display_graphemes(&code.get_line(line).map(|line| line.get_string()).unwrap_or_default(), tab_width);
+        let graphemes = line_graphemes(cursor.get_x());
+        let rows = self.rows(&graphemes);
+        let row = self.row_of(&graphemes, cursor.get_y());
+        let cell = cells_before(&graphemes, cursor.get_y()) - cells_before(&graphemes, rows[row].start);
+
+        let (line, target_graphemes, target_row) = if down && row + 1 < rows.len() {
+            (cursor.get_x(), graphemes, row + 1)
+        } else if !down && row > 0 {
+            (cursor.get_x(), graphemes, row - 1)
+        } else if down && cursor.get_x() + 1 < code.len_lines() {
+            (cursor.get_x() + 1, line_graphemes(cursor.get_x() + 1), 0)
+        } else if !down && cursor.get_x() > 0 {
+            let graphemes = line_graphemes(cursor.get_x() - 1);
+            let last = self.rows(&graphemes).len() - 1;
+            (cursor.get_x() - 1, graphemes, last)
+        } else {
+            return None;
+        };
+
+        let target_rows = self.rows(&target_graphemes);
+        let range = target_rows[target_row].clone();
+        //the column starting the next row is drawn there, so rows but the last one end before it
+        let end = if target_row + 1 < target_rows.len() { range.end.saturating_sub(1).max(range.start) } else { range.end };
+        let mut column = range.start;
+        let mut cells = 0;
+        while column < end && cells + target_graphemes[column].width() <= cell {
+            cells += target_graphemes[column].width();
+            column += 1;
+        }
+        Some(Point::new(line, column))
+    }
+}
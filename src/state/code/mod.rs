@@ -1,18 +1,57 @@
 pub mod code_history;
+pub mod code_brackets;
+pub mod code_buffers;
+pub mod code_cursor;
+pub mod code_format;
+pub mod code_highlight;
+pub mod code_indent;
+pub mod code_lines;
+pub mod code_mouse;
+pub mod code_navigation;
+pub mod code_panes;
+pub mod code_grammars;
+pub mod code_search;
 pub mod code_selection;
 pub mod code;
 pub mod code_utils;
+pub mod code_viewport;
 
-use std::{fs::File, io::Read, path::PathBuf};
-use self::{code::{Code, Line}, code_history::CodeHistory, code_utils::{handle_up, handle_down, handle_left, handle_right, handle_cut, handle_copy, handle_paste, handle_save, handle_undo, handle_redo, handle_char, handle_delete, handle_enter}};
-use crossterm::event::{KeyEventKind, Event, KeyCode, KeyModifiers};
+use std::{fs, io, ops::Range, path::{PathBuf, Path}, sync::Arc};
+use self::{code::Code, code_brackets::handle_jump_to_match, code_buffers::{BufferPicker, CodeBuffer, buffer_name, switch_buffer, open_empty_buffer, close_buffer, move_buffer, cycle_buffer, handle_buffer_picker}, code_format::CodeFormat, code_highlight::{CodeHighlighter, Grammar, GrammarRegistry}, code_indent::{IndentStyle, handle_tab, handle_back_tab}, code_navigation::{handle_visual_vertical, handle_word_left, handle_word_right, handle_home, handle_end, handle_code_start, handle_code_end, handle_page, handle_forward_delete}, code_viewport::CodeViewport, code_mouse::{MouseClicks, handle_mouse}, code_panes::{CodePanes, SplitDirection, PaneDirection, handle_split, handle_close_pane, handle_focus_pane, handle_resize_pane}, code_lines::{handle_duplicate, handle_move_lines, handle_delete_lines, handle_join_lines, handle_insert_line, handle_sort_lines, handle_reverse_lines}, code_search::{CodeSearch, handle_open_search, handle_open_replace, handle_search}, code_history::{CodeHistory, Change, HistoryPopup, cache_dir, history_cache_path}, code_selection::CodeSelection, code_cursor::{for_each_cursor, handle_add_cursor_vertical, handle_add_next_occurrence, handle_add_all_occurrences}, code_utils::{Point, handle_up, handle_down, handle_left, handle_right, handle_cut, handle_copy, handle_paste, handle_save, handle_undo, handle_redo, handle_char, handle_delete, handle_enter, handle_history_popup, handle_convert_line_ending}};
+use crossterm::event::{KeyEventKind, Event, MouseEventKind};
 
-use super::{Component, ComponentType, AppContext};
+use super::{Component, ComponentType, AppContext, ScreenArea, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CodeComponent {
     current: Code,
     history: CodeHistory,
+    history_popup: Option<HistoryPopup>,
+    // file shown in the component
+    path: Option<PathBuf>,
+    // line ending, BOM and final newline used to write the file
+    format: CodeFormat,
+    saved_format: CodeFormat,
+    // search prompt, keys go to it while it is open
+    search: Option<CodeSearch>,
+    // syntax tokens of the code, for the grammar of the file
+    highlighter: CodeHighlighter,
+    // indentation of the file, and the one used by files without indented lines
+    indent: IndentStyle,
+    default_indent: IndentStyle,
+    // cells a hard tab takes on screen
+    tab_width: usize,
+    // part of the code shown by the panel
+    viewport: CodeViewport,
+    // files open in the tab bar, the one at `active` is the one in the fields above
+    buffers: Vec<CodeBuffer>,
+    active: usize,
+    buffer_picker: Option<BufferPicker>,
+    // closing the buffer was asked, the app closes it once it knows what to do with its changes
+    close_requested: bool,
+    // panes the panel is split in, the focused one shows the buffer in the fields above
+    panes: CodePanes,
+    clicks: MouseClicks,
 }
 
 impl Component for CodeComponent {
@@ -24,82 +63,46 @@ impl Component for CodeComponent {
     fn handle_event(&mut self, context: &mut AppContext, event: Event) {
 
         if let Event::Key(key) = event {
-            self.get_mut_current().remove_cursor();
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char(char) => {
-                        let mut char_normalized = char.clone().to_string();
-                        char_normalized = char_normalized.to_lowercase().to_string();
-                        if char_normalized == "x" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_cut(self);
-                        } else if char_normalized == "c" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_copy(self);                            
-                        } else if char_normalized == "v" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_paste(self);
-                        } else if char_normalized == "s" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_save(self, context);
-                        } else if char_normalized == "z" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_undo(self);                            
-                        } else if char_normalized == "y" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            handle_redo(self);
-                        } else {
-                            handle_char(self, char.to_string());
-                        }
-                    },
-                    KeyCode::Backspace => {
-                        handle_delete(self);
-                    },
-                    KeyCode::Enter => {
-                        handle_enter(self);
-                    },
-                    KeyCode::Up => {
-                        handle_up(self, event.clone());
-                    },
-                    KeyCode::Down => {
-                        handle_down(self, event.clone());
-                    },
-                    KeyCode::Left => {
-                        handle_left(self, event.clone());
-                    },
-                    KeyCode::Right => {
-                        handle_right(self, event.clone());
-                    },
-                    KeyCode::Esc => {
-                        context.set_focus(None);
-                        context.set_hover(self.get_type());             
-                    },
-                    _ => {}
+            let cursor = self.current.get_cursor().clone();
+            let selection = self.current.get_selection().clone();
+            if self.history_popup.is_some() {
+                if key.kind != KeyEventKind::Release {
+                    handle_history_popup(self, context, key);
                 }
-            } if key.kind == KeyEventKind::Repeat {
-                match key.code {
-                    KeyCode::Char(char) => {
-                        handle_char(self, char.to_string())
-                    },
-                    KeyCode::Backspace => {
-                        handle_delete(self);
-                    },
-                    KeyCode::Enter => {
-                        handle_enter(self);
-                    },
-                    KeyCode::Up => {
-                        handle_up(self, event.clone());
-
-                    },
-                    KeyCode::Down => {
-                        handle_down(self, event.clone());
-
-                    },
-                    KeyCode::Left => {
-                        handle_left(self, event.clone());
-
+            } else if self.buffer_picker.is_some() {
+                if key.kind != KeyEventKind::Release {
+                    handle_buffer_picker(self, context, key);
+                    context.set_active_file(self.path.clone());
+                    context.set_active_file_changed(false);
+                }
+            } else if self.search.is_some() && key.kind != KeyEventKind::Release {
+                handle_search(self, context, key);
+            } else if key.kind != KeyEventKind::Release {
+                match context.resolve_key(KeyScope::Code, key) {
+                    KeyMatch::Action(action) => {
+                        self.run_action(context, action, event.clone(), &cursor, &selection);
                     },
-                    KeyCode::Right => {
-                        handle_right(self, event.clone());
+                    KeyMatch::Pending => {},
+                    KeyMatch::Unbound => {
+                        //keys without an action type their character
+                        if let Some(char) = typed_char(&key) {
+                            for_each_cursor(self, |code_component| handle_char(code_component, char.to_string()));
+                        }
                     },
-                    _ => {}
                 }
             }
-            self.get_mut_current().set_cursor();
+            self.record_operations(cursor, selection);
+            self.update_highlight();
+            self.scroll_to_cursor();
+        } else if let Event::Mouse(mouse) = event {
+            let cursor = self.current.get_cursor().clone();
+            let selection = self.current.get_selection().clone();
+            handle_mouse(self, context, mouse);
+            self.record_operations(cursor, selection);
+            //the wheel scrolls away from the cursor
+            if matches!(mouse.kind, MouseEventKind::Down(_) | MouseEventKind::Drag(_)) {
+                self.scroll_to_cursor();
+            }
         }
 
     }
@@ -107,29 +110,297 @@ impl Component for CodeComponent {
 
 impl CodeComponent {
 
+    // Runs what a key of the keymap asked, `event` is the key for the moves that extend the selection with Shift
+    fn run_action(&mut self, context: &mut AppContext, action: Action, event: Event, cursor: &Point, selection: &Option<CodeSelection>) {
+        match action {
+            Action::Cut => handle_cut(self),
+            Action::Copy => handle_copy(self),
+            Action::Paste => handle_paste(self),
+            Action::Save => handle_save(self, context),
+            Action::Undo => handle_undo(self),
+            Action::Redo => handle_redo(self),
+            Action::ToggleWrap => self.set_wrap(!self.viewport.is_wrapping()),
+            Action::ShowHistory => {
+                self.history_popup = Some(HistoryPopup::new(self.history.get_current()));
+            },
+            Action::PreviousHistoryBranch => {
+                self.history.previous_branch();
+            },
+            Action::NextHistoryBranch => {
+                self.history.next_branch();
+            },
+            Action::CloseBuffer => {
+                self.close_requested = true;
+            },
+            Action::PickBuffer => {
+                self.buffer_picker = Some(BufferPicker::new());
+            },
+            Action::PreviousBuffer | Action::NextBuffer => {
+                cycle_buffer(self, action == Action::NextBuffer);
+                context.set_active_file(self.path.clone());
+                context.set_active_file_changed(false);
+            },
+            Action::MoveBufferLeft | Action::MoveBufferRight => move_buffer(self, action == Action::MoveBufferRight),
+            Action::Search => handle_open_search(self),
+            Action::Replace => handle_open_replace(self),
+            Action::ConvertLineEnding => handle_convert_line_ending(self, context),
+            Action::JumpToBracket => handle_jump_to_match(self),
+            Action::DuplicateLines => self.line_command(handle_duplicate, cursor, selection),
+            Action::DeleteLines => self.line_command(handle_delete_lines, cursor, selection),
+            Action::JoinLines => self.line_command(handle_join_lines, cursor, selection),
+            Action::SortLines => self.line_command(handle_sort_lines, cursor, selection),
+            Action::ReverseLines => self.line_command(handle_reverse_lines, cursor, selection),
+            Action::MoveLinesUp | Action::MoveLinesDown => {
+                let down = action == Action::MoveLinesDown;
+                self.line_command(|code_component| handle_move_lines(code_component, down), cursor, selection);
+            },
+            Action::InsertLineAbove | Action::InsertLineBelow => {
+                let above = action == Action::InsertLineAbove;
+                self.line_command(|code_component| handle_insert_line(code_component, above), cursor, selection);
+            },
+            Action::AddCursorAbove | Action::AddCursorBelow => handle_add_cursor_vertical(self, action == Action::AddCursorBelow),
+            Action::AddNextOccurrence => handle_add_next_occurrence(self),
+            Action::AddAllOccurrences => handle_add_all_occurrences(self),
+            Action::SplitSideBySide => handle_split(self, SplitDirection::Horizontal),
+            Action::SplitStacked => handle_split(self, SplitDirection::Vertical),
+            Action::ClosePane | Action::FocusPaneLeft | Action::FocusPaneRight | Action::FocusPaneUp | Action::FocusPaneDown => {
+                match action {
+                    Action::FocusPaneLeft => handle_focus_pane(self, PaneDirection::Left),
+                    Action::FocusPaneRight => handle_focus_pane(self, PaneDirection::Right),
+                    Action::FocusPaneUp => handle_focus_pane(self, PaneDirection::Up),
+                    Action::FocusPaneDown => handle_focus_pane(self, PaneDirection::Down),
+                    _ => handle_close_pane(self),
+                }
+                context.set_active_file(self.path.clone());
+                context.set_active_file_changed(false);
+            },
+            Action::ResizePaneLeft => handle_resize_pane(self, PaneDirection::Left),
+            Action::ResizePaneRight => handle_resize_pane(self, PaneDirection::Right),
+            Action::ResizePaneUp => handle_resize_pane(self, PaneDirection::Up),
+            Action::ResizePaneDown => handle_resize_pane(self, PaneDirection::Down),
+            Action::DeleteBackward => for_each_cursor(self, handle_delete),
+            Action::DeleteForward => for_each_cursor(self, handle_forward_delete),
+            Action::NewLine => for_each_cursor(self, handle_enter),
+            Action::Indent => for_each_cursor(self, handle_tab),
+            Action::Outdent => for_each_cursor(self, handle_back_tab),
+            Action::MoveUp | Action::MoveDown if self.viewport.is_wrapping() => {
+                let down = action == Action::MoveDown;
+                for_each_cursor(self, |code_component| handle_visual_vertical(code_component, event.clone(), down));
+            },
+            Action::MoveUp => for_each_cursor(self, |code_component| handle_up(code_component, event.clone())),
+            Action::MoveDown => for_each_cursor(self, |code_component| handle_down(code_component, event.clone())),
+            Action::MoveLeft => for_each_cursor(self, |code_component| handle_left(code_component, event.clone())),
+            Action::MoveRight => for_each_cursor(self, |code_component| handle_right(code_component, event.clone())),
+            Action::WordLeft => for_each_cursor(self, |code_component| handle_word_left(code_component, event.clone())),
+            Action::WordRight => for_each_cursor(self, |code_component| handle_word_right(code_component, event.clone())),
+            Action::LineStart => for_each_cursor(self, |code_component| handle_home(code_component, event.clone())),
+            Action::LineEnd => for_each_cursor(self, |code_component| handle_end(code_component, event.clone())),
+            Action::CodeStart => handle_code_start(self, event),
+            Action::CodeEnd => handle_code_end(self, event),
+            Action::PageUp | Action::PageDown => handle_page(self, event, action == Action::PageDown),
+            Action::Leave => {
+                if !self.current.get_cursors().is_empty() {
+                    //the first Esc leaves only the main cursor
+                    self.current.flush_cursors();
+                } else {
+                    context.set_focus(None);
+                    context.set_hover(self.get_type());
+                }
+            },
+            _ => {}
+        }
+    }
+
     pub fn new() -> Self {
         let code = Code::new();
         CodeComponent {
             current: code.clone(),
             history: CodeHistory::new(code.clone()),
+            history_popup: None,
+            path: None,
+            format: CodeFormat::default(),
+            saved_format: CodeFormat::default(),
+            search: None,
+            highlighter: CodeHighlighter::default(),
+            indent: IndentStyle::default(),
+            default_indent: IndentStyle::default(),
+            tab_width: 4,
+            viewport: CodeViewport::default(),
+            buffers: vec![CodeBuffer::new()],
+            active: 0,
+            buffer_picker: None,
+            close_requested: false,
+            panes: CodePanes::default(),
+            clicks: MouseClicks::default(),
         }
     }
 
-    pub fn set_current(&mut self, active_file: Option<PathBuf>) {
+    // Shows the buffer of a file, opening it in a new buffer if it is not open yet.
+    // An empty buffer without changes is replaced by the file.
+    // A file that can not be read as text is not opened and the buffers are left as they are
+    pub fn set_current(&mut self, active_file: Option<PathBuf>, project: &Path) -> io::Result<()> {
         if let Some(path) = active_file {
-            let file = File::open(path);
-            if let Ok(mut file) = file {
-                let mut contents = String::new();
-                let _ = file.read_to_string(&mut contents);
-                contents
-                .split("\n")
-                .enumerate()
-                .for_each(|tuple| {
-                    let line = Line::new(tuple.0+1, tuple.1.to_string());
-                    self.current.add_line(line);
-                })
+            if let Some(index) = self.find_buffer(&path) {
+                switch_buffer(self, index);
+                return Ok(());
             }
-            self.current.set_cursor();
+            let contents = fs::read_to_string(&path)?;
+            if self.path.is_some() || self.is_modified() {
+                open_empty_buffer(self);
+            }
+            let (format, text) = CodeFormat::detect(contents.as_str());
+            self.current.set_text(text.as_str());
+            self.indent = IndentStyle::detect(text.as_str()).unwrap_or(self.default_indent);
+            //a file mixing line endings differs from what the buffer writes, so it shows as modified
+            self.format = format.unified();
+            self.saved_format = format;
+            *self.current.get_mut_cursor() = Point::default();
+            self.current.flush_selection();
+            self.current.flush_cursors();
+            self.history = cache_dir()
+            .and_then(|cache_dir| CodeHistory::restore(&history_cache_path(&cache_dir, project, &path), &self.current))
+            .unwrap_or(CodeHistory::new(self.current.clone()));
+            self.history_popup = None;
+            self.search = None;
+            self.highlighter = CodeHighlighter::new(GrammarRegistry::default().for_path(&path));
+            self.update_highlight();
+            self.viewport.reset();
+            self.path = Some(path);
+        }
+        Ok(())
+    }
+
+    // Replaces char ranges of the code as a single undo step, for edits not made by typing in it
+    pub fn replace(&mut self, mut replacements: Vec<(Range<usize>, String)>) {
+        if replacements.is_empty() {
+            return;
+        }
+        let cursor = self.current.get_cursor().clone();
+        let selection = self.current.get_selection().clone();
+        replacements.sort_by_key(|(range, _)| range.start);
+        for (range, text) in replacements.iter().rev() {
+            self.current.remove(range.clone());
+            self.current.insert(range.start, text);
+        }
+        let at = self.current.char_to_point(self.current.point_to_char(&cursor));
+        *self.current.get_mut_cursor() = at;
+        self.current.flush_selection();
+        self.current.flush_cursors();
+        if let Some(search) = &mut self.search {
+            search.update(&self.current);
+        }
+        self.history.close_group();
+        self.record_operations(cursor, selection);
+        self.history.close_group();
+        self.update_highlight();
+    }
+
+    // Names of the open buffers in the order of the tab bar
+    pub fn get_buffer_names(&self) -> Vec<String> {
+        (0..self.buffers.len()).map(|index| buffer_name(self.get_buffer_path(index))).collect()
+    }
+
+    fn get_buffer_path(&self, index: usize) -> &Option<PathBuf> {
+        if index == self.active { &self.path } else { self.buffers[index].get_path() }
+    }
+
+    pub fn is_buffer_modified(&self, index: usize) -> bool {
+        if index == self.active { self.is_modified() } else { self.buffers[index].is_modified() }
+    }
+
+    pub fn has_modified_buffers(&self) -> bool {
+        (0..self.buffers.len()).any(|index| self.is_buffer_modified(index))
+    }
+
+    pub fn get_active_buffer(&self) -> usize {
+        self.active
+    }
+
+    // Index of the buffer showing a file
+    pub fn find_buffer(&self, path: &Path) -> Option<usize> {
+        (0..self.buffers.len()).find(|index| self.get_buffer_path(*index).as_deref() == Some(path))
+    }
+
+    pub fn get_buffer_picker(&self) -> &Option<BufferPicker> {
+        &self.buffer_picker
+    }
+
+    // Runs `action` with the buffer at `index` shown, then shows again the buffer shown before
+    pub fn with_buffer<R, F: FnOnce(&mut CodeComponent) -> R>(&mut self, index: usize, action: F) -> R {
+        let active = self.active;
+        switch_buffer(self, index);
+        let result = action(self);
+        switch_buffer(self, active);
+        result
+    }
+
+    // Saves every buffer with changes
+    pub fn save_all(&mut self, context: &mut AppContext) {
+        for index in 0..self.buffers.len() {
+            if self.is_buffer_modified(index) {
+                self.with_buffer(index, |code_component| code_component.save(context));
+            }
+        }
+    }
+
+    // Returns whether closing the buffer shown was asked, forgetting the request
+    pub fn take_close_request(&mut self) -> bool {
+        std::mem::take(&mut self.close_requested)
+    }
+
+    // Closes the buffer shown dropping its changes, the next one is shown
+    pub fn close_buffer(&mut self) {
+        close_buffer(self);
+        self.scroll_to_cursor();
+    }
+
+    // Runs a command editing whole lines for every cursor, as an undo step of its own
+    fn line_command<F: FnMut(&mut CodeComponent)>(&mut self, handler: F, cursor: &Point, selection: &Option<CodeSelection>) {
+        self.history.close_group();
+        for_each_cursor(self, handler);
+        self.record_operations(cursor.clone(), selection.clone());
+        self.history.close_group();
+    }
+
+    // Moves the cursor to a point of the code, clamped inside it, leaving a single cursor
+    pub fn go_to(&mut self, point: Point) {
+        let point = self.current.char_to_point(self.current.point_to_char(&point));
+        *self.current.get_mut_cursor() = point;
+        self.current.flush_selection();
+        self.current.flush_cursors();
+        self.history.close_group();
+        self.scroll_to_cursor();
+    }
+
+    // Keeps the history of the file in the cache of the project, to undo its changes in the next sessions
+    pub fn store_history(&self, project: &Path) {
+        if let (Some(path), Some(cache_dir)) = (&self.path, cache_dir()) {
+            let _ = self.history.store(&history_cache_path(&cache_dir, project, path));
+        }
+    }
+
+    // Keeps the history of every open buffer
+    pub fn store_histories(&self, project: &Path) {
+        self.store_history(project);
+        let Some(cache_dir) = cache_dir() else {
+            return;
+        };
+        for (_, buffer) in self.buffers.iter().enumerate().filter(|(index, _)| *index != self.active) {
+            if let Some(path) = buffer.get_path() {
+                let _ = buffer.get_history().store(&history_cache_path(&cache_dir, project, path));
+            }
+        }
+    }
+
+    // Moves the edits applied by the last event into the history as a single change
+    fn record_operations(&mut self, cursor: Point, selection: Option<CodeSelection>) {
+        let operations = self.current.take_operations();
+        if !operations.is_empty() {
+            let change = Change::new(operations, cursor, selection, self.current.get_cursor().clone(), self.current.get_selection().clone());
+            self.history.add_change(change, &self.current);
+        } else if &cursor != self.current.get_cursor() {
+            //typing somewhere else starts a new change
+            self.history.close_group();
         }
     }
 
@@ -141,9 +412,163 @@ impl CodeComponent {
         &mut self.current
     }
 
+    // The code differs from the text last saved or loaded
+    pub fn is_modified(&self) -> bool {
+        !self.history.is_saved() || self.format != self.saved_format
+    }
+
+    // The file on disk mixes line endings, saving writes the ones of the format everywhere
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.saved_format.has_mixed_line_endings()
+    }
+
+    pub fn get_format(&self) -> &CodeFormat {
+        &self.format
+    }
+
+    pub fn get_mut_format(&mut self) -> &mut CodeFormat {
+        &mut self.format
+    }
+
+    // Remembers the current text and format as the ones written on disk,
+    // the next keystrokes start a new change instead of joining the saved one
+    fn mark_saved(&mut self) {
+        self.history.close_group();
+        self.history.mark_saved();
+        self.saved_format = self.format.clone();
+    }
+
+    pub fn get_indent(&self) -> IndentStyle {
+        self.indent
+    }
+
+    pub fn set_indent(&mut self, indent: IndentStyle) {
+        self.indent = indent;
+    }
+
+    // Indentation used by the files opened next when they have no indented line to detect it from
+    pub fn set_default_indent(&mut self, indent: IndentStyle) {
+        self.default_indent = indent;
+    }
+
+    pub fn get_tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
+    pub fn get_viewport(&self) -> &CodeViewport {
+        &self.viewport
+    }
+
+    // Lines PageUp and PageDown move by
+    pub fn get_page_height(&self) -> usize {
+        self.viewport.get_height()
+    }
+
+    pub fn get_panes(&self) -> &CodePanes {
+        &self.panes
+    }
+
+    // Code, highlighting and viewport drawn by a pane, the code has the cursor and the selection of the pane
+    pub fn get_pane_view(&self, pane: usize) -> (Code, &CodeHighlighter, &CodeViewport) {
+        if pane == self.panes.get_focused() {
+            return (self.current.clone(), &self.highlighter, &self.viewport);
+        }
+        let buffer = self.panes.get_buffer(pane);
+        let view = self.panes.get_view(pane);
+        let (mut code, highlighter) = if buffer == self.active {
+            (self.current.clone(), &self.highlighter)
+        } else {
+            (self.buffers[buffer].get_current().clone(), self.buffers[buffer].get_highlighter())
+        };
+        view.apply(&mut code);
+        (code, highlighter, view.get_viewport())
+    }
+
+    // Name of the buffer shown by a pane
+    pub fn get_pane_name(&self, pane: usize) -> String {
+        let buffer = if pane == self.panes.get_focused() { self.active } else { self.panes.get_buffer(pane) };
+        buffer_name(self.get_buffer_path(buffer))
+    }
+
+    // Keeps where a pane and its lines are drawn, its viewport takes the size of the lines
+    pub fn set_pane_area(&mut self, pane: usize, frame: ScreenArea, area: ScreenArea) {
+        if pane >= self.panes.len() {
+            return;
+        }
+        self.panes.set_area(pane, frame, area);
+        if pane == self.panes.get_focused() {
+            self.set_viewport_size(area.get_width() as usize, area.get_height() as usize);
+        }
+    }
+
+    // Resizes the viewport to the panel, scrolling to keep the cursor inside it
+    pub fn set_viewport_size(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.viewport.get_width(), self.viewport.get_height()) {
+            self.viewport.set_size(width, height);
+            self.scroll_to_cursor();
+        }
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.viewport.set_wrap(wrap);
+        self.scroll_to_cursor();
+    }
+
+    pub fn set_scroll_off(&mut self, scroll_off: usize) {
+        self.viewport.set_scroll_off(scroll_off);
+        self.scroll_to_cursor();
+    }
+
+    // Scrolls the viewport the least needed to show the cursor
+    pub fn scroll_to_cursor(&mut self) {
+        self.viewport.follow(&self.current, self.tab_width);
+    }
+
+    pub fn save(&mut self, context: &mut AppContext) {
+        handle_save(self, context);
+    }
+
+    // Highlights again the lines edited since the last update
+    pub fn update_highlight(&mut self) {
+        let edits = self.current.take_line_edits();
+        self.highlighter.apply_edits(&edits);
+        self.highlighter.refresh(&self.current);
+    }
+
+    pub fn get_highlighter(&self) -> &CodeHighlighter {
+        &self.highlighter
+    }
+
+    // Highlights the code with another grammar, or with none
+    pub fn set_grammar(&mut self, grammar: Option<Arc<dyn Grammar>>) {
+        self.highlighter = CodeHighlighter::new(grammar);
+        self.current.take_line_edits();
+        self.highlighter.refresh(&self.current);
+    }
+
+    pub fn get_search(&self) -> &Option<CodeSearch> {
+        &self.search
+    }
+
+    pub fn get_path(&self) -> &Option<PathBuf> {
+        &self.path
+    }
+
     pub fn get_history(&self) -> &CodeHistory {
         &self.history
     }
 
+    pub fn get_history_popup(&self) -> &Option<HistoryPopup> {
+        &self.history_popup
+    }
+
+    pub fn set_history_popup(&mut self, history_popup: Option<HistoryPopup>) {
+        self.history_popup = history_popup;
+    }
+
 
 }
\ No newline at end of file
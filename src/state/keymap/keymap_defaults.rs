@@ -0,0 +1,363 @@
+use super::{KeyBinding, KeyScope, parse_sequence};
+
+// Something the user can ask with the keys, named in the keymap file
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Action {
+    // panels
+    NextPanel,
+    FocusPanel,
+    Quit,
+    Leave,
+    // editing
+    Cut,
+    Copy,
+    Paste,
+    Save,
+    Undo,
+    Redo,
+    DeleteBackward,
+    DeleteForward,
+    NewLine,
+    Indent,
+    Outdent,
+    // moving
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    CodeStart,
+    CodeEnd,
+    PageUp,
+    PageDown,
+    JumpToBracket,
+    // lines
+    DuplicateLines,
+    DeleteLines,
+    JoinLines,
+    MoveLinesUp,
+    MoveLinesDown,
+    InsertLineAbove,
+    InsertLineBelow,
+    SortLines,
+    ReverseLines,
+    // cursors
+    AddCursorAbove,
+    AddCursorBelow,
+    AddNextOccurrence,
+    AddAllOccurrences,
+    // code
+    Search,
+    Replace,
+    ToggleWrap,
+    ConvertLineEnding,
+    ShowHistory,
+    PreviousHistoryBranch,
+    NextHistoryBranch,
+    // buffers
+    CloseBuffer,
+    PickBuffer,
+    PreviousBuffer,
+    NextBuffer,
+    MoveBufferLeft,
+    MoveBufferRight,
+    // panes
+    SplitSideBySide,
+    SplitStacked,
+    ClosePane,
+    FocusPaneLeft,
+    FocusPaneRight,
+    FocusPaneUp,
+    FocusPaneDown,
+    ResizePaneLeft,
+    ResizePaneRight,
+    ResizePaneUp,
+    ResizePaneDown,
+    // project
+    Open,
+    NewFile,
+    NewFolder,
+    Rename,
+    DeleteEntry,
+    // terminal
+    Run,
+    PreviousCommand,
+    NextCommand,
+    SelectLeft,
+    SelectRight,
+    // prompts
+    Confirm,
+    SwitchField,
+    NextMatch,
+    PreviousMatch,
+    ReplaceAll,
+    ToggleInSelection,
+    ToggleCase,
+    ToggleWholeWord,
+    ToggleRegex,
+    ToggleHunk,
+    ToggleFileHunks,
+}
+
+impl Action {
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Action::NextPanel => "next_panel",
+            Action::FocusPanel => "focus_panel",
+            Action::Quit => "quit",
+            Action::Leave => "leave",
+            Action::Cut => "cut",
+            Action::Copy => "copy",
+            Action::Paste => "paste",
+            Action::Save => "save",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::DeleteBackward => "delete_backward",
+            Action::DeleteForward => "delete_forward",
+            Action::NewLine => "new_line",
+            Action::Indent => "indent",
+            Action::Outdent => "outdent",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::WordLeft => "word_left",
+            Action::WordRight => "word_right",
+            Action::LineStart => "line_start",
+            Action::LineEnd => "line_end",
+            Action::CodeStart => "code_start",
+            Action::CodeEnd => "code_end",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::JumpToBracket => "jump_to_bracket",
+            Action::DuplicateLines => "duplicate_lines",
+            Action::DeleteLines => "delete_lines",
+            Action::JoinLines => "join_lines",
+            Action::MoveLinesUp => "move_lines_up",
+            Action::MoveLinesDown => "move_lines_down",
+            Action::InsertLineAbove => "insert_line_above",
+            Action::InsertLineBelow => "insert_line_below",
+            Action::SortLines => "sort_lines",
+            Action::ReverseLines => "reverse_lines",
+            Action::AddCursorAbove => "add_cursor_above",
+            Action::AddCursorBelow => "add_cursor_below",
+            Action::AddNextOccurrence => "add_next_occurrence",
+            Action::AddAllOccurrences => "add_all_occurrences",
+            Action::Search => "search",
+            Action::Replace => "replace",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ConvertLineEnding => "convert_line_ending",
+            Action::ShowHistory => "show_history",
+            Action::PreviousHistoryBranch => "previous_history_branch",
+            Action::NextHistoryBranch => "next_history_branch",
+            Action::CloseBuffer => "close_buffer",
+            Action::PickBuffer => "pick_buffer",
+            Action::PreviousBuffer => "previous_buffer",
+            Action::NextBuffer => "next_buffer",
+            Action::MoveBufferLeft => "move_buffer_left",
+            Action::MoveBufferRight => "move_buffer_right",
+            Action::SplitSideBySide => "split_side_by_side",
+            Action::SplitStacked => "split_stacked",
+            Action::ClosePane => "close_pane",
+            Action::FocusPaneLeft => "focus_pane_left",
+            Action::FocusPaneRight => "focus_pane_right",
+            Action::FocusPaneUp => "focus_pane_up",
+            Action::FocusPaneDown => "focus_pane_down",
+            Action::ResizePaneLeft => "resize_pane_left",
+            Action::ResizePaneRight => "resize_pane_right",
+            Action::ResizePaneUp => "resize_pane_up",
+            Action::ResizePaneDown => "resize_pane_down",
+            Action::Open => "open",
+            Action::NewFile => "new_file",
+            Action::NewFolder => "new_folder",
+            Action::Rename => "rename",
+            Action::DeleteEntry => "delete_entry",
+            Action::Run => "run",
+            Action::PreviousCommand => "previous_command",
+            Action::NextCommand => "next_command",
+            Action::SelectLeft => "select_left",
+            Action::SelectRight => "select_right",
+            Action::Confirm => "confirm",
+            Action::SwitchField => "switch_field",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::ReplaceAll => "replace_all",
+            Action::ToggleInSelection => "toggle_in_selection",
+            Action::ToggleCase => "toggle_case",
+            Action::ToggleWholeWord => "toggle_whole_word",
+            Action::ToggleRegex => "toggle_regex",
+            Action::ToggleHunk => "toggle_hunk",
+            Action::ToggleFileHunks => "toggle_file_hunks",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        DEFAULTS.iter().map(|(_, action, _)| *action).find(|action| action.get_name() == name)
+    }
+
+    // Scopes the action works in, the ones it has default keys in
+    pub fn get_scopes(&self) -> Vec<KeyScope> {
+        let mut scopes: Vec<KeyScope> = DEFAULTS.iter().filter(|(_, action, _)| action == self).map(|(scope, _, _)| *scope).collect();
+        scopes.dedup();
+        scopes
+    }
+
+    // Holding the keys of these actions runs them again and again
+    pub fn is_repeatable(&self) -> bool {
+        matches!(self,
+            Action::DeleteBackward | Action::DeleteForward | Action::NewLine | Action::Indent |
+            Action::MoveUp | Action::MoveDown | Action::MoveLeft | Action::MoveRight |
+            Action::WordLeft | Action::WordRight | Action::PageUp | Action::PageDown |
+            Action::PreviousCommand | Action::NextCommand | Action::SelectLeft | Action::SelectRight |
+            Action::NextMatch | Action::PreviousMatch
+        )
+    }
+}
+
+// Keys of the actions when the keymap file does not change them. Moving keys are bound with Shift too, it extends the selection
+const DEFAULTS: &[(KeyScope, Action, &[&str])] = &[
+    (KeyScope::Global, Action::NextPanel, &["tab"]),
+    (KeyScope::Global, Action::FocusPanel, &["enter"]),
+    (KeyScope::Global, Action::Quit, &["esc"]),
+
+    (KeyScope::Project, Action::MoveUp, &["up"]),
+    (KeyScope::Project, Action::MoveDown, &["down"]),
+    (KeyScope::Project, Action::Open, &["enter"]),
+    (KeyScope::Project, Action::NewFile, &["ctrl+f"]),
+    (KeyScope::Project, Action::NewFolder, &["ctrl+d"]),
+    (KeyScope::Project, Action::Search, &["ctrl+g"]),
+    (KeyScope::Project, Action::Rename, &["ctrl+r"]),
+    (KeyScope::Project, Action::DeleteEntry, &["delete"]),
+    (KeyScope::Project, Action::Leave, &["esc"]),
+
+    (KeyScope::Code, Action::Cut, &["ctrl+x"]),
+    (KeyScope::Code, Action::Copy, &["ctrl+c"]),
+    (KeyScope::Code, Action::Paste, &["ctrl+v"]),
+    (KeyScope::Code, Action::Save, &["ctrl+s"]),
+    (KeyScope::Code, Action::Undo, &["ctrl+z"]),
+    (KeyScope::Code, Action::Redo, &["ctrl+y"]),
+    (KeyScope::Code, Action::DeleteBackward, &["backspace"]),
+    (KeyScope::Code, Action::DeleteForward, &["delete"]),
+    (KeyScope::Code, Action::NewLine, &["enter"]),
+    (KeyScope::Code, Action::Indent, &["tab"]),
+    (KeyScope::Code, Action::Outdent, &["backtab"]),
+    (KeyScope::Code, Action::MoveUp, &["up", "shift+up"]),
+    (KeyScope::Code, Action::MoveDown, &["down", "shift+down"]),
+    (KeyScope::Code, Action::MoveLeft, &["left", "shift+left"]),
+    (KeyScope::Code, Action::MoveRight, &["right", "shift+right"]),
+    (KeyScope::Code, Action::WordLeft, &["ctrl+left", "ctrl+shift+left"]),
+    (KeyScope::Code, Action::WordRight, &["ctrl+right", "ctrl+shift+right"]),
+    (KeyScope::Code, Action::LineStart, &["home", "shift+home"]),
+    (KeyScope::Code, Action::LineEnd, &["end", "shift+end"]),
+    (KeyScope::Code, Action::CodeStart, &["ctrl+home", "ctrl+shift+home"]),
+    (KeyScope::Code, Action::CodeEnd, &["ctrl+end", "ctrl+shift+end"]),
+    (KeyScope::Code, Action::PageUp, &["pageup", "shift+pageup"]),
+    (KeyScope::Code, Action::PageDown, &["pagedown", "shift+pagedown"]),
+    (KeyScope::Code, Action::JumpToBracket, &["ctrl+b"]),
+    (KeyScope::Code, Action::DuplicateLines, &["ctrl+shift+d"]),
+    (KeyScope::Code, Action::DeleteLines, &["ctrl+shift+k"]),
+    (KeyScope::Code, Action::JoinLines, &["ctrl+j"]),
+    (KeyScope::Code, Action::MoveLinesUp, &["alt+up"]),
+    (KeyScope::Code, Action::MoveLinesDown, &["alt+down"]),
+    (KeyScope::Code, Action::InsertLineAbove, &["ctrl+shift+enter"]),
+    (KeyScope::Code, Action::InsertLineBelow, &["ctrl+enter"]),
+    (KeyScope::Code, Action::SortLines, &["alt+s"]),
+    (KeyScope::Code, Action::ReverseLines, &["alt+r"]),
+    (KeyScope::Code, Action::AddCursorAbove, &["ctrl+alt+up"]),
+    (KeyScope::Code, Action::AddCursorBelow, &["ctrl+alt+down"]),
+    (KeyScope::Code, Action::AddNextOccurrence, &["ctrl+d"]),
+    (KeyScope::Code, Action::AddAllOccurrences, &["ctrl+shift+l"]),
+    (KeyScope::Code, Action::Search, &["ctrl+f"]),
+    (KeyScope::Code, Action::Replace, &["ctrl+r"]),
+    (KeyScope::Code, Action::ToggleWrap, &["alt+z"]),
+    (KeyScope::Code, Action::ConvertLineEnding, &["ctrl+e"]),
+    (KeyScope::Code, Action::ShowHistory, &["ctrl+t"]),
+    (KeyScope::Code, Action::PreviousHistoryBranch, &["alt+left"]),
+    (KeyScope::Code, Action::NextHistoryBranch, &["alt+right"]),
+    (KeyScope::Code, Action::CloseBuffer, &["ctrl+w"]),
+    (KeyScope::Code, Action::PickBuffer, &["ctrl+p"]),
+    (KeyScope::Code, Action::PreviousBuffer, &["ctrl+pageup"]),
+    (KeyScope::Code, Action::NextBuffer, &["ctrl+pagedown"]),
+    (KeyScope::Code, Action::MoveBufferLeft, &["ctrl+shift+pageup"]),
+    (KeyScope::Code, Action::MoveBufferRight, &["ctrl+shift+pagedown"]),
+    (KeyScope::Code, Action::SplitSideBySide, &["alt+|"]),
+    (KeyScope::Code, Action::SplitStacked, &["alt+-"]),
+    (KeyScope::Code, Action::ClosePane, &["alt+w"]),
+    (KeyScope::Code, Action::FocusPaneLeft, &["alt+shift+left"]),
+    (KeyScope::Code, Action::FocusPaneRight, &["alt+shift+right"]),
+    (KeyScope::Code, Action::FocusPaneUp, &["alt+shift+up"]),
+    (KeyScope::Code, Action::FocusPaneDown, &["alt+shift+down"]),
+    (KeyScope::Code, Action::ResizePaneLeft, &["ctrl+alt+shift+left"]),
+    (KeyScope::Code, Action::ResizePaneRight, &["ctrl+alt+shift+right"]),
+    (KeyScope::Code, Action::ResizePaneUp, &["ctrl+alt+shift+up"]),
+    (KeyScope::Code, Action::ResizePaneDown, &["ctrl+alt+shift+down"]),
+    (KeyScope::Code, Action::Leave, &["esc"]),
+
+    (KeyScope::Terminal, Action::Cut, &["ctrl+x"]),
+    (KeyScope::Terminal, Action::Copy, &["ctrl+c"]),
+    (KeyScope::Terminal, Action::Paste, &["ctrl+v"]),
+    (KeyScope::Terminal, Action::Run, &["enter"]),
+    (KeyScope::Terminal, Action::DeleteForward, &["delete"]),
+    (KeyScope::Terminal, Action::PreviousCommand, &["up"]),
+    (KeyScope::Terminal, Action::NextCommand, &["down"]),
+    (KeyScope::Terminal, Action::MoveLeft, &["left"]),
+    (KeyScope::Terminal, Action::MoveRight, &["right"]),
+    (KeyScope::Terminal, Action::SelectLeft, &["leftshift"]),
+    (KeyScope::Terminal, Action::SelectRight, &["rightshift"]),
+    (KeyScope::Terminal, Action::Leave, &["esc"]),
+
+    //prompts take the keys first while they are open, the keys without an action are typed in them
+    (KeyScope::CodeSearch, Action::Confirm, &["enter"]),
+    (KeyScope::CodeSearch, Action::NextMatch, &["down", "f3"]),
+    (KeyScope::CodeSearch, Action::PreviousMatch, &["up", "shift+enter"]),
+    (KeyScope::CodeSearch, Action::Replace, &["ctrl+r"]),
+    (KeyScope::CodeSearch, Action::SwitchField, &["tab"]),
+    (KeyScope::CodeSearch, Action::ReplaceAll, &["alt+a"]),
+    (KeyScope::CodeSearch, Action::ToggleInSelection, &["alt+s"]),
+    (KeyScope::CodeSearch, Action::ToggleCase, &["alt+c"]),
+    (KeyScope::CodeSearch, Action::ToggleWholeWord, &["alt+w"]),
+    (KeyScope::CodeSearch, Action::ToggleRegex, &["alt+r"]),
+    (KeyScope::CodeSearch, Action::DeleteBackward, &["backspace"]),
+    (KeyScope::CodeSearch, Action::Leave, &["esc"]),
+
+    (KeyScope::HistoryPopup, Action::MoveUp, &["up"]),
+    (KeyScope::HistoryPopup, Action::MoveDown, &["down"]),
+    (KeyScope::HistoryPopup, Action::Confirm, &["enter"]),
+    (KeyScope::HistoryPopup, Action::DeleteBackward, &["backspace"]),
+    (KeyScope::HistoryPopup, Action::ShowHistory, &["ctrl+t"]),
+    (KeyScope::HistoryPopup, Action::Leave, &["esc"]),
+
+    (KeyScope::BufferPicker, Action::MoveUp, &["up"]),
+    (KeyScope::BufferPicker, Action::MoveDown, &["down"]),
+    (KeyScope::BufferPicker, Action::Confirm, &["enter"]),
+    (KeyScope::BufferPicker, Action::DeleteBackward, &["backspace"]),
+    (KeyScope::BufferPicker, Action::Leave, &["esc"]),
+
+    (KeyScope::ProjectSearch, Action::MoveUp, &["up"]),
+    (KeyScope::ProjectSearch, Action::MoveDown, &["down"]),
+    (KeyScope::ProjectSearch, Action::Confirm, &["enter"]),
+    (KeyScope::ProjectSearch, Action::Replace, &["ctrl+r"]),
+    (KeyScope::ProjectSearch, Action::SwitchField, &["tab"]),
+    (KeyScope::ProjectSearch, Action::ToggleHunk, &["alt+t"]),
+    (KeyScope::ProjectSearch, Action::ToggleFileHunks, &["alt+f"]),
+    (KeyScope::ProjectSearch, Action::ReplaceAll, &["alt+a"]),
+    (KeyScope::ProjectSearch, Action::ToggleCase, &["alt+c"]),
+    (KeyScope::ProjectSearch, Action::ToggleWholeWord, &["alt+w"]),
+    (KeyScope::ProjectSearch, Action::ToggleRegex, &["alt+r"]),
+    (KeyScope::ProjectSearch, Action::DeleteBackward, &["backspace"]),
+    (KeyScope::ProjectSearch, Action::Leave, &["esc"]),
+];
+
+// Bindings of every action to its default keys
+pub fn default_bindings() -> Vec<KeyBinding> {
+    DEFAULTS
+    .iter()
+    .flat_map(|(scope, action, keys)| {
+        keys.iter().map(|text| KeyBinding::new(*scope, parse_sequence(text).expect("invalid default key"), *action))
+    })
+    .collect()
+}
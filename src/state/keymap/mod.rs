@@ -0,0 +1,405 @@
+use std::{env, fmt, fs, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, ModifierKeyCode};
+
+use self::keymap_defaults::{default_bindings, Action};
+
+pub mod keymap_defaults;
+
+// Part of the editor a binding works in: a panel, a prompt open over a panel, or the app while no panel is focused
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum KeyScope {
+    Global,
+    Project,
+    Code,
+    Terminal,
+    CodeSearch,
+    HistoryPopup,
+    BufferPicker,
+    ProjectSearch,
+}
+
+impl KeyScope {
+
+    // Table of the keymap file with the bindings of the scope
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            KeyScope::Global => "global",
+            KeyScope::Project => "project",
+            KeyScope::Code => "code",
+            KeyScope::Terminal => "terminal",
+            KeyScope::CodeSearch => "code_search",
+            KeyScope::HistoryPopup => "history",
+            KeyScope::BufferPicker => "buffer_picker",
+            KeyScope::ProjectSearch => "project_search",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyScope> {
+        [
+            KeyScope::Global, KeyScope::Project, KeyScope::Code, KeyScope::Terminal,
+            KeyScope::CodeSearch, KeyScope::HistoryPopup, KeyScope::BufferPicker, KeyScope::ProjectSearch,
+        ]
+        .into_iter()
+        .find(|scope| scope.get_name() == name)
+    }
+}
+
+// A key pressed with its modifiers. Letters are kept lowercase with Shift among the modifiers,
+// the other characters already tell whether Shift was held so it is left out
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(char) if char.is_alphabetic() => {
+                if char.is_uppercase() {
+                    modifiers |= KeyModifiers::SHIFT;
+                }
+                KeyCode::Char(char.to_lowercase().next().unwrap_or(char))
+            },
+            KeyCode::Char(char) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(char)
+            },
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            },
+            KeyCode::BackTab => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            },
+            code => code,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    pub fn get_code(&self) -> KeyCode {
+        self.code
+    }
+
+    pub fn get_modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    // Parses a chord like "ctrl+shift+d", "alt+|", "pageup" or "ctrl++"
+    pub fn parse(text: &str) -> Result<KeyChord, String> {
+        let text = text.trim();
+        let (prefix, key) = if text == "+" {
+            ("", "+")
+        } else if let Some(prefix) = text.strip_suffix("++") {
+            (prefix, "+")
+        } else {
+            text.rsplit_once('+').unwrap_or(("", text))
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in prefix.split('+').filter(|modifier| !modifier.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, text)),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(char), None) => KeyCode::Char(char.to_lowercase().next().unwrap_or(char)),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                "leftshift" => KeyCode::Modifier(ModifierKeyCode::LeftShift),
+                "rightshift" => KeyCode::Modifier(ModifierKeyCode::RightShift),
+                name => match name.strip_prefix('f').and_then(|number| number.parse::<u8>().ok()) {
+                    Some(number) if (1..=12).contains(&number) => KeyCode::F(number),
+                    _ => return Err(format!("unknown key '{}' in '{}'", key, text)),
+                },
+            },
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> KeyChord {
+        KeyChord::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [(KeyModifiers::CONTROL, "ctrl"), (KeyModifiers::ALT, "alt"), (KeyModifiers::SHIFT, "shift")] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(char) => write!(f, "{}", char),
+            KeyCode::F(number) => write!(f, "f{}", number),
+            KeyCode::Modifier(ModifierKeyCode::LeftShift) => write!(f, "leftshift"),
+            KeyCode::Modifier(ModifierKeyCode::RightShift) => write!(f, "rightshift"),
+            code => write!(f, "{}", format!("{:?}", code).to_lowercase()),
+        }
+    }
+}
+
+// Chords pressed one after the other, written separated by spaces like "ctrl+k ctrl+c"
+pub fn parse_sequence(text: &str) -> Result<Vec<KeyChord>, String> {
+    let keys: Vec<KeyChord> = text.split_whitespace().map(KeyChord::parse).collect::<Result<_, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(keys)
+}
+
+pub fn sequence_to_string(keys: &[KeyChord]) -> String {
+    keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+// The char typed by a key without an action, only plain or shifted keys type
+pub fn typed_char(key: &KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(char) if (key.modifiers - KeyModifiers::SHIFT).is_empty() => Some(char),
+        _ => None,
+    }
+}
+
+// A sequence of chords running an action in a scope
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct KeyBinding {
+    scope: KeyScope,
+    keys: Vec<KeyChord>,
+    action: Action,
+}
+
+impl KeyBinding {
+
+    pub fn new(scope: KeyScope, keys: Vec<KeyChord>, action: Action) -> KeyBinding {
+        KeyBinding { scope, keys, action }
+    }
+
+    pub fn get_scope(&self) -> KeyScope {
+        self.scope
+    }
+
+    pub fn get_keys(&self) -> &Vec<KeyChord> {
+        &self.keys
+    }
+
+    pub fn get_action(&self) -> Action {
+        self.action
+    }
+}
+
+// What a key does in a scope
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum KeyMatch {
+    Action(Action),
+    // the key starts (or goes on with) a sequence and the next keys tell which action it is,
+    // or it is held on an action that does not repeat
+    Pending,
+    // no binding uses the key, the component can take it as text
+    Unbound,
+}
+
+// Actions run by the keys of every scope, and the first keys of a sequence pressed so far
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+    pending: Vec<KeyChord>,
+    pending_scope: Option<KeyScope>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new(default_bindings())
+    }
+}
+
+impl Keymap {
+
+    pub fn new(bindings: Vec<KeyBinding>) -> Keymap {
+        Keymap {
+            bindings,
+            pending: vec![],
+            pending_scope: None,
+        }
+    }
+
+    pub fn get_bindings(&self) -> &Vec<KeyBinding> {
+        &self.bindings
+    }
+
+    pub fn get_pending(&self) -> &Vec<KeyChord> {
+        &self.pending
+    }
+
+    // Keys running an action in a scope
+    pub fn keys_of(&self, scope: KeyScope, action: Action) -> Vec<&Vec<KeyChord>> {
+        self.bindings
+        .iter()
+        .filter(|binding| binding.scope == scope && binding.action == action)
+        .map(|binding| &binding.keys)
+        .collect()
+    }
+
+    // The default bindings with the ones of a keymap file in place of the defaults of the actions it lists.
+    // Every table of the file is a scope mapping action names to a key or a list of keys, an empty list unbinds the action.
+    // What cannot be used is returned with the keymap
+    pub fn with_overrides(text: &str) -> (Keymap, Vec<String>) {
+        let mut keymap = Keymap::default();
+        let mut problems = vec![];
+        let table = match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(error) => return (keymap, vec![format!("invalid keymap file: {}", error.message())]),
+        };
+
+        for (scope_name, actions) in table {
+            let Some(scope) = KeyScope::from_name(&scope_name) else {
+                problems.push(format!("unknown scope [{}]", scope_name));
+                continue;
+            };
+            let Some(actions) = actions.as_table() else {
+                problems.push(format!("[{}] is not a table", scope_name));
+                continue;
+            };
+            for (action_name, keys) in actions {
+                let action = Action::from_name(action_name).filter(|action| action.get_scopes().contains(&scope));
+                let Some(action) = action else {
+                    problems.push(format!("unknown action {} in [{}]", action_name, scope_name));
+                    continue;
+                };
+                let texts: Vec<&str> = match keys {
+                    toml::Value::String(text) => vec![text.as_str()],
+                    toml::Value::Array(texts) if texts.iter().all(|text| text.is_str()) => texts.iter().filter_map(|text| text.as_str()).collect(),
+                    _ => {
+                        problems.push(format!("{} in [{}] is not a key or a list of keys", action_name, scope_name));
+                        continue;
+                    }
+                };
+                //an action with a wrong key keeps its default keys
+                let sequences = match texts.into_iter().map(parse_sequence).collect::<Result<Vec<_>, _>>() {
+                    Ok(sequences) => sequences,
+                    Err(error) => {
+                        problems.push(format!("{} in [{}]: {}", action_name, scope_name, error));
+                        continue;
+                    }
+                };
+                keymap.bindings.retain(|binding| binding.scope != scope || binding.action != action);
+                keymap.bindings.extend(sequences.into_iter().map(|keys| KeyBinding::new(scope, keys, action)));
+            }
+        }
+        (keymap, problems)
+    }
+
+    // Bindings of a scope sharing their keys, or whose keys start another binding which then can never run
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = vec![];
+        for (index, first) in self.bindings.iter().enumerate() {
+            for second in self.bindings.iter().skip(index + 1) {
+                if first.scope != second.scope || (first.keys == second.keys && first.action == second.action) {
+                    continue;
+                }
+                let scope = first.scope.get_name();
+                if first.keys == second.keys {
+                    conflicts.push(format!("{} runs both {} and {} in [{}]", sequence_to_string(&first.keys), first.action.get_name(), second.action.get_name(), scope));
+                } else if second.keys.starts_with(&first.keys) || first.keys.starts_with(&second.keys) {
+                    let (short, long) = if first.keys.len() < second.keys.len() { (first, second) } else { (second, first) };
+                    conflicts.push(format!("{} ({}) hides {} ({}) in [{}]", sequence_to_string(&short.keys), short.action.get_name(), sequence_to_string(&long.keys), long.action.get_name(), scope));
+                }
+            }
+        }
+        conflicts
+    }
+
+    // Action of a key pressed in a scope. The keys of a sequence are kept until it is complete,
+    // a key that does not go on with it drops the sequence and counts on its own.
+    // Held keys repeat only the actions that make sense to repeat
+    pub fn resolve(&mut self, scope: KeyScope, key: KeyEvent) -> KeyMatch {
+        let chord = KeyChord::from(key);
+        if key.kind == KeyEventKind::Repeat {
+            return match self.action_of(scope, &[chord]) {
+                Some(action) if action.is_repeatable() => KeyMatch::Action(action),
+                Some(_) => KeyMatch::Pending,
+                None if self.is_prefix(scope, &[chord]) => KeyMatch::Pending,
+                None => KeyMatch::Unbound,
+            };
+        }
+
+        if self.pending_scope != Some(scope) {
+            self.pending.clear();
+            self.pending_scope = Some(scope);
+        }
+        self.pending.push(chord);
+        if let Some(action) = self.action_of(scope, &self.pending) {
+            self.pending.clear();
+            return KeyMatch::Action(action);
+        }
+        if self.is_prefix(scope, &self.pending) {
+            return KeyMatch::Pending;
+        }
+        let broken = self.pending.len() > 1;
+        self.pending.clear();
+        if broken {
+            return self.resolve(scope, key);
+        }
+        KeyMatch::Unbound
+    }
+
+    fn action_of(&self, scope: KeyScope, keys: &[KeyChord]) -> Option<Action> {
+        self.bindings
+        .iter()
+        .find(|binding| binding.scope == scope && binding.keys == keys)
+        .map(|binding| binding.action)
+    }
+
+    fn is_prefix(&self, scope: KeyScope, keys: &[KeyChord]) -> bool {
+        self.bindings
+        .iter()
+        .any(|binding| binding.scope == scope && binding.keys.len() > keys.len() && binding.keys.starts_with(keys))
+    }
+}
+
+// Folder of the configuration of the editor: $XDG_CONFIG_HOME/noce or ~/.config/noce
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config) if !config.is_empty() => PathBuf::from(config),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("noce"))
+}
+
+// Keymap of the keymap.toml of the configuration folder, or the default one without the file,
+// with the problems of the file and the conflicts of its bindings
+pub fn load_keymap() -> (Keymap, Vec<String>) {
+    let text = config_dir().and_then(|config_dir| fs::read_to_string(config_dir.join("keymap.toml")).ok());
+    let Some(text) = text else {
+        return (Keymap::default(), vec![]);
+    };
+    let (keymap, mut problems) = Keymap::with_overrides(&text);
+    problems.extend(keymap.conflicts());
+    (keymap, problems)
+}
@@ -1,10 +1,11 @@
 use std::{path::PathBuf, env::{current_dir, self}};
 
-use crossterm::event::{Event, KeyEventKind, KeyCode};
+use crossterm::event::{Event, KeyEvent, KeyEventKind, KeyCode, MouseEvent, MouseEventKind, MouseButton};
 
-use self::{project::ProjectComponent, code::CodeComponent, terminal::TerminalComponent};
+use self::{project::{ProjectComponent, project_search::apply_replacements}, code::{CodeComponent, code_utils::Point}, terminal::TerminalComponent, keymap::{Keymap, KeyMatch, KeyScope, keymap_defaults::Action}};
 
 pub mod code;
+pub mod keymap;
 pub mod project;
 pub mod terminal;
 
@@ -20,13 +21,57 @@ pub trait Component {
     fn handle_event(&mut self, context: &mut AppContext, event: Event);
 }
 
+// Cells of the screen a panel, or a part of it, was drawn on in the last frame. Mouse events are routed with them
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct ScreenArea {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+}
+
+impl ScreenArea {
+
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> ScreenArea {
+        ScreenArea { x, y, width, height }
+    }
+
+    pub fn get_x(&self) -> u16 {
+        self.x
+    }
+
+    pub fn get_y(&self) -> u16 {
+        self.y
+    }
+
+    pub fn get_width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn contains(&self, column: u16, row: u16) -> bool {
+        column >= self.x && column < self.x + self.width && row >= self.y && row < self.y + self.height
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct AppContext {
+    // folder the editor was opened in
+    project_root: PathBuf,
     active_folder: PathBuf,
     active_file: Option<PathBuf>,
     active_file_changed: bool,
+    // where to put the cursor once the active file is opened
+    active_position: Option<Point>,
     focus: Option<ComponentType>,
     hover: ComponentType,
+    // last result reported to the user, like a failed save
+    message: Option<String>,
+    // actions of the keys in every panel
+    keymap: Keymap,
 }
 
 impl Default for AppContext {
@@ -39,11 +84,15 @@ impl Default for AppContext {
             folder = PathBuf::default();
         }
         AppContext {
+            project_root: folder.clone(),
             active_folder: folder,
             active_file: None,
             active_file_changed: false,
+            active_position: None,
             focus: None,
             hover: ComponentType::Project,
+            message: None,
+            keymap: Keymap::default(),
         }
     }
 }
@@ -51,14 +100,23 @@ impl Default for AppContext {
 impl AppContext {
     pub fn new(active_folder: PathBuf, active_file: Option<PathBuf>, focus: Option<ComponentType>, hover: ComponentType) -> AppContext {
         AppContext {
+            project_root: active_folder.clone(),
             active_folder: active_folder,
             active_file: active_file,
             active_file_changed: false,
+            active_position: None,
             focus: focus,
             hover: hover,
+            message: None,
+            keymap: Keymap::default(),
         }
     }
 
+    // Getter for project_root
+    pub fn project_root(&self) -> &PathBuf {
+        &self.project_root
+    }
+
    // Getter for active_folder
    pub fn active_folder(&self) -> &PathBuf {
         &self.active_folder
@@ -87,6 +145,16 @@ impl AppContext {
     pub fn set_active_file_changed(&mut self, change: bool) {
         self.active_file_changed = change;
     }
+
+    // Getter for active_position
+    pub fn active_position(&self) -> &Option<Point> {
+        &self.active_position
+    }
+
+    // Setter for active_position
+    pub fn set_active_position(&mut self, position: Option<Point>) {
+        self.active_position = position;
+    }
     
     // Getter for focus
     pub fn focus(&self) -> &Option<ComponentType> {
@@ -107,14 +175,58 @@ impl AppContext {
     pub fn set_hover(&mut self, component: ComponentType) {
         self.hover = component;
     }
+
+    // Getter for message
+    pub fn message(&self) -> &Option<String> {
+        &self.message
+    }
+
+    // Setter for message
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    // Getter for keymap
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    // Setter for keymap
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    // Action of a key pressed in a scope, see Keymap::resolve
+    pub fn resolve_key(&mut self, scope: KeyScope, key: KeyEvent) -> KeyMatch {
+        self.keymap.resolve(scope, key)
+    }
     
 }
 
+// What the user asked to do while the code had unsaved changes
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum PendingAction {
+    Quit,
+    Close,
+}
+
+// Buttons of the unsaved changes dialog
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ConfirmChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct App {
     project: ProjectComponent,
     code: CodeComponent,
     terminal: TerminalComponent,    
+    pending: Option<PendingAction>,
+    confirm_choice: ConfirmChoice,
+    // where every panel was drawn, to find the one under the mouse
+    areas: Vec<(ComponentType, ScreenArea)>,
 }
 
 impl Default for App {
@@ -123,7 +235,10 @@ impl Default for App {
         App {
             project: ProjectComponent::new(env::current_dir().unwrap().to_path_buf()),
             code: CodeComponent::new(),
-            terminal: TerminalComponent::new()
+            terminal: TerminalComponent::new(),
+            pending: None,
+            confirm_choice: ConfirmChoice::Save,
+            areas: vec![],
         }
     }
 }
@@ -135,7 +250,10 @@ impl App {
         App {
             project: ProjectComponent::new(path),
             code: code,
-            terminal: terminal 
+            terminal: terminal,
+            pending: None,
+            confirm_choice: ConfirmChoice::Save,
+            areas: vec![],
         }
     }
 
@@ -155,51 +273,234 @@ impl App {
         &mut self.code
     }
 
+    pub fn get_mut_project(&mut self) -> &mut ProjectComponent {
+        &mut self.project
+    }
+
+    pub fn get_mut_terminal(&mut self) -> &mut TerminalComponent {
+        &mut self.terminal
+    }
+
+    pub fn set_panel_areas(&mut self, areas: Vec<(ComponentType, ScreenArea)>) {
+        self.areas = areas;
+    }
+
+    pub fn get_pending(&self) -> &Option<PendingAction> {
+        &self.pending
+    }
+
+    pub fn get_confirm_choice(&self) -> ConfirmChoice {
+        self.confirm_choice
+    }
+
+    // Opens the unsaved changes dialog, the action runs once the user chose what to do with the changes
+    fn ask_confirm(&mut self, action: PendingAction) {
+        self.pending = Some(action);
+        self.confirm_choice = ConfirmChoice::Save;
+    }
+
+    // Writes the hunks accepted in the replace preview of the project search, then searches again
+    fn apply_project_replace(&mut self, context: &mut AppContext) {
+        if let Some(search) = self.project.get_mut_search() {
+            if search.take_apply_request() {
+                let report = apply_replacements(search, &mut self.code);
+                context.set_message(Some(report.get_message()));
+                search.update(context.active_folder());
+            }
+        }
+    }
+
+    // Keys of the unsaved changes dialog, returns false if the app has to quit
+    fn handle_confirm(&mut self, context: &mut AppContext, event: Event) -> bool {
+        let Event::Key(key) = event else {
+            return true;
+        };
+        if key.kind != KeyEventKind::Press {
+            return true;
+        }
+        let choice = match key.code {
+            KeyCode::Left | KeyCode::BackTab => {
+                self.confirm_choice = match self.confirm_choice {
+                    ConfirmChoice::Save => ConfirmChoice::Cancel,
+                    ConfirmChoice::Discard => ConfirmChoice::Save,
+                    ConfirmChoice::Cancel => ConfirmChoice::Discard,
+                };
+                None
+            },
+            KeyCode::Right | KeyCode::Tab => {
+                self.confirm_choice = match self.confirm_choice {
+                    ConfirmChoice::Save => ConfirmChoice::Discard,
+                    ConfirmChoice::Discard => ConfirmChoice::Cancel,
+                    ConfirmChoice::Cancel => ConfirmChoice::Save,
+                };
+                None
+            },
+            KeyCode::Enter => Some(self.confirm_choice),
+            KeyCode::Char('s') => Some(ConfirmChoice::Save),
+            KeyCode::Char('d') => Some(ConfirmChoice::Discard),
+            KeyCode::Char('c') | KeyCode::Esc => Some(ConfirmChoice::Cancel),
+            _ => None,
+        };
+
+        let Some(choice) = choice else {
+            return true;
+        };
+        let Some(action) = self.pending.take() else {
+            return true;
+        };
+        if choice == ConfirmChoice::Cancel {
+            context.set_active_position(None);
+            return true;
+        }
+        match action {
+            PendingAction::Quit => {
+                if choice == ConfirmChoice::Save {
+                    self.code.save_all(context);
+                }
+                //a failed save keeps the changes and the message explains why
+                choice == ConfirmChoice::Save && self.code.has_modified_buffers()
+            },
+            PendingAction::Close => {
+                if choice == ConfirmChoice::Save {
+                    self.code.save(context);
+                    if self.code.is_modified() {
+                        return true;
+                    }
+                }
+                self.close_buffer(context);
+                true
+            }
+        }
+    }
+
+    // Closes the buffer shown by the code, keeping its history, and makes the next one the active file
+    fn close_buffer(&mut self, context: &mut AppContext) {
+        self.code.store_history(context.project_root());
+        self.code.close_buffer();
+        context.set_active_file(self.code.get_path().clone());
+        context.set_active_file_changed(false);
+    }
+
+    // Loads the active file in the code when it changed, with the cursor where it was asked.
+    // When the file can not be read the code keeps its file and the error is shown
+    pub fn open_active_file(&mut self, context: &mut AppContext) {
+        if context.active_file_changed() {
+            let opened = self.code.set_current(context.active_file().clone(), context.project_root());
+            context.set_active_file_changed(false);
+            let name = context.active_file().as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+            if let Err(error) = opened {
+                context.set_message(Some(format!("Could not open {}: {}", name, error)));
+                context.set_active_file(self.code.get_path().clone());
+                context.set_active_file_changed(false);
+                context.set_active_position(None);
+                return;
+            }
+            if self.code.has_mixed_line_endings() {
+                let line_ending = self.code.get_format().get_line_ending().get_name();
+                context.set_message(Some(format!("{} mixes line endings, saving converts them to {}", name, line_ending)));
+            }
+            if let Some(position) = context.active_position().clone() {
+                self.code.go_to(position);
+                context.set_active_position(None);
+            }
+        }
+    }
+
+    // Passes an event to a component, then runs what the component asked to the app
+    fn dispatch(&mut self, context: &mut AppContext, component: ComponentType, event: Event) {
+        match component {
+            ComponentType::Project => {
+                self.project.handle_event(context, event);
+                self.apply_project_replace(context);
+            },
+            ComponentType::Code => {
+                self.code.handle_event(context, event);
+                if self.code.take_close_request() {
+                    if self.code.is_modified() {
+                        self.ask_confirm(PendingAction::Close);
+                    } else {
+                        self.close_buffer(context);
+                    }
+                }
+            },
+            ComponentType::Terminal => {
+                self.terminal.handle_event(context, event);
+            }
+        }
+    }
+
+    // Component getting a mouse event: a click focuses the panel under the mouse, the wheel scrolls it
+    // and dragging or releasing the button goes on in the focused panel
+    fn mouse_target(&mut self, context: &mut AppContext, mouse: MouseEvent) -> Option<ComponentType> {
+        let under = self.areas.iter().find(|(_, area)| area.contains(mouse.column, mouse.row)).map(|(component, _)| component.clone());
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let component = under?;
+                context.set_focus(Some(component.clone()));
+                context.set_hover(component.clone());
+                Some(component)
+            },
+            MouseEventKind::Drag(MouseButton::Left) | MouseEventKind::Up(MouseButton::Left) => context.focus().clone(),
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => under,
+            _ => None,
+        }
+    }
+
     pub fn handle_event(&mut self, context: &mut AppContext, focus: Option<ComponentType>, event: Event) -> bool {
 
+        //a message is shown until the next key is pressed
+        if let Event::Key(key) = event {
+            if key.kind == KeyEventKind::Press {
+                context.set_message(None);
+            }
+        }
+
+        if self.pending.is_some() {
+            return self.handle_confirm(context, event);
+        }
+
+
+        if let Event::Mouse(mouse) = event {
+            if let Some(component) = self.mouse_target(context, mouse) {
+                self.dispatch(context, component, event);
+            }
+            return true;
+        }
 
         if focus.is_some() {
             if let Some(focus) = focus {
-                match focus {
-                    ComponentType::Project => {
-                        self.project.handle_event(context, event);
-                    },
-                    ComponentType::Code => {
-                        self.code.handle_event(context, event);
-                    },
-                    ComponentType::Terminal => {
-                        self.terminal.handle_event(context, event);
-                    }
-                }
+                self.dispatch(context, focus, event);
             }
         } else {
             if let Event::Key(key) = event {
                 if key.kind == KeyEventKind::Press {
-                    let hover: Option<ComponentType> = Some(context.hover().clone());
-                    match key.code {
-                        KeyCode::Tab => {
-                            if focus.is_none() {
-                                if let Some(hover) = hover {
-                                    let next_hover = match hover {
-                                        ComponentType::Project => {
-                                            ComponentType::Code
-                                        },
-                                        ComponentType::Code => {
-                                            ComponentType::Terminal
-                                        },
-                                        ComponentType::Terminal => {
-                                            ComponentType::Project
-                                        },
-                                    };
-                                    context.set_hover(next_hover);
-                                }
-                            }
+                    match context.resolve_key(KeyScope::Global, key) {
+                        KeyMatch::Action(Action::NextPanel) => {
+                            let next_hover = match context.hover() {
+                                ComponentType::Project => {
+                                    ComponentType::Code
+                                },
+                                ComponentType::Code => {
+                                    ComponentType::Terminal
+                                },
+                                ComponentType::Terminal => {
+                                    ComponentType::Project
+                                },
+                            };
+                            context.set_hover(next_hover);
                         },
-                        KeyCode::Enter => {
+                        KeyMatch::Action(Action::FocusPanel) => {
                             context.set_focus(Some(context.hover().clone()));
                         },
-                        KeyCode::Esc => {
-                            return false;
+                        KeyMatch::Action(Action::Quit) => {
+                            if self.code.has_modified_buffers() {
+                                self.ask_confirm(PendingAction::Quit);
+                            } else {
+                                return false;
+                            }
                         },
                         _ => {
                         }
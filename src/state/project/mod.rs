@@ -1,8 +1,15 @@
 use std::{path::{PathBuf, Path}, fs::{File, create_dir, read_dir, rename, remove_file, remove_dir_all}};
 
-use crossterm::event::{Event, KeyEventKind, KeyCode, KeyModifiers};
+use crossterm::event::{Event, KeyEventKind, KeyCode, MouseEvent, MouseEventKind, MouseButton};
 
-use super::{Component, ComponentType, AppContext};
+use super::{Component, ComponentType, AppContext, ScreenArea, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
+
+use self::project_search::{ProjectSearch, handle_project_search};
+
+pub mod project_search;
+
+// Entries scrolled by a step of the wheel
+const WHEEL_ROWS: usize = 3;
 
 #[derive(PartialEq, Eq)]
 pub enum ContentType {
@@ -20,6 +27,10 @@ pub struct ProjectComponent {
     edit_extension: bool,
     popup: bool,
     popup_decision: bool,
+    search: Option<ProjectSearch>,
+    // first entry shown by the list and where the list is drawn
+    offset: usize,
+    area: ScreenArea,
 }
 
 impl ProjectComponent {
@@ -73,6 +84,78 @@ impl ProjectComponent {
         self.popup_decision
     }
 
+    pub fn get_search(&self) -> &Option<ProjectSearch> {
+        &self.search
+    }
+
+    pub fn get_mut_search(&mut self) -> &mut Option<ProjectSearch> {
+        &mut self.search
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    // Keeps where the entries are listed, scrolling the list to show the hovered one
+    pub fn set_list_area(&mut self, area: ScreenArea) {
+        self.area = area;
+        let height = (area.get_height() as usize).max(1);
+        if self.hover < self.offset {
+            self.offset = self.hover;
+        } else if self.hover >= self.offset + height {
+            self.offset = self.hover + 1 - height;
+        }
+        self.offset = self.offset.min(self.contents.len().saturating_sub(height));
+    }
+
+    // Enters the hovered folder, or opens the hovered file in the code
+    fn open_hover(&mut self, context: &mut AppContext) {
+        self.set_focus(Some(self.get_hover().clone()));
+
+        if let Some(focus) = self.get_focus() {
+            let selected_item = self.contents[focus.clone()].clone();
+
+            if selected_item.is_dir() {
+                context.set_active_folder(selected_item.clone());
+                self.update_contents(&selected_item);
+                self.set_hover(0);
+                self.set_focus(None);
+                self.offset = 0;
+            } else if  selected_item.is_file() {
+                context.set_active_file(Some(selected_item));
+            }
+        }
+    }
+
+    // A click on an entry opens it like Enter does, the wheel scrolls the list keeping the hovered entry inside it
+    fn handle_mouse(&mut self, context: &mut AppContext, mouse: MouseEvent) {
+        if self.search.is_some() || self.popup || self.edit {
+            return;
+        }
+        let height = (self.area.get_height() as usize).max(1);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !self.area.contains(mouse.column, mouse.row) {
+                    return;
+                }
+                let index = self.offset + (mouse.row - self.area.get_y()) as usize;
+                if index < self.contents.len() {
+                    self.set_hover(index);
+                    self.open_hover(context);
+                }
+            },
+            MouseEventKind::ScrollUp => {
+                self.offset = self.offset.saturating_sub(WHEEL_ROWS);
+                self.hover = self.hover.min(self.offset + height - 1);
+            },
+            MouseEventKind::ScrollDown => {
+                self.offset = (self.offset + WHEEL_ROWS).min(self.contents.len().saturating_sub(height));
+                self.hover = self.hover.max(self.offset);
+            },
+            _ => {}
+        }
+    }
+
 }
 
 impl Component for ProjectComponent {
@@ -82,170 +165,164 @@ impl Component for ProjectComponent {
     }
 
     fn handle_event(&mut self, context: &mut AppContext, event: Event) {
+        if let Event::Mouse(mouse) = event {
+            self.handle_mouse(context, mouse);
+        }
         if let Event::Key(key) = event {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Left => {
-                        if self.popup {
-                            self.popup_decision = true;
-                        }
-                    },
-                    KeyCode::Right => {
-                        if self.popup {
-                            self.popup_decision = false;
-                        }
-                    },
-                    KeyCode::Up => {
-                        if !self.edit {
-                            if self.get_hover() > &0 {
-                                self.set_hover(self.get_hover() - 1);
-                            } else {
-                                self.set_hover(self.contents.len()-1);
-                            }    
-                        }
-                    },
-                    KeyCode::Down => {
-                        if !self.edit {
-                            if self.get_hover() < &(self.contents.len() - 1){
-                                self.set_hover(self.get_hover() + 1);
-                            } else {
-                                self.set_hover(0);
-                            }    
-                        }
+            if self.search.is_some() {
+                if key.kind != KeyEventKind::Release {
+                    handle_project_search(self, context, key);
+                }
+                return;
+            }
+            //the dialog asking to delete an entry takes its answer keys
+            if self.popup && key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Left | KeyCode::Right | KeyCode::Enter) {
+                self.handle_popup(context, key.code);
+                return;
+            }
+            if key.kind != KeyEventKind::Release {
+                match context.resolve_key(KeyScope::Project, key) {
+                    KeyMatch::Action(action) => {
+                        self.run_action(context, action);
                     },
-                    KeyCode::Enter => {
-                        if !self.popup {
-                        if !self.edit {
-                            self.set_focus(Some(self.get_hover().clone()));
-
-                            if let Some(focus) = self.get_focus() {
-                                let selected_item = self.contents[focus.clone()].clone();
-
-                                if selected_item.is_dir() {
-                                    context.set_active_folder(selected_item.clone());
-                                    self.update_contents(&selected_item);
-                                    self.set_hover(0);
-                                    self.set_focus(None);
-                                } else if  selected_item.is_file() {
-                                    context.set_active_file(Some(selected_item));
-                                }                                    
-                            }    
-                        } else {
-                            self.edit = false;
-                            self.edit_extension = false;
-                        }
-                    } else {
-                        if self.popup_decision {
-                            let hover = self.contents[self.get_hover().clone()].clone();
-                            if hover.is_file() {
-                                let _ = remove_file(hover);
-                            } else if hover.is_dir() {
-                                let _ = remove_dir_all(hover);
+                    KeyMatch::Pending => {},
+                    KeyMatch::Unbound => {
+                        //keys without an action type the new name of the entry being renamed
+                        if let Some(char) = typed_char(&key) {
+                            if key.kind == KeyEventKind::Press {
+                                self.type_name(context, char);
                             }
                         }
-                        self.popup = false;
-
-                        self.update_contents(context.active_folder());
-
-                    }
+                    },
+                }
+            }
+        }
+    }
+}
 
+impl ProjectComponent {
 
-                    },
-                    KeyCode::Char(char) => {
-                        let char = char.to_lowercase().last();
-                        if let Some(char) = char {
-                            if char == 'f' && key.modifiers.contains(KeyModifiers::CONTROL) {
-                                self.add_content(context.active_folder(), String::from("new_file"), ContentType::FILE);
-                            }
-                            else if char == 'd' && key.modifiers.contains(KeyModifiers::CONTROL) {
-                                self.add_content(context.active_folder(), String::from("new_folder"), ContentType::FOLDER);
-                            }
-                            else if char == 'r' && key.modifiers.contains(KeyModifiers::CONTROL) {
-                                if self.get_focus().is_none() && !self.edit {
-                                    self.edit = true;
-                                    self.first_edit = true;
-                                }
-                            }
-                            else if char == 'x' && key.modifiers.contains(KeyModifiers::CONTROL) {
-                                //eliminate folder or file which is hovered
-                                if self.get_focus().is_none() {
-                                    self.popup = true;
-                                }
-                            } else {
-                                if self.edit {
-                                    if let Some(thing) = self.contents.get(self.get_hover().clone()) {
-                                        let path = thing.as_path();
-                                        if let Some(name) = thing.file_name() {
-                                            if let Some(name_as_str) = name.to_str() {
-                                                let from = name_as_str.to_string();
-                                                let mut to = from.clone();
-
-                                                if !self.edit_extension {
-                                                    //if is the first char the user digits the name goes resetted
-                                                    if self.first_edit {
-                                                        to.clear();
-                                                        self.first_edit = false;
-                                                    } else if char == '.' && path.is_file() {
-                                                        //if the user pressed . and the name is not empty we want to enter in extension mode to add an extension
-                                                        self.edit_extension = true;
-                                                    } 
-                                                    to.push(char);
-                                                    let _ = rename(from, to);
-    
-                                                } else {
-
-                                                    if let Some(extension) = path.extension() {
-                                                        if let Some(extension) = extension.to_str() {
-                                                            let mut extension = extension.to_string();
-                                                            extension.push(char);
-                                                            let to_path = Path::new(&name_as_str.to_string()).with_extension(extension);
-                                                            let _ = rename(from, to_path);
-                                                        }
-                                                    } else {
-                                                        let to_path = Path::new(&name_as_str.to_string()).with_extension(char.to_string());
-                                                        let _ = rename(from, to_path);
-                                                    }
-                                                }
-                                                self.update_contents(context.active_folder());
-                                            } 
-                                        }
-                                    }
-                                }
-                            }   
-                        }
+    // Runs what a key of the keymap asked
+    fn run_action(&mut self, context: &mut AppContext, action: Action) {
+        match action {
+            Action::MoveUp if !self.edit => {
+                if self.get_hover() > &0 {
+                    self.set_hover(self.get_hover() - 1);
+                } else {
+                    self.set_hover(self.contents.len().saturating_sub(1));
+                }
+            },
+            Action::MoveDown if !self.edit => {
+                if self.get_hover() + 1 < self.contents.len() {
+                    self.set_hover(self.get_hover() + 1);
+                } else {
+                    self.set_hover(0);
+                }
+            },
+            Action::Open => {
+                if !self.edit {
+                    self.open_hover(context);
+                } else {
+                    self.edit = false;
+                    self.edit_extension = false;
+                }
+            },
+            Action::NewFile => {
+                self.add_content(context.active_folder(), String::from("new_file"), ContentType::FILE);
+            },
+            Action::NewFolder => {
+                self.add_content(context.active_folder(), String::from("new_folder"), ContentType::FOLDER);
+            },
+            Action::Search if !self.edit && !self.popup => {
+                self.search = Some(ProjectSearch::new());
+            },
+            Action::Rename if self.get_focus().is_none() && !self.edit => {
+                self.edit = true;
+                self.first_edit = true;
+            },
+            //eliminate folder or file which is hovered
+            Action::DeleteEntry if self.get_focus().is_none() => {
+                self.popup = true;
+            },
+            Action::Leave => {
+                context.set_focus(None);
+                context.set_hover(self.get_type());
+            },
+            _ => {}
+        }
+    }
 
-                    },
-                    KeyCode::Esc => {
-                        context.set_focus(None);
-                        context.set_hover(self.get_type());                   
+    // Left and Right choose whether to delete the hovered entry, Enter does what was chosen
+    fn handle_popup(&mut self, context: &mut AppContext, code: KeyCode) {
+        match code {
+            KeyCode::Left => {
+                self.popup_decision = true;
+            },
+            KeyCode::Right => {
+                self.popup_decision = false;
+            },
+            _ => {
+                if self.popup_decision {
+                    let hover = self.contents[self.get_hover().clone()].clone();
+                    if hover.is_file() {
+                        let _ = remove_file(hover);
+                    } else if hover.is_dir() {
+                        let _ = remove_dir_all(hover);
                     }
-                    _ => {}
                 }
+                self.popup = false;
+
+                self.update_contents(context.active_folder());
             }
-            else if key.kind == KeyEventKind::Repeat {
-                match key.code {
-                    KeyCode::Up => {
-                        if self.get_hover() > &0 {
-                            self.set_hover(self.get_hover() - 1);
-                        } else {
-                            self.set_hover(0);
-                        }
-                    },
-                    KeyCode::Down => {
-                        if self.get_hover() < &self.contents.len() {
-                            self.set_hover(self.get_hover() + 1);
+        }
+    }
+
+    // Adds a character to the name of the entry being renamed
+    fn type_name(&mut self, context: &mut AppContext, char: char) {
+        let Some(char) = char.to_lowercase().last() else {
+            return;
+        };
+        if self.edit {
+            if let Some(thing) = self.contents.get(self.get_hover().clone()) {
+                let path = thing.as_path();
+                if let Some(name) = thing.file_name() {
+                    if let Some(name_as_str) = name.to_str() {
+                        let from = name_as_str.to_string();
+                        let mut to = from.clone();
+
+                        if !self.edit_extension {
+                            //if is the first char the user digits the name goes resetted
+                            if self.first_edit {
+                                to.clear();
+                                self.first_edit = false;
+                            } else if char == '.' && path.is_file() {
+                                //if the user pressed . and the name is not empty we want to enter in extension mode to add an extension
+                                self.edit_extension = true;
+                            } 
+                            to.push(char);
+                            let _ = rename(from, to);
+
                         } else {
-                            self.set_hover(0);
+
+                            if let Some(extension) = path.extension() {
+                                if let Some(extension) = extension.to_str() {
+                                    let mut extension = extension.to_string();
+                                    extension.push(char);
+                                    let to_path = Path::new(&name_as_str.to_string()).with_extension(extension);
+                                    let _ = rename(from, to_path);
+                                }
+                            } else {
+                                let to_path = Path::new(&name_as_str.to_string()).with_extension(char.to_string());
+                                let _ = rename(from, to_path);
+                            }
                         }
-                    },
-                    _ => {}
+                        self.update_contents(context.active_folder());
+                    } 
                 }
             }
         }
     }
-}
 
-impl ProjectComponent {
     pub fn new(active_folder: PathBuf) -> Self {
         let mut contents: Vec<PathBuf> = vec![];
 
@@ -265,7 +342,10 @@ impl ProjectComponent {
             first_edit: false,
             edit_extension: false,
             popup: false,
-            popup_decision: true
+            popup_decision: true,
+            search: None,
+            offset: 0,
+            area: ScreenArea::default(),
         }
     }
 
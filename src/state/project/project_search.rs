@@ -0,0 +1,586 @@
+use std::{fs::{self, read_dir, FileType}, path::{Path, PathBuf}, cmp::min, ops::Range};
+
+use crossterm::event::KeyEvent;
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::state::{AppContext, ComponentType, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}, code::{CodeComponent, code_search::CodeSearch, code_format::CodeFormat, code_utils::{Point, write_atomic}}};
+
+use super::ProjectComponent;
+
+// A search stops after this many results, to keep the panel usable on big projects
+const MAX_RESULTS: usize = 5000;
+// Files with a NUL byte in their first bytes are considered binary
+const BINARY_CHECK_LEN: usize = 8000;
+
+// A match in a line of a file, column is a grapheme column like the cursor of the code and range the bytes of the line.
+// With a replacement it is a hunk of the replace preview, applied only while accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectMatch {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    text: String,
+    range: Range<usize>,
+    replacement: Option<String>,
+    accepted: bool,
+}
+
+impl ProjectMatch {
+
+    pub fn new(path: PathBuf, line: usize, column: usize, text: String, range: Range<usize>) -> ProjectMatch {
+        ProjectMatch {
+            path,
+            line,
+            column,
+            text,
+            range,
+            replacement: None,
+            accepted: true
+        }
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    // Whole text of the line, as it was when it was searched
+    pub fn get_text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn get_snippet(&self) -> &str {
+        self.text.trim()
+    }
+
+    pub fn get_range(&self) -> &Range<usize> {
+        &self.range
+    }
+
+    pub fn get_replacement(&self) -> &Option<String> {
+        &self.replacement
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.accepted
+    }
+
+    // Snippet of the line once the replacement is applied
+    pub fn get_preview(&self) -> Option<String> {
+        self.replacement.as_ref().map(|replacement| {
+            let mut text = self.text.clone();
+            text.replace_range(self.range.clone(), replacement);
+            text.trim().to_string()
+        })
+    }
+
+    // Text of the result in the list, as path:line: snippet with the path relative to the project
+    pub fn label(&self, root: &Path) -> String {
+        let path = self.path.strip_prefix(root).unwrap_or(&self.path);
+        format!("{}:{}: {}", path.display(), self.line + 1, self.get_snippet())
+    }
+}
+
+// State of the search panel of the project, the query uses the same options of the code search prompt
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSearch {
+    query: CodeSearch,
+    results: Vec<ProjectMatch>,
+    selected: usize,
+    // the results were found with the current query and options
+    searched: bool,
+    truncated: bool,
+    editing_replacement: bool,
+    // the accepted hunks have to be written, the app applies them since it owns the open code
+    apply_requested: bool,
+}
+
+impl ProjectSearch {
+
+    pub fn new() -> ProjectSearch {
+        ProjectSearch::default()
+    }
+
+    pub fn get_query(&self) -> &CodeSearch {
+        &self.query
+    }
+
+    pub fn get_results(&self) -> &Vec<ProjectMatch> {
+        &self.results
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn get_selected_result(&self) -> Option<&ProjectMatch> {
+        self.results.get(self.selected)
+    }
+
+    pub fn is_searched(&self) -> bool {
+        self.searched
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    // Searches the files under `root` again with the current query
+    pub fn update(&mut self, root: &Path) {
+        self.results.clear();
+        self.selected = 0;
+        self.truncated = false;
+        self.searched = true;
+        if self.query.get_query().is_empty() {
+            return;
+        }
+        if let Ok(regex) = self.query.build_regex() {
+            self.results = search_files(root, &regex);
+            self.truncated = self.results.len() >= MAX_RESULTS;
+        }
+        self.update_replacements();
+    }
+
+    // Text replacing every result, regex replacements can refer to capture groups as $1 or ${name}
+    pub fn update_replacements(&mut self) {
+        let regex = self.query.build_regex().ok();
+        for result in self.results.iter_mut() {
+            result.replacement = match (self.query.get_replacement(), &regex) {
+                (Some(replacement), Some(regex)) if self.query.is_regex() => {
+                    regex.captures_at(&result.text, result.range.start).map(|captures| {
+                        let mut expanded = String::new();
+                        captures.expand(replacement, &mut expanded);
+                        expanded
+                    })
+                },
+                (Some(replacement), _) => Some(replacement.clone()),
+                (None, _) => None,
+            };
+        }
+    }
+
+    pub fn is_replacing(&self) -> bool {
+        self.query.get_replacement().is_some()
+    }
+
+    pub fn is_editing_replacement(&self) -> bool {
+        self.editing_replacement && self.is_replacing()
+    }
+
+    // Shows the replacement field, or hides it with the preview of the hunks
+    pub fn toggle_replace(&mut self) {
+        if self.is_replacing() {
+            self.query.set_replacement(None);
+            self.editing_replacement = false;
+        } else {
+            self.query.set_replacement(Some(String::new()));
+            self.editing_replacement = !self.query.get_query().is_empty();
+        }
+        self.update_replacements();
+    }
+
+    // Accepts or rejects the selected hunk
+    pub fn toggle_selected(&mut self) {
+        if let Some(result) = self.results.get_mut(self.selected) {
+            result.accepted = !result.accepted;
+        }
+    }
+
+    // Accepts or rejects all the hunks of the file of the selected one, together
+    pub fn toggle_selected_file(&mut self) {
+        let Some(selected) = self.results.get(self.selected) else {
+            return;
+        };
+        let path = selected.path.clone();
+        let accepted = !self.results.iter().filter(|result| result.path == path).all(|result| result.accepted);
+        for result in self.results.iter_mut().filter(|result| result.path == path) {
+            result.accepted = accepted;
+        }
+    }
+
+    pub fn get_accepted(&self) -> Vec<&ProjectMatch> {
+        self.results.iter().filter(|result| result.accepted && result.replacement.is_some()).collect()
+    }
+
+    pub fn take_apply_request(&mut self) -> bool {
+        let requested = self.apply_requested;
+        self.apply_requested = false;
+        requested
+    }
+
+    fn edited(&mut self) {
+        self.searched = false;
+    }
+}
+
+// A pattern of a .gitignore file, matching paths under the folder of the file
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    base: PathBuf,
+    pattern: Regex,
+    negated: bool,
+    dir_only: bool,
+    // a pattern with a slash matches the path from the base, the others only the name
+    anchored: bool,
+}
+
+impl IgnoreRule {
+
+    fn parse(base: &Path, line: &str) -> Option<IgnoreRule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let pattern = Regex::new(&glob_to_regex(line)).ok()?;
+        Some(IgnoreRule {
+            base: base.to_path_buf(),
+            pattern,
+            negated,
+            dir_only,
+            anchored
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        if self.anchored {
+            let relative: Vec<String> = relative.components().map(|component| component.as_os_str().to_string_lossy().to_string()).collect();
+            self.pattern.is_match(&relative.join("/"))
+        } else {
+            path.file_name().is_some_and(|name| self.pattern.is_match(&name.to_string_lossy()))
+        }
+    }
+}
+
+// Converts a gitignore glob into a regex matching the whole path
+fn glob_to_regex(glob: &str) -> String {
+    let mut result = String::from("^");
+    let chars: Vec<char> = glob.chars().collect();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                if chars.get(index + 2) == Some(&'/') {
+                    result.push_str("(?:.*/)?");
+                    index += 1;
+                } else {
+                    result.push_str(".*");
+                }
+                index += 1;
+            },
+            '*' => result.push_str("[^/]*"),
+            '?' => result.push_str("[^/]"),
+            '[' => {
+                match chars[index..].iter().position(|char| *char == ']') {
+                    Some(end) if end > 1 => {
+                        let class: String = chars[index + 1..index + end].iter().collect();
+                        let class = class.strip_prefix('!').map(|class| format!("^{}", class)).unwrap_or(class);
+                        result.push_str(&format!("[{}]", class));
+                        index += end;
+                    },
+                    _ => result.push_str(r"\["),
+                }
+            },
+            char => result.push_str(&regex::escape(&char.to_string())),
+        }
+        index += 1;
+    }
+    result.push('$');
+    result
+}
+
+// Ignored state of a path given the rules of its folders, the last matching rule wins
+fn is_ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rule in rules {
+        if rule.matches(path, is_dir) {
+            ignored = !rule.negated;
+        }
+    }
+    ignored
+}
+
+// Text of a file, None for binary or unreadable files
+pub fn read_text(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if bytes[..min(bytes.len(), BINARY_CHECK_LEN)].contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Every match of `regex` in the text files under `root`, skipping the ignored ones
+pub fn search_files(root: &Path, regex: &Regex) -> Vec<ProjectMatch> {
+    let mut results = vec![];
+    for path in list_files(root) {
+        if let Some(text) = read_text(&path) {
+            let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+            for (number, line) in text.lines().enumerate() {
+                for found in regex.find_iter(line).filter(|found| !found.is_empty()) {
+                    let column = line[..found.start()].graphemes(true).count();
+                    results.push(ProjectMatch::new(path.clone(), number, column, line.to_string(), found.range()));
+                    if results.len() >= MAX_RESULTS {
+                        return results;
+                    }
+                }
+            }
+        }
+    }
+    results
+}
+
+// Files under `root` that are not ignored by git, sorted by path
+pub fn list_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    walk(root, &[], &mut files);
+    files
+}
+
+fn walk(folder: &Path, rules: &[IgnoreRule], files: &mut Vec<PathBuf>) {
+    let mut rules = rules.to_vec();
+    if let Ok(gitignore) = fs::read_to_string(folder.join(".gitignore")) {
+        rules.extend(gitignore.lines().filter_map(|line| IgnoreRule::parse(folder, line)));
+    }
+
+    let Ok(entries) = read_dir(folder) else {
+        return;
+    };
+    //the file type does not follow symlinks, a link to a parent folder would never end
+    let mut paths: Vec<(PathBuf, FileType)> = entries
+    .filter_map(|entry| entry.ok())
+    .filter_map(|entry| entry.file_type().ok().map(|file_type| (entry.path(), file_type)))
+    .collect();
+    paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (path, file_type) in paths {
+        let is_dir = file_type.is_dir();
+        if path.file_name().is_some_and(|name| name == ".git") || is_ignored(&rules, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk(&path, &rules, files);
+        } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+            files.push(path);
+        }
+    }
+}
+
+// Outcome of a project replace
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceReport {
+    replaced: usize,
+    files: usize,
+    // hunks whose line changed since the search, or whose file could not be written
+    skipped: usize,
+    error: Option<String>,
+}
+
+impl ReplaceReport {
+
+    pub fn get_replaced(&self) -> usize {
+        self.replaced
+    }
+
+    pub fn get_files(&self) -> usize {
+        self.files
+    }
+
+    pub fn get_skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn get_error(&self) -> &Option<String> {
+        &self.error
+    }
+
+    pub fn get_message(&self) -> String {
+        let mut message = format!("Replaced {} occurrences in {} files", self.replaced, self.files);
+        if self.skipped > 0 {
+            message.push_str(&format!(", {} skipped", self.skipped));
+        }
+        if let Some(error) = &self.error {
+            message.push_str(&format!(": {}", error));
+        }
+        message
+    }
+}
+
+// Applies the accepted hunks: the files open in a buffer are changed in memory as a single undo step,
+// the other files are written atomically keeping their line endings
+pub fn apply_replacements(search: &ProjectSearch, code_component: &mut CodeComponent) -> ReplaceReport {
+    let mut report = ReplaceReport::default();
+    let accepted = search.get_accepted();
+    let mut files: Vec<&PathBuf> = accepted.iter().map(|hunk| &hunk.path).collect();
+    files.dedup();
+
+    for path in files {
+        let hunks: Vec<&ProjectMatch> = accepted.iter().filter(|hunk| &hunk.path == path).copied().collect();
+        let applied = if let Some(index) = code_component.find_buffer(path) {
+            code_component.with_buffer(index, |code_component| replace_in_code(code_component, &hunks))
+        } else {
+            match replace_in_file(path, &hunks) {
+                Ok(applied) => applied,
+                Err(error) => {
+                    report.error = Some(format!("could not write {}: {}", path.display(), error));
+                    0
+                }
+            }
+        };
+        report.replaced += applied;
+        report.skipped += hunks.len() - applied;
+        if applied > 0 {
+            report.files += 1;
+        }
+    }
+    report
+}
+
+// The lines of the hunks still holding the text found by the search
+fn still_matching<'a>(hunks: &[&'a ProjectMatch], line: impl Fn(usize) -> Option<String>) -> Vec<&'a ProjectMatch> {
+    hunks
+    .iter()
+    .filter(|hunk| line(hunk.line).as_ref() == Some(&hunk.text))
+    .copied()
+    .collect()
+}
+
+fn replace_in_code(code_component: &mut CodeComponent, hunks: &[&ProjectMatch]) -> usize {
+    let code = code_component.get_current();
+    let hunks = still_matching(hunks, |number| code.get_line(number).map(|line| line.get_string()));
+    let replacements: Vec<(Range<usize>, String)> = hunks
+    .iter()
+    .map(|hunk| {
+        let start = code.line_to_char(hunk.line) + hunk.text[..hunk.range.start].chars().count();
+        let end = start + hunk.text[hunk.range.clone()].chars().count();
+        (start..end, hunk.replacement.clone().unwrap_or_default())
+    })
+    .collect();
+    code_component.replace(replacements);
+    hunks.len()
+}
+
+fn replace_in_file(path: &Path, hunks: &[&ProjectMatch]) -> std::io::Result<usize> {
+    let Some(text) = read_text(path) else {
+        return Ok(0);
+    };
+    let (format, text) = CodeFormat::detect(&text);
+    let mut lines: Vec<String> = text.split('\n').map(|line| line.to_string()).collect();
+    let mut hunks = still_matching(hunks, |number| lines.get(number).cloned());
+    //from the last one, so the ranges of the previous hunks on the same line stay valid
+    hunks.sort_by_key(|hunk| (hunk.line, hunk.range.start));
+    for hunk in hunks.iter().rev() {
+        lines[hunk.line].replace_range(hunk.range.clone(), hunk.replacement.as_deref().unwrap_or_default());
+    }
+    if !hunks.is_empty() {
+        write_atomic(path, format.apply(&lines.join("\n")).as_bytes())?;
+    }
+    Ok(hunks.len())
+}
+
+// Keys of the search panel, resolved in its keymap scope: keys without an action are typed in the field edited.
+// Confirming searches after the query changed and opens the selected result otherwise,
+// with the replacement shown hunks can be toggled one by one or by file and then applied
+pub fn handle_project_search(project: &mut ProjectComponent, context: &mut AppContext, key: KeyEvent) {
+    let Some(search) = project.search.as_mut() else {
+        return;
+    };
+    let editing_replacement = search.is_editing_replacement();
+    match context.resolve_key(KeyScope::ProjectSearch, key) {
+        KeyMatch::Action(Action::Leave) => {
+            project.search = None;
+        },
+        KeyMatch::Action(Action::Replace) => {
+            search.toggle_replace();
+        },
+        KeyMatch::Action(Action::SwitchField) if search.is_replacing() => {
+            search.editing_replacement = !search.editing_replacement;
+        },
+        KeyMatch::Action(Action::ToggleHunk) => {
+            search.toggle_selected();
+        },
+        KeyMatch::Action(Action::ToggleFileHunks) => {
+            search.toggle_selected_file();
+        },
+        KeyMatch::Action(Action::ReplaceAll) if search.is_replacing() && search.searched => {
+            search.apply_requested = true;
+        },
+        KeyMatch::Action(Action::DeleteBackward) if editing_replacement => {
+            let mut replacement = search.query.get_replacement().clone().unwrap_or_default();
+            replacement.pop();
+            search.query.set_replacement(Some(replacement));
+            search.update_replacements();
+        },
+        KeyMatch::Action(Action::ToggleCase) => {
+            search.query.toggle_case_sensitive();
+            search.edited();
+        },
+        KeyMatch::Action(Action::ToggleWholeWord) => {
+            search.query.toggle_whole_word();
+            search.edited();
+        },
+        KeyMatch::Action(Action::ToggleRegex) => {
+            search.query.toggle_regex();
+            search.edited();
+        },
+        KeyMatch::Action(Action::DeleteBackward) => {
+            let mut query = search.query.get_query().clone();
+            query.pop();
+            search.query.set_query(query);
+            search.edited();
+        },
+        KeyMatch::Action(Action::MoveUp) if !search.results.is_empty() => {
+            search.selected = (search.selected + search.results.len() - 1) % search.results.len();
+        },
+        KeyMatch::Action(Action::MoveDown) if !search.results.is_empty() => {
+            search.selected = (search.selected + 1) % search.results.len();
+        },
+        KeyMatch::Action(Action::Confirm) => {
+            if !search.searched {
+                search.update(context.active_folder());
+            } else if let Some(result) = search.get_selected_result() {
+                context.set_active_file(Some(result.get_path().clone()));
+                context.set_active_position(Some(Point::new(result.get_line(), result.get_column())));
+                context.set_focus(Some(ComponentType::Code));
+                context.set_hover(ComponentType::Code);
+            }
+        },
+        KeyMatch::Unbound => match typed_char(&key) {
+            Some(char) if editing_replacement => {
+                let mut replacement = search.query.get_replacement().clone().unwrap_or_default();
+                replacement.push(char);
+                search.query.set_replacement(Some(replacement));
+                search.update_replacements();
+            },
+            Some(char) => {
+                let mut query = search.query.get_query().clone();
+                query.push(char);
+                search.query.set_query(query);
+                search.edited();
+            },
+            None => {}
+        },
+        _ => {}
+    }
+}
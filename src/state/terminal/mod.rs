@@ -1,24 +1,30 @@
 use std::{error::Error, path::PathBuf, process::{Command, Stdio}, sync::Arc};
 
 use clipboard::{ClipboardContext, ClipboardProvider};
-use crossterm::event::{Event, KeyEventKind, KeyCode, KeyModifiers, ModifierKeyCode};
+use crossterm::event::{Event, KeyEventKind, MouseEventKind};
 use regex::Regex;
 
 
 use self::{terminal_command::TerminalCommand, terminal_history::{ExecutedTerminalHistory, ExecutedTerminalCommand}, terminal_selection::TerminalSelection};
 
-use super::{Component, ComponentType, AppContext};
+use super::{Component, ComponentType, AppContext, ScreenArea, keymap::{KeyMatch, KeyScope, typed_char, keymap_defaults::Action}};
 
 pub mod terminal_command;
 pub mod terminal_history;
 pub mod terminal_selection;
 
+// Lines scrolled by a step of the wheel
+const WHEEL_ROWS: usize = 3;
+
 // Terminal State
 #[derive(Debug, PartialEq, Eq)]
 pub struct TerminalComponent {
     current_command: Arc<TerminalCommand>,
     commands_history: Arc<ExecutedTerminalHistory>,
-    selection: Arc<TerminalSelection>
+    selection: Arc<TerminalSelection>,
+    // lines of the output scrolled up from the prompt, and where the output is drawn
+    scroll: usize,
+    area: ScreenArea,
 }
 
 impl TerminalComponent {
@@ -27,6 +33,8 @@ impl TerminalComponent {
             current_command: Arc::new(TerminalCommand::default()),
             commands_history: Arc::new(ExecutedTerminalHistory::default()),
             selection: Arc::new(TerminalSelection::new()),
+            scroll: 0,
+            area: ScreenArea::default(),
         }
     }
 
@@ -41,6 +49,31 @@ impl TerminalComponent {
     pub fn get_selection(&self) -> &TerminalSelection {
         &self.selection
     }
+
+    // Lines of the executed commands and their output, the oldest first
+    pub fn get_output_lines(&self) -> Vec<String> {
+        let output = self.commands_history.to_string();
+        if output.is_empty() {
+            return vec![];
+        }
+        output.lines().map(|line| line.to_string()).collect()
+    }
+
+    pub fn get_scroll(&self) -> usize {
+        self.scroll
+    }
+
+    pub fn set_area(&mut self, area: ScreenArea) {
+        self.area = area;
+    }
+
+    // The wheel scrolls the output, up to its first line at the top of the panel
+    fn scroll(&mut self, up: bool) {
+        //the prompt takes the last line of the panel
+        let lines = self.get_output_lines().len() + 1;
+        let max = lines.saturating_sub(self.area.get_height() as usize);
+        self.scroll = if up { (self.scroll + WHEEL_ROWS).min(max) } else { self.scroll.saturating_sub(WHEEL_ROWS) };
+    }
 }
 
 impl Component for TerminalComponent {
@@ -50,8 +83,24 @@ impl Component for TerminalComponent {
     }
 
     fn handle_event(&mut self, context: &mut AppContext, event: Event) {
+
+        if let Event::Mouse(mouse) = event {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll(true),
+                MouseEventKind::ScrollDown => self.scroll(false),
+                _ => {}
+            }
+        }
         
         if let Event::Key(key) = event {
+            //typing goes back to the prompt
+            if key.kind == KeyEventKind::Press {
+                self.scroll = 0;
+            }
+            if key.kind == KeyEventKind::Release {
+                return;
+            }
+            let key_match = context.resolve_key(KeyScope::Terminal, key);
             let command = &*Arc::clone(&self.current_command);
             //let history = &*Arc::clone(&self.commands_history);
             let selection = &*Arc::clone(&self.selection);
@@ -59,265 +108,175 @@ impl Component for TerminalComponent {
             let mutable_history = Arc::get_mut(&mut self.commands_history);
             let mutable_selection = Arc::get_mut(&mut self.selection);
 
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char(char) => {
-                        let mut char_normalized = char.clone().to_string();
-                        char_normalized = char_normalized.to_lowercase().to_string();
-                        if char_normalized == "x" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            let cut = selection.get_selection();
-                            let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
-                            if let Ok(mut context) =  clipboard {
-                                let _ = context.set_contents(cut);
-                            } 
-                        } else if char_normalized == "c" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            let copy = selection.get_selection();
-                            let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
-                            if let Ok(mut context) =  clipboard {
-                                let _ = context.set_contents(copy);
-                            } 
-                        } else if char_normalized == "v" && key.modifiers.contains(KeyModifiers::CONTROL) {
-                            let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
-                            if let Ok(mut context) =  clipboard {
-                                if let Ok(contents) = context.get_contents() {
-                                    if let Some(mutable_command) = mutable_command {
-                                        mutable_command.set_buffer(command.get_buffer().to_owned() + &contents);
-                                    }
-                                }
-                            }                  
-                        } else {
+            match key_match {
+                KeyMatch::Action(Action::Cut) => {
+                    let cut = selection.get_selection();
+                    let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
+                    if let Ok(mut context) =  clipboard {
+                        let _ = context.set_contents(cut);
+                    } 
+                },
+                KeyMatch::Action(Action::Copy) => {
+                    let copy = selection.get_selection();
+                    let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
+                    if let Ok(mut context) =  clipboard {
+                        let _ = context.set_contents(copy);
+                    } 
+                },
+                KeyMatch::Action(Action::Paste) => {
+                    let clipboard: Result<ClipboardContext, Box<dyn Error>> = ClipboardProvider::new();
+                    if let Ok(mut context) =  clipboard {
+                        if let Ok(contents) = context.get_contents() {
                             if let Some(mutable_command) = mutable_command {
-                                if let Some(mutable_selection) = mutable_selection {    
-                                    mutable_selection.clear_selection();
-                                    mutable_command.add(char);
-                                }
+                                mutable_command.set_buffer(command.get_buffer().to_owned() + &contents);
                             }
                         }
-                    },
-                    KeyCode::Up => {
+                    }                  
+                },
+                //keys without an action type their character
+                KeyMatch::Unbound => {
+                    if let Some(char) = typed_char(&key) {
                         if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_history) = mutable_history {
-                                if let Some(mutable_selection) = mutable_selection {
-                                    if let Some(previous_command) = mutable_history.up() {
-                                        mutable_selection.clear_selection();
-                                        mutable_command.set_buffer(command.get_buffer().to_owned() + previous_command.get_command());
-                                    }             
-                                }
+                            if let Some(mutable_selection) = mutable_selection {    
+                                mutable_selection.clear_selection();
+                                mutable_command.add(char);
                             }
                         }
-                    },
-                    KeyCode::Down => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_history) = mutable_history {
-                                if let Some(mutable_selection) = mutable_selection {
-                                    if let Some(previous_command) = mutable_history.up() {
-                                        mutable_selection.clear_selection();
-                                        mutable_command.set_buffer(command.get_buffer().to_owned() + previous_command.get_command());
-                                    }             
-                                }
+                    }
+                },
+                KeyMatch::Action(Action::PreviousCommand) => {
+                    if let Some(mutable_command) = mutable_command {
+                        if let Some(mutable_history) = mutable_history {
+                            if let Some(mutable_selection) = mutable_selection {
+                                if let Some(previous_command) = mutable_history.up() {
+                                    mutable_selection.clear_selection();
+                                    mutable_command.set_buffer(command.get_buffer().to_owned() + previous_command.get_command());
+                                }             
                             }
                         }
-                    },
-                    KeyCode::Left => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_selection) = mutable_selection {    
-                                mutable_selection.clear_selection();
-                                mutable_command.move_cursor_backward();
+                    }
+                },
+                KeyMatch::Action(Action::NextCommand) => {
+                    if let Some(mutable_command) = mutable_command {
+                        if let Some(mutable_history) = mutable_history {
+                            if let Some(mutable_selection) = mutable_selection {
+                                if let Some(newer_command) = mutable_history.down() {
+                                    mutable_selection.clear_selection();
+                                    mutable_command.set_buffer(command.get_buffer().to_owned() + newer_command.get_command());
+                                }             
                             }
                         }
-                    },
-                    KeyCode::Right => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_selection) = mutable_selection {    
-                                mutable_selection.clear_selection();
-                                mutable_command.move_cursor_forward();
-                            }
+                    }
+                },
+                KeyMatch::Action(Action::MoveLeft) => {
+                    if let Some(mutable_command) = mutable_command {
+                        if let Some(mutable_selection) = mutable_selection {    
+                            mutable_selection.clear_selection();
+                            mutable_command.move_cursor_backward();
                         }
-                    },
-                    KeyCode::Enter => {
-                                if let Some(mutable_selection) = mutable_selection {
-                                    if let Some(mutable_history) = mutable_history {
-                                        if let Some(mutable_command) = mutable_command {
-                                            mutable_selection.clear_selection();
-                                            let command_string: String = command.get_buffer().clone();
-                                            let re = Regex::new(r#""[^"]+"|\S+"#);
-                                            if let Ok(re) = re {
-        
-                                                let command_args: Vec<&str> = re.find_iter(command_string.as_str())
-                                                .map(|m| m.as_str())
-                                                .collect();
-                                                let mut command_output: String = String::from("");
-        
-                                                if command_string.len() > 0 {
-        
-                                                    if *command_args.get(0).unwrap() == "cd" && command_args.len() == 2 {
-                                                        let folder_to_access = *command_args.get(1).unwrap();
-                                                        let mut path: PathBuf = PathBuf::new();
-        
-                                                        path.push(context.active_folder());
-                                                        path.push(folder_to_access);
-                                            
-                                                        if path.is_dir() {
-                                                            context.set_active_folder(path);
-                                                        }
-                                                
-                                                    } else if *command_args.get(0).unwrap() == "cls" || *command_args.get(0).unwrap() == "clear" {    
-                                                                                        
-                                                        mutable_history.flush();
-                                                        mutable_command.flush();    
-        
-                                                    } else if command_args.len() > 0 { 
-                                            
-                                                        let output = if cfg!(target_os = "windows") {
-                                                            Command::new("powershell")
-                                                                    .args(&["-c", command_string.as_str().clone()])
-                                                                    .current_dir(context.active_folder().display().to_string())
-                                                                    .stdout(Stdio::piped())
-                                                                    .stderr(Stdio::piped())
-                                                                    .output()
-                                                                    .expect("failed to execute process")
-                                                        } else {
-                                                            Command::new("sh")
-                                                                    .arg("-c")
-                                                                    .arg(command_string.clone())
-                                                                    .current_dir(context.active_folder().display().to_string())
-                                                                    .stdout(Stdio::piped())
-                                                                    .stderr(Stdio::piped())
-                                                                    .output()
-                                                                    .expect("failed to execute process")
-                                                        };
+                    }
+                },
+                KeyMatch::Action(Action::MoveRight) => {
+                    if let Some(mutable_command) = mutable_command {
+                        if let Some(mutable_selection) = mutable_selection {    
+                            mutable_selection.clear_selection();
+                            mutable_command.move_cursor_forward();
+                        }
+                    }
+                },
+                KeyMatch::Action(Action::Run) => {
+                            if let Some(mutable_selection) = mutable_selection {
+                                if let Some(mutable_history) = mutable_history {
+                                    if let Some(mutable_command) = mutable_command {
+                                        mutable_selection.clear_selection();
+                                        let command_string: String = command.get_buffer().clone();
+                                        let re = Regex::new(r#""[^"]+"|\S+"#);
+                                        if let Ok(re) = re {
+    
+                                            let command_args: Vec<&str> = re.find_iter(command_string.as_str())
+                                            .map(|m| m.as_str())
+                                            .collect();
+                                            let mut command_output: String = String::from("");
+    
+                                            if command_string.len() > 0 {
+    
+                                                if *command_args.get(0).unwrap() == "cd" && command_args.len() == 2 {
+                                                    let folder_to_access = *command_args.get(1).unwrap();
+                                                    let mut path: PathBuf = PathBuf::new();
+    
+                                                    path.push(context.active_folder());
+                                                    path.push(folder_to_access);
+                                        
+                                                    if path.is_dir() {
+                                                        context.set_active_folder(path);
+                                                    }
                                             
-                                                        let mut is_error = false;
-                                                        if let Ok(error_string) = String::from_utf8(output.stderr) {
-                                                            if error_string.len() > 0 {
-                                                                is_error = true;
-                                                                command_output = error_string;
-                                                            }
+                                                } else if *command_args.get(0).unwrap() == "cls" || *command_args.get(0).unwrap() == "clear" {    
+                                                                                    
+                                                    mutable_history.flush();
+                                                    mutable_command.flush();    
+    
+                                                } else if command_args.len() > 0 { 
+                                        
+                                                    let output = if cfg!(target_os = "windows") {
+                                                        Command::new("powershell")
+                                                                .args(&["-c", command_string.as_str().clone()])
+                                                                .current_dir(context.active_folder().display().to_string())
+                                                                .stdout(Stdio::piped())
+                                                                .stderr(Stdio::piped())
+                                                                .output()
+                                                                .expect("failed to execute process")
+                                                    } else {
+                                                        Command::new("sh")
+                                                                .arg("-c")
+                                                                .arg(command_string.clone())
+                                                                .current_dir(context.active_folder().display().to_string())
+                                                                .stdout(Stdio::piped())
+                                                                .stderr(Stdio::piped())
+                                                                .output()
+                                                                .expect("failed to execute process")
+                                                    };
+                                        
+                                                    let mut is_error = false;
+                                                    if let Ok(error_string) = String::from_utf8(output.stderr) {
+                                                        if error_string.len() > 0 {
+                                                            is_error = true;
+                                                            command_output = error_string;
                                                         }
-                                                        if let Ok (output_string) = String::from_utf8(output.stdout) {
-                                                            if output_string.len() > 0 && !is_error {
-                                                                command_output = output_string;
-                                                            }
+                                                    }
+                                                    if let Ok (output_string) = String::from_utf8(output.stdout) {
+                                                        if output_string.len() > 0 && !is_error {
+                                                            command_output = output_string;
                                                         }
-                                                        
                                                     }
-        
+                                                    
                                                 }
-        
-                                                mutable_command.flush();
-                                                mutable_history.add(ExecutedTerminalCommand::new(command_string, context.active_folder().clone(), command_output.clone()));
+    
                                             }
-        
+    
+                                            mutable_command.flush();
+                                            mutable_history.add(ExecutedTerminalCommand::new(command_string, context.active_folder().clone(), command_output.clone()));
                                         }
+    
                                     }
-                        }
-                        
-                    },
-                    KeyCode::Delete => {
-                        if let Some(mutable_selection) = mutable_selection {
-                            if let Some(mutable_command) = mutable_command {
-                                if !selection.is_empty() {
-                                    mutable_selection.clear_selection();
-                                }    
-                                mutable_command.remove();    
-                            }
-                        }
-                    },
-                    KeyCode::Modifier(ModifierKeyCode::LeftShift) => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_selection) = mutable_selection {
-                                let current_command = command;
-                                let pos = command.get_position();
-
-                                if selection.is_empty() {
-                                    mutable_selection.start_selection(pos - 1, pos);
-                                } else {
-                                    let start_selection =  selection.get_start();
-                                    let end_selection = selection.get_end();
-                                    mutable_selection.set_command(current_command.clone());
-                                    mutable_selection.start_selection( start_selection- 1, end_selection);    
                                 }
-                                mutable_command.set_position(pos - 1);
-                            }
-                        }
-                    },
-                    KeyCode::Modifier(ModifierKeyCode::RightShift) => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_selection) = mutable_selection {
-                                let pos = command.get_position();
-
-                                if selection.is_empty() {
-                                    mutable_selection.start_selection(pos + 1, pos);
-                                } else {
-                                    let start_selection =  selection.get_start();
-                                    let end_selection = selection.get_end();
-                                    mutable_selection.set_command(command.clone());
-                                    mutable_selection.start_selection( start_selection+ 1, end_selection);    
-                                }
-                                mutable_command.set_position(pos + 1);                    
-                            }
-                        }
-                    },
-                    KeyCode::Esc => {
-                        context.set_focus(None);
-                        context.set_hover(self.get_type());                                              
-                    },
-                    _ => {}
-                }
-            } else if key.kind == KeyEventKind::Repeat {
-                match key.code {
-                    KeyCode::Char(char) => {
-                        if let Some(mutable_command) = mutable_command {
-                            mutable_command.add(char);
-                        }   
-                    },
-                    KeyCode::Up => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_history) = mutable_history {
-                                if let Some(previous_command) = mutable_history.up() {
-                                    mutable_command.set_buffer(command.get_buffer().to_owned() + previous_command.get_command());
-                                }         
-                            }
-                        }
-                    },
-                    KeyCode::Down => {
+                    }
+                    
+                },
+                KeyMatch::Action(Action::DeleteForward) => {
+                    if let Some(mutable_selection) = mutable_selection {
                         if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_history) = mutable_history {
-                                if let Some(newer_command) = mutable_history.down() {
-                                    mutable_command.set_buffer(command.get_buffer().to_owned() + newer_command.get_command());
-                                } 
-                            }
-                        }
-                    },
-                    KeyCode::Left => {
-                        if let Some(mutable_command) = mutable_command {
-                            let position = command.get_position();
-                            if position > 0 {
-                                mutable_command.set_position(position - 1);
-                            }    
-                        }
-                    },
-                    KeyCode::Right => {
-                        if let Some(mutable_command) = mutable_command {
-                            let position = command.get_position();
-                            if position > 0 {
-                                mutable_command.set_position(position + 1);
+                            if !selection.is_empty() {
+                                mutable_selection.clear_selection();
                             }    
+                            mutable_command.remove();    
                         }
-                    },
-                    KeyCode::Delete => {
-                        if let Some(mutable_command) = mutable_command {
-                            if let Some(mutable_selection) = mutable_selection {
-                                if !selection.is_empty() {
-                                    mutable_selection.clear_selection();
-                                }
-                                mutable_command.remove();
-                            }
-                        }
-                    },
-                    KeyCode::Modifier(ModifierKeyCode::LeftShift) => {
+                    }
+                },
+                KeyMatch::Action(Action::SelectLeft) => {
+                    if let Some(mutable_command) = mutable_command {
                         if let Some(mutable_selection) = mutable_selection {
-                            if let Some(mutable_command) = mutable_command {
+                            let current_command = command;
                             let pos = command.get_position();
 
                             if selection.is_empty() {
@@ -325,33 +284,36 @@ impl Component for TerminalComponent {
                             } else {
                                 let start_selection =  selection.get_start();
                                 let end_selection = selection.get_end();
-                                mutable_selection.set_command(command.clone());
+                                mutable_selection.set_command(current_command.clone());
                                 mutable_selection.start_selection( start_selection- 1, end_selection);    
                             }
                             mutable_command.set_position(pos - 1);
-                            }
                         }
-                    },
-                    KeyCode::Modifier(ModifierKeyCode::RightShift) => {
+                    }
+                },
+                KeyMatch::Action(Action::SelectRight) => {
+                    if let Some(mutable_command) = mutable_command {
                         if let Some(mutable_selection) = mutable_selection {
-                            if let Some(mutable_command) = mutable_command {
-                                let pos = command.get_position();
+                            let pos = command.get_position();
 
-                                if selection.is_empty() {
-                                    mutable_selection.start_selection(pos + 1, pos);
-                                } else {
-                                    let start_selection =  selection.get_start();
-                                    let end_selection = selection.get_end();
-                                    mutable_selection.set_command(command.clone());
-                                    mutable_selection.start_selection( start_selection+ 1, end_selection);    
-                                }
-                                mutable_command.set_position(pos + 1);
+                            if selection.is_empty() {
+                                mutable_selection.start_selection(pos + 1, pos);
+                            } else {
+                                let start_selection =  selection.get_start();
+                                let end_selection = selection.get_end();
+                                mutable_selection.set_command(command.clone());
+                                mutable_selection.start_selection( start_selection+ 1, end_selection);    
                             }
+                            mutable_command.set_position(pos + 1);                    
                         }
-                    },
-                    _ => {}
-                }
-            } 
+                    }
+                },
+                KeyMatch::Action(Action::Leave) => {
+                    context.set_focus(None);
+                    context.set_hover(self.get_type());                                              
+                },
+                _ => {}
+            }
         }
     }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::{error::Error, io::stdout};
 
-use crossterm::{terminal::{enable_raw_mode, EnterAlternateScreen, disable_raw_mode, LeaveAlternateScreen}, execute};
+use crossterm::{terminal::{enable_raw_mode, EnterAlternateScreen, disable_raw_mode, LeaveAlternateScreen}, event::{EnableMouseCapture, DisableMouseCapture}, execute};
 use ratatui::{prelude::CrosstermBackend, Terminal};
 
 use crate::state::{AppContext, App};
@@ -28,7 +28,7 @@ pub fn start(mut app: App, mut context: AppContext, debugging_events: bool) -> R
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend);
 //    let fps = 60;
@@ -36,28 +36,13 @@ pub fn start(mut app: App, mut context: AppContext, debugging_events: bool) -> R
     let event_system = EventSystem::new();
 
     if let Ok(mut terminal) = terminal {
-        let mut nframe = 0;
-
         loop {
-            {
-                let current_code = app.get_mut_code(); 
-                //blink the cursor on frame update
-                if context.active_file().is_some() {
-                    if nframe%30 == 0 {
-                        current_code.get_mut_current().remove_cursor();
-                    } else {
-                        current_code.get_mut_current().set_cursor();
-                    }
-                }
-            
-
-                //set the new file if the active file changed
-                if context.active_file_changed() {
-                    current_code.get_mut_current().flush();
-                    current_code.set_current(context.active_file().clone());
-                    context.set_active_file_changed(false);
-                }
+            //set the new file if the active file changed
+            app.open_active_file(&mut context);
 
+            //the viewports and the areas under the mouse follow the size of the terminal
+            if let Ok(size) = terminal.size() {
+                ui_system.update_areas(size, &mut app);
             }
 
             //update the ui on the upcoming frame (if debugging events do not display the ui)
@@ -73,15 +58,16 @@ pub fn start(mut app: App, mut context: AppContext, debugging_events: bool) -> R
                     break;
                 }
             }
-            
-            nframe += 1;
         }
 
+        app.get_code().store_histories(context.project_root());
+
         // restore terminal
         disable_raw_mode()?;
         execute!(
             terminal.backend_mut(),
-            LeaveAlternateScreen
+            LeaveAlternateScreen,
+            DisableMouseCapture
         )?;
         terminal.show_cursor()?;
     }
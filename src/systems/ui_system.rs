@@ -1,22 +1,30 @@
-use std::{error::Error, path::MAIN_SEPARATOR};
+use std::{error::Error, path::MAIN_SEPARATOR, time::{Instant, SystemTime}, cmp::{min, max}};
 
-use ratatui::{Terminal, prelude::{Backend, Rect, Alignment, Layout, Direction, Constraint}, Frame, style::{Style, Stylize, Styled}, widgets::{Paragraph, Block, BorderType, Borders, ListItem, List, ListState, Clear}, text::{Line, Span}};
+use ratatui::{Terminal, prelude::{Backend, Rect, Alignment, Layout, Direction, Constraint}, Frame, style::{Style, Stylize}, widgets::{Paragraph, Block, BorderType, Borders, ListItem, List, ListState, Clear, Tabs}, text::{Line, Span}};
 
-use crate::state::{App, AppContext, ComponentType, code::code_utils::Point};
+use crate::state::{App, AppContext, ComponentType, ConfirmChoice, ScreenArea, PendingAction, project::project_search::ProjectSearch, code::{code_utils::Point, code_format::LineEnding, code_highlight::TokenKind, code_indent::IndentStyle, code_viewport::display_graphemes, code_panes::{PaneLayout, SplitDirection}, code_brackets::get_bracket_match}, keymap::{KeyScope, keymap_defaults::Action}};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use super::System;
 
 // Define a generic UiSystem struct implementing the System trait.
 pub struct UiSystem {
-    // Add your fields here if needed.
+    // Instant used as reference to blink the cursor
+    started: Instant,
 }
 
 impl UiSystem {
     pub fn new() -> Self {
         UiSystem {
-            // Initialize your fields here if needed.
+            started: Instant::now(),
         }
     }
+
+    // The cursor is shown for half a second and hidden for the next half
+    fn is_cursor_visible(&self) -> bool {
+        self.started.elapsed().as_millis() % 1000 < 500
+    }
 }
 
 impl System for UiSystem {
@@ -38,11 +46,14 @@ impl UiSystem {
         let code_area = main_area[1];
         let terminal_area = main_area[2];
         
-        self.render_title(context, f, title_area);
+        self.render_title(app, context, f, title_area);
         self.render_project(app, context, f, project_area);
         self.render_code(app, context, f, code_area);
         self.render_terminal(app, context, f, terminal_area);
         self.render_popup(f, app);
+        self.render_history_popup(f, app, context);
+        self.render_buffer_picker(f, app);
+        self.render_confirm_popup(f, app);
     }
     
     fn render_project<B: Backend>(&self, app: &App, context: &AppContext, frame: &mut Frame<B>, project_area:Rect) {
@@ -75,6 +86,11 @@ impl UiSystem {
             block = block.border_style(style);
         }
 
+        if let Some(search) = app.get_project().get_search() {
+            self.render_project_search(search, context, frame, project_area, block.title("Search"));
+            return;
+        }
+
         // Create a List from all list items and highlight the currently selected one
         let list: List = List::new(items)
         .block(block)
@@ -84,12 +100,10 @@ impl UiSystem {
         
 
 
-        let offset = app.get_project().get_hover();
-        let mut list_state = ListState::default();
+        let hover = app.get_project().get_hover();
+        let mut list_state = ListState::default().with_offset(app.get_project().get_offset());
         if context_focus == Some(ComponentType::Project) {
-            list_state = list_state
-            .with_offset(0)
-            .with_selected(Some(offset.clone()));
+            list_state = list_state.with_selected(Some(*hover));
         }
 
 
@@ -99,19 +113,131 @@ impl UiSystem {
         
     }
 
+    /// Draws the query of the project search on the first lines of the panel and its results below it.
+    /// While replacing the results are grouped by file, each hunk shows the line before and after the replacement.
+    fn render_project_search<B: Backend>(&self, search: &ProjectSearch, context: &AppContext, frame: &mut Frame<B>, project_area: Rect, block: Block) {
+        let inner = block.inner(project_area);
+        frame.render_widget(block, project_area);
+
+        let query = search.get_query();
+        let toggle = |enabled: bool, label: &str| if enabled { format!("[{}]", label) } else { format!(" {} ", label) };
+        let editing = |field: bool| if field { ">" } else { " " };
+        let count = if !search.is_searched() {
+            "Enter to search".to_string()
+        } else if search.is_truncated() {
+            format!("{}+ results", search.get_results().len())
+        } else {
+            format!("{} results", search.get_results().len())
+        };
+        let mut prompt = vec![Line::from(format!("{}Find: {}  {}{}{}  {}", editing(!search.is_editing_replacement()), query.get_query(), toggle(query.is_case_sensitive(), "Aa"), toggle(query.is_whole_word(), "W"), toggle(query.is_regex(), ".*"), count))];
+        if let Some(replacement) = query.get_replacement() {
+            prompt.push(Line::from(format!("{}Replace: {}  {} accepted, {}/{} toggle, {} apply", editing(search.is_editing_replacement()), replacement, search.get_accepted().len(), key_hint(context, KeyScope::ProjectSearch, Action::ToggleHunk), key_hint(context, KeyScope::ProjectSearch, Action::ToggleFileHunks), key_hint(context, KeyScope::ProjectSearch, Action::ReplaceAll))));
+        }
+        let prompt_height = min(prompt.len() as u16, inner.height);
+        frame.render_widget(Paragraph::new(prompt).style(Style::new().white().on_blue()), Rect::new(inner.x, inner.y, inner.width, prompt_height));
+
+        let mut items: Vec<ListItem> = vec![];
+        let mut selected_row = 0;
+        let mut file = None;
+        for (index, result) in search.get_results().iter().enumerate() {
+            if search.is_replacing() {
+                if file != Some(result.get_path()) {
+                    file = Some(result.get_path());
+                    let path = result.get_path().strip_prefix(context.active_folder()).unwrap_or(result.get_path());
+                    items.push(ListItem::new(path.display().to_string()).style(Style::new().bold()));
+                }
+                if index == search.get_selected() {
+                    selected_row = items.len();
+                }
+                let mark = if result.is_accepted() { "[x]" } else { "[ ]" };
+                let mut lines = vec![Line::from(format!("{} {}: - {}", mark, result.get_line() + 1, result.get_snippet()))];
+                if let Some(preview) = result.get_preview() {
+                    lines.push(Line::from(format!("    {}: + {}", result.get_line() + 1, preview)));
+                }
+                items.push(ListItem::new(lines));
+            } else {
+                if index == search.get_selected() {
+                    selected_row = items.len();
+                }
+                items.push(ListItem::new(result.label(context.active_folder())));
+            }
+        }
+        let list = List::new(items).highlight_style(Style::default().white().on_blue().bold());
+        let mut list_state = ListState::default().with_selected(Some(selected_row));
+        let results_area = Rect::new(inner.x, inner.y + prompt_height, inner.width, inner.height - prompt_height);
+        frame.render_stateful_widget(list, results_area, &mut list_state);
+    }
+
+    /// Splits the code panel in the tab bar of the buffers on top and the areas of its panes.
+    fn layout_code(&self, code_area: Rect, layout: &PaneLayout) -> (Rect, Vec<(usize, Rect)>) {
+        let tabs_area = Rect::new(code_area.x + 1, code_area.y + 1, code_area.width.saturating_sub(2), code_area.height.saturating_sub(2).min(1));
+        let mut panes = vec![];
+        if let PaneLayout::Pane(pane) = layout {
+            panes.push((*pane, Rect::new(code_area.x, code_area.y + 1, code_area.width, code_area.height.saturating_sub(1))));
+        } else {
+            let area = Rect::new(code_area.x + 1, code_area.y + 2, code_area.width.saturating_sub(2), code_area.height.saturating_sub(3));
+            self.layout_panes(layout, area, &mut panes);
+        }
+        (tabs_area, panes)
+    }
+
+    /// Areas of the panes of a split, the first part takes the ratio of the split.
+    fn layout_panes(&self, layout: &PaneLayout, area: Rect, panes: &mut Vec<(usize, Rect)>) {
+        match layout {
+            PaneLayout::Pane(pane) => panes.push((*pane, area)),
+            PaneLayout::Split { direction, ratio, first, second } => {
+                let parts = Layout::default()
+                .direction(if *direction == SplitDirection::Horizontal { Direction::Horizontal } else { Direction::Vertical })
+                .constraints([Constraint::Percentage(*ratio), Constraint::Percentage(100 - *ratio)])
+                .split(area);
+                self.layout_panes(first, parts[0], panes);
+                self.layout_panes(second, parts[1], panes);
+            }
+        }
+    }
+
+    /// Splits a pane in the column of the line numbers and the one of the lines, inside its border when the panel is split.
+    fn layout_pane(&self, pane_area: Rect, bordered: bool) -> Vec<Rect> {
+        let area = if bordered { Block::new().borders(Borders::ALL).inner(pane_area) } else { self.layout_center(90, 80, pane_area) };
+        Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 10),Constraint::Ratio(9, 10)])
+        .split(area)
+        .to_vec()
+    }
+
+    /// Tells the components where they are drawn on a terminal of the given size, so that they can size their viewports
+    /// and find what is under the mouse: the panels, the frame and the lines of every code pane and the inside of the lists.
+    pub fn update_areas(&self, size: Rect, app: &mut App) {
+        let (_, main_area) = self.calculate_layout(size);
+        app.set_panel_areas(vec![
+            (ComponentType::Project, screen_area(main_area[0])),
+            (ComponentType::Code, screen_area(main_area[1])),
+            (ComponentType::Terminal, screen_area(main_area[2])),
+        ]);
+
+        let layout = app.get_code().get_panes().get_layout().clone();
+        let bordered = matches!(layout, PaneLayout::Split { .. });
+        for (pane, pane_area) in self.layout_code(main_area[1], &layout).1 {
+            let lines_area = self.layout_pane(pane_area, bordered)[1];
+            app.get_mut_code().set_pane_area(pane, screen_area(pane_area), screen_area(lines_area));
+        }
+
+        let inside = |area: Rect| Block::new().borders(Borders::ALL).inner(area);
+        app.get_mut_project().set_list_area(screen_area(inside(main_area[0])));
+        app.get_mut_terminal().set_area(screen_area(inside(main_area[2])));
+    }
+
     fn render_code<B: Backend>(&self, app: &App, context: &AppContext, frame: &mut Frame<B>, code_area: Rect) {
         //Prepare data to conditionally render different variants of the same ui
         let context_focus: Option<ComponentType> = context.focus().clone();
         let context_hover: ComponentType = context.hover().clone();
-        let area = self.layout_center(90, 80, code_area);
-        let layout_code = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Ratio(1, 10),Constraint::Ratio(9, 10)])
-        .split(area)
-        .to_vec();
+        let code = app.get_code();
+        let panes = code.get_panes();
+        let (tabs_area, pane_areas) = self.layout_code(code_area, panes.get_layout());
     
         let mut block = Block::new()
-        .title("Code")
+        .title(if code.get_viewport().is_wrapping() { "Code (wrap)" } else { "Code" })
         .borders(Borders::ALL)
         .border_type(BorderType::Plain);
 
@@ -121,181 +247,231 @@ impl UiSystem {
         } else if context_focus.is_none() && context_hover == ComponentType::Code {
             block = block.border_style(style);
         }
+        let is_focused = context_focus == Some(ComponentType::Code);
 
-        let selection_style = Style::new().white().on_blue().bold().italic();
-
-        let code = app
-        .get_code()
-        .get_current()
-        .get_content();
+        //Tabs of the open buffers, the modified ones are marked
+        let titles: Vec<Line> = code
+        .get_buffer_names()
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| Line::from(if code.is_buffer_modified(index) { format!("{} *", name) } else { name }))
+        .collect();
+        let tabs = Tabs::new(titles)
+        .select(code.get_active_buffer())
+        .highlight_style(Style::new().white().on_blue().bold());
 
-        //Retrieve if something is selected the starting and ending point of the selection
-        let selection = app.get_code().get_current().get_selection();
-        let is_selecting = selection.is_some();
-        let mut start_point: Point = Point::default();
-        let mut end_point: Point = Point::default();
-        if let Some(selection) = selection {
-            start_point = selection.get_start().clone();
-            end_point = selection.get_end().clone();
+        frame.render_widget(block, code_area);
+        frame.render_widget(tabs, tabs_area);
+
+        //Every pane draws its buffer, only the focused one with the cursor and the highlights
+        let bordered = pane_areas.len() > 1;
+        let text_style = if is_focused { style } else { Style::default() };
+        for (pane, pane_area) in pane_areas {
+            let pane_focused = pane == panes.get_focused();
+            if bordered {
+                let pane_block = Block::new()
+                .title(code.get_pane_name(pane))
+                .borders(Borders::ALL)
+                .border_type(if pane_focused { BorderType::Thick } else { BorderType::Plain });
+                frame.render_widget(pane_block, pane_area);
+            }
+            self.render_pane(frame, app, pane, text_style, is_focused && pane_focused, &self.layout_pane(pane_area, bordered));
         }
 
-        //Get the text style according to his status (selected or not)
-        let lines: Vec<ListItem> = code
-        .into_iter()
-        .map(move |line| {
-            let mut vec: Vec<Span<'_>> = vec![];
-            let line_number = line.get_number()-1;
-            let focus: Option<&ComponentType>;
-            if let Some(tmp) = context.focus() {
-                focus = Some(tmp);
+        if let Some(search) = app.get_code().get_search() {
+            let toggle = |enabled: bool, label: &str| if enabled { format!("[{}]", label) } else { format!(" {} ", label) };
+            let count = if let Some(error) = search.get_error() {
+                error.lines().last().unwrap_or_default().to_string()
+            } else if search.get_matches().is_empty() {
+                "no matches".to_string()
             } else {
-                focus = None;
+                format!("{}/{}", search.get_current().map(|current| current + 1).unwrap_or(0), search.get_matches().len())
+            };
+            let editing = |field: bool| if field { ">" } else { " " };
+            let mut bar = vec![Line::from(format!("{}Find: {}  {}{}{}  {}", editing(!search.is_editing_replacement()), search.get_query(), toggle(search.is_case_sensitive(), "Aa"), toggle(search.is_whole_word(), "W"), toggle(search.is_regex(), ".*"), count))];
+            if let Some(replacement) = search.get_replacement() {
+                bar.push(Line::from(format!("{}Replace: {}  {}  {} replace, {} all", editing(search.is_editing_replacement()), replacement, toggle(search.get_scope().is_some(), "in selection"), key_hint(context, KeyScope::CodeSearch, Action::Confirm), key_hint(context, KeyScope::CodeSearch, Action::ReplaceAll))));
             }
+            let height = bar.len() as u16;
+            let bar_area = Rect::new(code_area.x + 1, code_area.y + code_area.height.saturating_sub(height + 1), code_area.width.saturating_sub(2), height);
+            frame.render_widget(Clear, bar_area);
+            frame.render_widget(Paragraph::new(bar).style(Style::new().white().on_blue()), bar_area);
+        }
 
-            if focus != Some(&ComponentType::Code) || context.focus().is_none() {
-                //not focused code
-                vec.push(Span::from(line.get_string()));
-            } else if focus == Some(&ComponentType::Code) && is_selecting && start_point.get_x() < end_point.get_x() {
-                //selection multiple lines left to right
-                if line_number >= start_point.get_x() && line_number <= end_point.get_x() {
-                    //current line is inside the selection or first or last line of the selection
-                    if line_number == start_point.get_x() {
-                        //first line
-                        let not_styled = Span::from(line.get_string()[..start_point.get_y()].to_string());
-                        let styled = Span::from(line.get_string()[start_point.get_y()..].to_string());
-                        vec.push(not_styled.set_style(style));
-                        vec.push(styled.set_style(selection_style));
-                    } else if line_number == end_point.get_x() {
-                        //last line
-                        let styled = Span::from(line.get_string()[..end_point.get_y()].to_string());
-                        let not_styled = Span::from(line.get_string()[end_point.get_y()..].to_string());
-                        vec.push(styled.set_style(selection_style));
-                        vec.push(not_styled.set_style(style));
-                    } else {
-                        //line inside selection
-                        let styled = Span::from(line.get_string());
-                        vec.push(styled.set_style(selection_style));
-                    }
-                } else {
-                    let not_styled = Span::from(line.get_string());
-                    vec.push(not_styled.set_style(style));
+
+
+
+    }
+
+    /// Draws the rows of a pane inside its viewport, with the cursors and the highlights when it is focused.
+    fn render_pane<B: Backend>(&self, frame: &mut Frame<B>, app: &App, pane: usize, text_style: Style, is_focused: bool, layout_pane: &[Rect]) {
+        let selection_style = Style::new().white().on_blue().bold().italic();
+        let match_style = Style::new().black().on_yellow();
+        let bracket_style = Style::new().black().on_light_cyan();
+        let cursor_style = Style::new().reversed();
+        let code = app.get_code();
+        let (current, highlighter, viewport) = code.get_pane_view(pane);
+        let cursors = current.get_all_cursors();
+        let cursor_visible = is_focused && self.is_cursor_visible();
+        let tab_width = code.get_tab_width();
+
+        //Retrieve the first and last point of the search matches, of the matching brackets and of the selection of every cursor,
+        //the later ones are drawn over the previous
+        let mut highlights: Vec<(Point, Point, Style)> = vec![];
+        if let Some(search) = code.get_search() {
+            for found in search.get_matches() {
+                highlights.push((current.char_to_point(found.start), current.char_to_point(found.end), match_style));
+            }
+        }
+        if let Some((bracket, other)) = get_bracket_match(&current) {
+            for char_idx in [bracket, other] {
+                highlights.push((current.char_to_point(char_idx), current.char_to_point(char_idx + 1), bracket_style));
+            }
+        }
+        for selection in cursors.iter().filter_map(|cursor| cursor.get_selection().as_ref()) {
+            highlights.push((min(selection.get_start(), selection.get_end()).clone(), max(selection.get_start(), selection.get_end()).clone(), selection_style));
+        }
+
+        //Draw the rows of the lines inside the viewport, a wrapped line takes more rows marked in the numbers column
+        let mut lines: Vec<Line> = vec![];
+        let mut numbers: Vec<Line> = vec![];
+        let mut line_number = viewport.get_top();
+        let mut skipped_rows = viewport.get_top_row();
+        while lines.len() < viewport.get_height() && line_number < current.len_lines() {
+            let line = current.get_line(line_number).unwrap_or_default();
+
+            //columns of the line covered by the syntax tokens, the last one excluded
+            let mut ranges: Vec<(usize, usize, Style)> = highlighter
+            .get_tokens(line_number)
+            .iter()
+            .map(|token| (line.column(token.get_range().start), line.column(token.get_range().end), self.token_style(token.get_kind())))
+            .filter(|(first, last, _)| first < last)
+            .collect();
+
+            if is_focused {
+                //and by the highlights, drawn over the tokens
+                ranges.extend(highlights
+                .iter()
+                .filter(|(start_point, end_point, _)| line_number >= start_point.get_x() && line_number <= end_point.get_x())
+                .map(|(start_point, end_point, highlight)| {
+                    let first = if line_number == start_point.get_x() { start_point.get_y() } else { 0 };
+                    let last = if line_number == end_point.get_x() { end_point.get_y() } else { line.len() };
+                    (first, last, *highlight)
+                })
+                .filter(|(first, last, _)| first < last));
+            }
+
+            let graphemes = display_graphemes(&line.get_string(), tab_width);
+            let rows = viewport.rows(&graphemes);
+            let last_row = rows.len() - 1;
+            for (row, columns) in rows.into_iter().enumerate().skip(skipped_rows) {
+                if lines.len() == viewport.get_height() {
+                    break;
                 }
-            } else if focus == Some(&ComponentType::Code) && is_selecting && start_point.get_x() > end_point.get_x() {
-                //selection multiple lines right to left
-                if line_number <= start_point.get_x() && line_number >= end_point.get_x() && start_point.get_x() != end_point.get_x() {
-                    //current line is inside the selection or first or last line of the selection
-                    if line_number == end_point.get_x() {
-                        //first line
-                        let not_styled = Span::from(line.get_string()[..end_point.get_y()].to_string());
-                        vec.push(not_styled.set_style(style));
-                        let styled = Span::from(line.get_string()[end_point.get_y()..].to_string());
-                        vec.push(styled.set_style(selection_style));
-                    } else if line_number == start_point.get_x() {
-                        //last line
-                        let styled = Span::from(line.get_string()[..start_point.get_y()].to_string());
-                        let not_styled = Span::from(line.get_string()[start_point.get_y()..].to_string());
-                        vec.push(styled.set_style(selection_style));
-                        vec.push(not_styled.set_style(style));
-                    } else {
-                        //line inside selection
-                        let styled = Span::from(line.get_string());
-                        vec.push(styled.set_style(selection_style));
-                    }
-                } else if start_point.get_x() == end_point.get_x() {
-                    if start_point.get_y() > end_point.get_y() {
-                        if end_point.get_y() != 0 {
-                            let not_styled = Span::from(line.get_string()[..end_point.get_y()].to_string());
-                            vec.push(not_styled.set_style(style));
-                        }
-                        let styled = Span::from(line.get_string()[end_point.get_y()..start_point.get_y()].to_string());
-                        vec.push(styled.set_style(selection_style));
-                        if start_point.get_y() != line.get_string().len() - 1 {
-                            let not_styled = Span::from(line.get_string()[start_point.get_y()..].to_string());
-                            vec.push(not_styled.set_style(style));
-                        }
-                    } else if start_point.get_y() < end_point.get_y() {
-                        if start_point.get_y() != 0 {
-                            let not_styled = Span::from(line.get_string()[..start_point.get_y()].to_string());
-                            vec.push(not_styled.set_style(style));
-                        }
-                        let styled = Span::from(line.get_string()[start_point.get_y()..end_point.get_y()].to_string());
-                        vec.push(styled.set_style(selection_style));
-                        if end_point.get_y() != line.get_string().len() - 1 {
-                            let not_styled = Span::from(line.get_string()[end_point.get_y()..].to_string());
-                            vec.push(not_styled.set_style(style));
+                let row_ranges: Vec<(usize, usize, Style)> = ranges
+                .iter()
+                .map(|(first, last, style)| ((*first).max(columns.start) - columns.start, (*last).min(columns.end).saturating_sub(columns.start), *style))
+                .filter(|(first, last, _)| first < last)
+                .collect();
+                let mut vec = self.styled_spans(&graphemes[columns.clone()], &row_ranges, text_style);
+
+                if cursor_visible {
+                    for cursor in cursors.iter().filter(|cursor| cursor.get_position().get_x() == line_number) {
+                        let column = cursor.get_position().get_y();
+                        //the end of the line is drawn on its last row
+                        if columns.contains(&column) || (column == columns.end && row == last_row) {
+                            let byte_index = graphemes[columns.start..column].iter().map(|grapheme| grapheme.len()).sum();
+                            vec = self.overlay_cursor(vec, byte_index, cursor_style);
                         }
-                    } else if start_point.get_y() == end_point.get_y() {
-                        let not_styled = Span::from(line.get_string());
-                        vec.push(not_styled.set_style(style));
                     }
-                    let not_styled = Span::from(line.get_string()[start_point.get_y()..].to_string());
-                    let styled = Span::from(line.get_string()[..start_point.get_y()].to_string());
-                    vec.push(styled.set_style(selection_style));
-                    vec.push(not_styled.set_style(style));
-                } else {
-                    //lines out of the selection
-                    let not_styled = Span::from(line.get_string());
-                    vec.push(not_styled.set_style(style));
                 }
-            } else if focus == Some(&ComponentType::Code) && is_selecting && start_point.get_x() == end_point.get_x() && line_number == start_point.get_x() {
-                //selection on a single line
-                if (start_point.get_y() == 0 && end_point.get_y() == line.get_string().len() - 1) || (end_point.get_y() == 0 && start_point.get_y() == line.get_string().len() - 1) {
-                    let styled = Span::from(line.get_string());
-                    vec.push(styled.set_style(selection_style));
-                } else if start_point.get_y() > end_point.get_y() {
-                    //selection right to left
-                    let not_styled_first = Span::from(line.get_string()[..end_point.get_y()].to_string());
-                    let styled = Span::from(line.get_string()[end_point.get_y()..start_point.get_y()].to_string());
-                    let not_styled_last = Span::from(line.get_string()[start_point.get_y()..].to_string());
-                    vec.push(not_styled_first.set_style(style));
-                    vec.push(styled.set_style(selection_style));
-                    vec.push(not_styled_last.set_style(style));
-                } else if start_point.get_y() < end_point.get_y() {
-                    //selection left to right
-                    let not_styled_first = Span::from(line.get_string()[..start_point.get_y()].to_string());
-                    let styled = Span::from(line.get_string()[start_point.get_y()..end_point.get_y()].to_string());
-                    let not_styled_last = Span::from(line.get_string()[end_point.get_y()..].to_string());
-                    vec.push(not_styled_first.set_style(style));
-                    vec.push(styled.set_style(selection_style));
-                    vec.push(not_styled_last.set_style(style));
-                } else if start_point.get_y() == end_point.get_y() {
-                    //selection empty
-                    let not_styled = Span::from(line.get_string());
-                    vec.push(not_styled.set_style(style));    
-                }
-            } else if focus == Some(&ComponentType::Code) {
-                //focused with selection empty
-                let not_styled = Span::from(line.get_string());
-                vec.push(not_styled.set_style(style));
-            }
-
-            ListItem::new(Line::from(vec))
-        })
-        .collect();
-        let numbers: Vec<ListItem>= code
-        .into_iter()
-        .map(|line| {
-            ListItem::new((line.get_number()).to_string())
-        })
-        .collect();
-
 
-        let list_lines = List::new(lines);
+                lines.push(Line::from(vec));
+                numbers.push(Line::from(if row == 0 { (line_number + 1).to_string() } else { "↪".to_string() }));
+            }
+            skipped_rows = 0;
+            line_number += 1;
+        }
 
-        let list_numbers = List::new(numbers);
 
-        let mut state = ListState::default().with_selected(Some(app.get_code().get_current().get_cursor().get_x()+1));
-        
-        frame.render_widget(block, code_area);
-        frame.render_stateful_widget(list_numbers, layout_code.get(0).unwrap().clone(), &mut state);
-        frame.render_stateful_widget(list_lines, layout_code.get(1).unwrap().clone(), &mut state);
+        frame.render_widget(Paragraph::new(numbers), layout_pane[0]);
+        frame.render_widget(Paragraph::new(lines), layout_pane[1]);
+    }
 
+    /// Color of a kind of syntax token, only the foreground so selections and matches stay visible over it.
+    fn token_style(&self, kind: TokenKind) -> Style {
+        match kind {
+            TokenKind::Keyword => Style::new().magenta(),
+            TokenKind::Type => Style::new().cyan(),
+            TokenKind::Function => Style::new().light_blue(),
+            TokenKind::String => Style::new().green(),
+            TokenKind::Number | TokenKind::Constant => Style::new().red(),
+            TokenKind::Comment => Style::new().dark_gray(),
+            TokenKind::Property | TokenKind::Variable => Style::new().light_red(),
+            TokenKind::Attribute => Style::new().yellow(),
+            TokenKind::Heading => Style::new().magenta().bold(),
+            TokenKind::Emphasis => Style::new().italic(),
+            TokenKind::Link => Style::new().blue().underlined(),
+        }
+    }
 
+    /// Builds the spans of a line, every grapheme takes the `base` style patched by the ranges of columns covering it.
+    fn styled_spans<'a>(&self, graphemes: &[String], ranges: &[(usize, usize, Style)], base: Style) -> Vec<Span<'a>> {
+        let mut spans: Vec<Span<'a>> = vec![];
+        let mut content = String::new();
+        let mut current = base;
+        for (column, grapheme) in graphemes.iter().enumerate() {
+            let style = ranges
+            .iter()
+            .filter(|(first, last, _)| column >= *first && column < *last)
+            .fold(base, |style, (_, _, patch)| style.patch(*patch));
+            if style != current && !content.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut content), current));
+            }
+            current = style;
+            content.push_str(grapheme);
+        }
+        if !content.is_empty() || spans.is_empty() {
+            spans.push(Span::styled(content, current));
+        }
+        spans
+    }
 
+    /// Splits the spans of a line to draw the cursor over the grapheme at `column` (a byte offset of the line).
+    ///
+    /// When the cursor is past the end of the line an empty cell is drawn instead.
+    fn overlay_cursor<'a>(&self, spans: Vec<Span<'a>>, column: usize, cursor_style: Style) -> Vec<Span<'a>> {
+        let mut result: Vec<Span<'a>> = vec![];
+        let mut offset = 0;
+        let mut drawn = false;
+
+        for span in spans {
+            let content = span.content.to_string();
+            if !drawn && column >= offset && column < offset + content.len() {
+                let start = column - offset;
+                let end = start + content[start..].graphemes(true).next().map(|grapheme| grapheme.len()).unwrap_or(0);
+                if start > 0 {
+                    result.push(Span::styled(content[..start].to_string(), span.style));
+                }
+                result.push(Span::styled(content[start..end].to_string(), span.style.patch(cursor_style)));
+                if end < content.len() {
+                    result.push(Span::styled(content[end..].to_string(), span.style));
+                }
+                drawn = true;
+            } else {
+                result.push(span);
+            }
+            offset += content.len();
+        }
 
+        if !drawn {
+            result.push(Span::styled(" ", cursor_style));
+        }
+        result
     }
 
-    fn render_terminal<B: Backend>(&self,_app: &App, context: &AppContext, frame: &mut Frame<B>, terminal_area: Rect) {
+    /// Draws the output of the executed commands with the prompt on the last line, scrolled up by the wheel.
+    fn render_terminal<B: Backend>(&self, app: &App, context: &AppContext, frame: &mut Frame<B>, terminal_area: Rect) {
         let context_focus: Option<ComponentType> = context.focus().clone();
         let context_hover: ComponentType = context.hover().clone();
 
@@ -311,7 +487,14 @@ impl UiSystem {
             block = block.border_style(style);
         }
 
-        frame.render_widget(block, terminal_area);
+        let terminal = app.get_terminal();
+        let mut lines = terminal.get_output_lines();
+        lines.push(format!("{}> {}", context.active_folder().display(), terminal.get_current_command().get_buffer()));
+        let height = block.inner(terminal_area).height as usize;
+        let end = lines.len().saturating_sub(terminal.get_scroll());
+        let visible: Vec<Line> = lines[end.saturating_sub(height)..end].iter().map(|line| Line::from(line.clone())).collect();
+
+        frame.render_widget(Paragraph::new(visible).block(block), terminal_area);
     }
     /// Calculate the layout of the UI elements.
     ///
@@ -338,7 +521,7 @@ impl UiSystem {
         (title_area, vec![project_area,code_area,terminal_area])
     }
     
-    fn render_title<B: Backend>(&self, context: &AppContext, frame: &mut Frame<B>, area: Rect) {
+    fn render_title<B: Backend>(&self, app: &App, context: &AppContext, frame: &mut Frame<B>, area: Rect) {
         let mut title = "NOCE".to_string();
         let active_file = context.active_file();
         if let Some(active) = active_file {
@@ -346,6 +529,27 @@ impl UiSystem {
                 title = title + " - " + name.to_str().unwrap();
             }
         }
+        if app.get_code().is_modified() {
+            title += " *";
+        }
+        //the format is shown only when it is not the usual one
+        let format = app.get_code().get_format();
+        let mut details = vec![];
+        if format.get_line_ending() != LineEnding::Lf {
+            details.push(format.get_line_ending().get_name().to_string());
+        }
+        if format.has_bom() {
+            details.push("BOM".to_string());
+        }
+        if app.get_code().get_indent() != IndentStyle::default() {
+            details.push(app.get_code().get_indent().get_name());
+        }
+        if !details.is_empty() {
+            title = title + " (" + details.join(", ").as_str() + ")";
+        }
+        if let Some(message) = context.message() {
+            title = title + " | " + message;
+        }
         frame.render_widget(
             Paragraph::new(title.as_str())
                 .dark_gray()
@@ -390,6 +594,140 @@ impl UiSystem {
         }
     }
     
+    /// Asks what to do with the unsaved changes before quitting or closing a buffer.
+    fn render_confirm_popup<B: Backend>(&self, f: &mut Frame<B>, app: &App) {
+        if app.get_pending().is_none() {
+            return;
+        }
+        let popup_size = self.layout_center(60, 30, f.size());
+        let popup_content = self.layout_center(99, 80, popup_size);
+        let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Ratio(7, 10), Constraint::Ratio(3, 10)])
+        .split(popup_content)
+        .to_vec();
+        let buttons_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(1, 3), Constraint::Ratio(1, 3)])
+        .split(popup_layout[1])
+        .to_vec();
+
+        let block = Block::default().title("Unsaved changes").title_alignment(Alignment::Center).style(Style::new().blue().on_white().bold().italic()).borders(Borders::ALL);
+        let text = match app.get_pending() {
+            Some(PendingAction::Quit) => {
+                let names: Vec<String> = app.get_code().get_buffer_names().into_iter().enumerate().filter(|(index, _)| app.get_code().is_buffer_modified(*index)).map(|(_, name)| name).collect();
+                format!("{} {} unsaved changes.\nSave them before quitting?", names.join(", "), if names.len() == 1 { "has" } else { "have" })
+            },
+            _ => {
+                let name = app.get_code().get_path().as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy().to_string()).unwrap_or("The code".to_string());
+                format!("{} has unsaved changes.\nSave them before closing it?", name)
+            }
+        };
+        let paragraph = Paragraph::new(text).alignment(Alignment::Center);
+        let selected_button_style = Style::new().white().on_blue().bold().italic();
+
+        f.render_widget(Clear, popup_size); //this clears out the background
+        f.render_widget(block, popup_size);
+        f.render_widget(paragraph, popup_layout[0]);
+        let buttons = [(ConfirmChoice::Save, "Save"), (ConfirmChoice::Discard, "Discard"), (ConfirmChoice::Cancel, "Cancel")];
+        for (index, (choice, label)) in buttons.iter().enumerate() {
+            let mut button = Paragraph::new(*label).alignment(Alignment::Center);
+            if app.get_confirm_choice() == *choice {
+                button = button.style(selected_button_style);
+            }
+            f.render_widget(button, buttons_layout[index]);
+        }
+    }
+
+    /// Lists the open buffers whose name contains the query typed, Enter shows the selected one.
+    fn render_buffer_picker<B: Backend>(&self, f: &mut Frame<B>, app: &App) {
+        let Some(picker) = app.get_code().get_buffer_picker() else {
+            return;
+        };
+        let names = app.get_code().get_buffer_names();
+        let popup_size = self.layout_center(50, 50, f.size());
+        let block = Block::default().title("Open buffers").title_alignment(Alignment::Center).style(Style::new().blue().on_white().bold()).borders(Borders::ALL);
+        let inner = block.inner(popup_size);
+        let query = Paragraph::new(format!("> {}", picker.get_query()));
+        let items: Vec<ListItem> = picker
+        .matches(&names)
+        .into_iter()
+        .map(|index| ListItem::new(if app.get_code().is_buffer_modified(index) { format!("{} *", names[index]) } else { names[index].clone() }))
+        .collect();
+        let list = List::new(items).highlight_style(Style::default().white().on_blue().bold());
+        let mut list_state = ListState::default().with_selected(Some(picker.get_selected()));
+
+        f.render_widget(Clear, popup_size); //this clears out the background
+        f.render_widget(block, popup_size);
+        f.render_widget(query, Rect::new(inner.x, inner.y, inner.width, inner.height.min(1)));
+        f.render_stateful_widget(list, Rect::new(inner.x, inner.y + 1, inner.width, inner.height.saturating_sub(1)), &mut list_state);
+    }
+
+    /// Draws the undo tree of the code on the left and a preview of the selected state on the right.
+    fn render_history_popup<B: Backend>(&self, f: &mut Frame<B>, app: &App, context: &AppContext) {
+        let Some(popup) = app.get_code().get_history_popup() else {
+            return;
+        };
+        let history = app.get_code().get_history();
+        let popup_size = self.layout_center(80, 60, f.size());
+        let popup_content = self.layout_center(98, 90, popup_size);
+        let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(popup_content)
+        .to_vec();
+        let tree_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+        .split(popup_layout[0])
+        .to_vec();
+
+        let now = SystemTime::now();
+        let rows = history.get_tree_rows();
+        let items: Vec<ListItem> = rows
+        .iter()
+        .map(|(id, indent)| {
+            let node = history.get_node(*id).unwrap();
+            let marker = if *id == history.get_current() { "@" } else { "o" };
+            let seconds = now.duration_since(node.get_timestamp()).unwrap_or_default().as_secs();
+            let age = if seconds < 60 {
+                format!("{}s", seconds)
+            } else if seconds < 60 * 60 {
+                format!("{}m", seconds / 60)
+            } else {
+                format!("{}h", seconds / (60 * 60))
+            };
+            let description = match node.get_change() {
+                Some(change) => format!("{} ago {}", age, change.summary()),
+                None => "original".to_string(),
+            };
+            ListItem::new(format!("{}{} {} {}", "| ".repeat(*indent), marker, id, description))
+        })
+        .collect();
+        let selected = rows.iter().position(|(id, _)| *id == popup.get_selected());
+        let list = List::new(items)
+        .block(Block::default().borders(Borders::RIGHT))
+        .highlight_style(Style::default().white().on_blue().bold());
+        let mut list_state = ListState::default().with_selected(selected);
+
+        let preview = history.get_code_at(popup.get_selected()).get_text();
+        let preview = Paragraph::new(preview);
+
+        let help = if popup.get_input().is_empty() {
+            let key = |action: Action| key_hint(context, KeyScope::HistoryPopup, action);
+            format!("{}/{} preview, {} restore, type 30s/5m/1h + {} to go back in time, {} close", key(Action::MoveUp), key(Action::MoveDown), key(Action::Confirm), key(Action::Confirm), key(Action::Leave))
+        } else {
+            format!("Go back: {}", popup.get_input())
+        };
+
+        let block = Block::default().title("Undo tree").title_alignment(Alignment::Center).style(Style::new().blue().on_white()).borders(Borders::ALL);
+        f.render_widget(Clear, popup_size);
+        f.render_widget(block, popup_size);
+        f.render_stateful_widget(list, tree_layout[0], &mut list_state);
+        f.render_widget(preview, tree_layout[1]);
+        f.render_widget(Paragraph::new(help).italic(), popup_layout[1]);
+    }
+
     /// helper function to create a centered rect using up certain percentage of the available rect `r`
     fn layout_center(&self, percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         let popup_layout = Layout::default()
@@ -410,4 +748,17 @@ impl UiSystem {
             ])
             .split(popup_layout[1])[1]
     }
-}
\ No newline at end of file
+}
+
+// Area of the state matching a rect of the terminal
+fn screen_area(rect: Rect) -> ScreenArea {
+    ScreenArea::new(rect.x, rect.y, rect.width, rect.height)
+}
+
+// First key of an action written like in the keymap file, for the hints of the prompts
+fn key_hint(context: &AppContext, scope: KeyScope, action: Action) -> String {
+    match context.keymap().keys_of(scope, action).first() {
+        Some(keys) => keys.iter().map(|key| key.to_string()).collect::<Vec<String>>().join(" "),
+        None => "unbound".to_string(),
+    }
+}
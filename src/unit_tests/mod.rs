@@ -1,5 +1,28 @@
 #[cfg(test)]
 mod unit_tests {
+    use crossterm::event::{Event, KeyEvent, KeyCode, KeyModifiers};
+
+    use crate::state::{AppContext, Component, code::{CodeComponent, code_utils::Point}};
+
+    // A code component holding a text, with the cursor at a point of it
+    pub fn component(text: &str, cursor: Point) -> CodeComponent {
+        let mut code_component = CodeComponent::new();
+        code_component.get_mut_current().set_text(text);
+        *code_component.get_mut_current().get_mut_cursor() = cursor;
+        code_component
+    }
+
+    // Presses a key on a component, as the event system passes it
+    pub fn press(component: &mut impl Component, context: &mut AppContext, code: KeyCode, modifiers: KeyModifiers) {
+        component.handle_event(context, Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    // Types the chars of a text one key at a time
+    pub fn type_text(component: &mut impl Component, context: &mut AppContext, text: &str) {
+        for char in text.chars() {
+            press(component, context, KeyCode::Char(char), KeyModifiers::empty());
+        }
+    }
 
     pub mod contructors_tests {
         use crate::state::{AppContext, App, ComponentType, project::ProjectComponent, code::{CodeComponent, code::{Line, Code}, code_utils::Point, code_selection::CodeSelection}, terminal::{TerminalComponent, terminal_history::ExecutedTerminalCommand}};
@@ -27,8 +50,8 @@ mod unit_tests {
         #[test]
         fn test_new_code() {
             let code = Code::new();
-            assert_eq!(code.get_content().len(), 0);
-            assert_eq!(code.is_cursor_displayed(), false);
+            //an empty buffer still has its first, empty, line
+            assert_eq!(code.get_content(), vec![Line::new(1, String::new())]);
             assert_eq!(code.get_cursor().clone(), Point::default());
             assert_eq!(code.get_selection().clone(), None);
         }
@@ -37,8 +60,8 @@ mod unit_tests {
         // Ensure the code component is created correctly
         fn test_new_code_component() {
             let code_component = CodeComponent::new();
-            assert_eq!(code_component.get_current().get_content().len(), 0);
-            assert_eq!(code_component.get_history().get_current_code().get_content().len(), 0);
+            assert_eq!(code_component.get_current().get_content().len(), 1);
+            assert_eq!(code_component.get_history().get_current_code().get_content().len(), 1);
         }
 
         // Define a test for the construction of the ProjectComponent struct
@@ -118,179 +141,2031 @@ mod unit_tests {
                     assert_eq!(point.get_x(), 3);
                     assert_eq!(point.get_y(), 0);
                 }
-    
+    
+                #[test]
+                fn test_move_left() {
+                    let mut point = Point::new(3, 4);
+                    point.move_left(false, 5); // Not exceeding the limit
+                    assert_eq!(point.get_x(), 3);
+                    assert_eq!(point.get_y(), 3);
+    
+                    let mut point = Point::new(1, 0);
+                    point.move_left(true, 5); // Exceeding the limit
+                    assert_eq!(point.get_x(), 0);
+                    assert_eq!(point.get_y(), 4);
+                }
+    
+                #[test]
+                fn test_move_right() {
+                    let mut point = Point::new(3, 4);
+                    point.move_right(false, 5); // Not exceeding the limit
+                    assert_eq!(point.get_x(), 3);
+                    assert_eq!(point.get_y(), 5);
+    
+                    let mut point = Point::new(4, 4);
+                    point.move_right(true, 4); // Exceeding the limit
+                    assert_eq!(point.get_x(), 5);
+                    assert_eq!(point.get_y(), 0);
+                }
+    
+                #[test]
+                fn test_move_down() {
+                    let mut point = Point::new(3, 4);
+                    point.move_down(false, 5, 4); // Not exceeding the limit
+                    assert_eq!(point.get_x(), 4);
+                    assert_eq!(point.get_y(), 4);
+    
+                    let mut point = Point::new(3, 5);
+                    point.move_down(true, 5, 4); // Exceeding the limit
+                    assert_eq!(point.get_x(), 3);
+                    assert_eq!(point.get_y(), 5);
+                }
+    
+                #[test]
+                fn test_set_x_and_set_y() {
+                    let mut point = Point::new(3, 4);
+                    point.set_x(5);
+                    point.set_y(6);
+                    assert_eq!(point.get_x(), 5);
+                    assert_eq!(point.get_y(), 6);
+                }
+    
+            }
+    
+            pub mod content_tests {
+                use crate::state::code::{code::{Code, Line}, code_utils::Point};
+
+                #[test]
+                fn test_lines_are_numbered_by_position() {
+                    let code = Code::from("first\nsecond\nthird");
+                    assert_eq!(code.len_lines(), 3);
+                    assert_eq!(code.get_line(1), Some(Line::new(2, "second".to_string())));
+                    assert_eq!(code.get_line(3), None);
+                }
+
+                #[test]
+                fn test_insert_and_remove_renumber_lines() {
+                    let mut code = Code::from("first\nsecond\nthird");
+                    code.insert(code.line_to_char(1), "new\n");
+                    assert_eq!(code.get_line(2), Some(Line::new(3, "second".to_string())));
+
+                    code.remove(0..code.line_to_char(2));
+                    assert_eq!(code.get_text(), "second\nthird");
+                    assert_eq!(code.get_line(0), Some(Line::new(1, "second".to_string())));
+                }
+
+                #[test]
+                fn test_byte_ranges() {
+                    let mut code = Code::from("héllo");
+                    code.remove_bytes(1..3);
+                    assert_eq!(code.get_text(), "hllo");
+                    code.insert_at_byte(1, "e");
+                    assert_eq!(code.get_text(), "hello");
+                }
+
+                #[test]
+                fn test_point_conversions() {
+                    let code = Code::from("ab\ncd");
+                    assert_eq!(code.point_to_char(&Point::new(1, 1)), 4);
+                    assert_eq!(code.char_to_point(4), Point::new(1, 1));
+                    //points past the end of a line are clamped to it
+                    assert_eq!(code.point_to_char(&Point::new(0, 10)), 2);
+                }
+
+                #[test]
+                fn test_remove_line() {
+                    let mut code = Code::from("first\nsecond\nthird");
+                    code.remove_line(2);
+                    assert_eq!(code.get_text(), "first\nsecond");
+                    code.remove_line(0);
+                    assert_eq!(code.get_text(), "second");
+                }
+
+            }
+
+            pub mod unicode_tests {
+                use crossterm::event::{Event, KeyEvent, KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{code::Line, code_utils::{Point, handle_char, handle_delete, handle_enter, handle_left, handle_right, handle_down}}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                #[test]
+                fn test_line_columns_are_graphemes() {
+                    let line = Line::new(1, "e\u{301}👍🏽漢x".to_string());
+                    assert_eq!(line.len(), 4);
+                    assert_eq!(line.byte_index(1), 3);
+                    assert_eq!(line.byte_index(2), 11);
+                    assert_eq!(line.column(11), 2);
+
+                    //the CJK char takes two cells on screen
+                    let line = Line::new(1, "e\u{301}漢x".to_string());
+                    assert_eq!(line.width(3), 4);
+                    assert_eq!(line.column_at_width(2), 1);
+                    assert_eq!(line.column_at_width(3), 2);
+                }
+
+                #[test]
+                fn test_typing_and_deleting_non_ascii() {
+                    let mut code_component = component("àb", Point::default());
+                    let right = Event::Key(KeyEvent::new(KeyCode::Right, KeyModifiers::empty()));
+                    handle_right(&mut code_component, right);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 1));
+
+                    handle_char(&mut code_component, "漢".to_string());
+                    assert_eq!(code_component.get_current().get_text(), "à漢b");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 2));
+
+                    handle_delete(&mut code_component);
+                    handle_delete(&mut code_component);
+                    assert_eq!(code_component.get_current().get_text(), "b");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+                }
+
+                #[test]
+                fn test_combining_char_joins_previous_grapheme() {
+                    let mut code_component = component("", Point::default());
+                    handle_char(&mut code_component, "e".to_string());
+                    handle_char(&mut code_component, "\u{301}".to_string());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 1));
+                    handle_delete(&mut code_component);
+                    assert_eq!(code_component.get_current().get_text(), "");
+                }
+
+                #[test]
+                fn test_enter_and_moves_over_emoji() {
+                    let mut code_component = component("👍🏽👍🏽", Point::default());
+                    let right = Event::Key(KeyEvent::new(KeyCode::Right, KeyModifiers::empty()));
+                    let left = Event::Key(KeyEvent::new(KeyCode::Left, KeyModifiers::empty()));
+                    handle_right(&mut code_component, right);
+                    handle_enter(&mut code_component);
+                    assert_eq!(code_component.get_current().get_text(), "👍🏽\n👍🏽");
+                    handle_left(&mut code_component, left);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 1));
+                }
+
+                #[test]
+                fn test_vertical_moves_keep_screen_column() {
+                    let mut code_component = component("漢字x\nabcdef", Point::default());
+                    let down = Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::empty()));
+                    code_component.get_mut_current().get_mut_cursor().set_y(2);
+                    handle_down(&mut code_component, down);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 4));
+                }
+
+                #[test]
+                fn test_moves_stay_inside_the_buffer() {
+                    let mut context = AppContext::default();
+                    //selecting up onto an empty line
+                    let mut code_component = component("\nabc", Point::new(1, 2));
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::SHIFT);
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+
+                    //selecting left from the start of the buffer, then collapsing the selection
+                    let mut code_component = component("abc", Point::default());
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::SHIFT);
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+
+                    //selecting right and down from the end of the buffer
+                    let mut code_component = component("abc", Point::new(0, 3));
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::SHIFT);
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 3));
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 3));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::SHIFT);
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 3));
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 3));
+                    for code in [KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down] {
+                        for modifiers in [KeyModifiers::empty(), KeyModifiers::SHIFT] {
+                            for _ in 0..3 {
+                                press(&mut code_component, &mut context, code, modifiers);
+                                let cursor = code_component.get_current().get_cursor().clone();
+                                assert_eq!(cursor.get_x(), 0);
+                                assert!(cursor.get_y() <= 3);
+                            }
+                        }
+                    }
+                }
+
+            }
+
+            pub mod selection_tests {
+                use crate::state::code::{code_selection::CodeSelection, code_utils::Point};
+    
+    
+                #[test]
+                fn test_set_start_and_set_end() {
+                    let mut selection = CodeSelection::new(Point::new(1, 2), Point::new(3, 4));
+            
+                    let new_start = Point::new(5, 6);
+                    let new_end = Point::new(7, 8);
+            
+                    selection.set_start(new_start.clone());
+                    selection.set_end(new_end.clone());
+            
+                    assert_eq!(selection.get_start(), &new_start);
+                    assert_eq!(selection.get_end(), &new_end);
+                }
+    
+            }
+    
+            pub mod change_tests {
+    
+                use crate::state::code::{code_history::Operation, code::Code};
+    
+                #[test]
+                fn test_invert_operation() {
+                    let insert = Operation::Insert { at: 2, text: "New Text".to_string() };
+                    assert_eq!(insert.invert(), Operation::Delete { at: 2, text: "New Text".to_string() });
+                    assert_eq!(insert.invert().invert(), insert);
+                }
+            
+                #[test]
+                fn test_apply_operation() {
+                    let mut code = Code::from("Old Text");
+                    Operation::Delete { at: 0, text: "Old".to_string() }.apply(&mut code);
+                    Operation::Insert { at: 0, text: "New".to_string() }.apply(&mut code);
+                    assert_eq!(code.get_text(), "New Text");
+                }
+
+                #[test]
+                fn test_code_records_operations() {
+                    let mut code = Code::from("line");
+                    code.insert(4, "\nnext");
+                    code.remove(0..5);
+                    assert_eq!(code.take_operations(), vec![
+                        Operation::Insert { at: 4, text: "\nnext".to_string() },
+                        Operation::Delete { at: 0, text: "line\n".to_string() },
+                    ]);
+                    assert!(code.take_operations().is_empty());
+                }
+    
+            }
+    
+            pub mod history_tests {
+                use std::{time::Duration, path::Path};
+
+                use tempfile::TempDir;
+
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code::Code, code_history::{CodeHistory, Change, Operation, parse_duration, history_cache_path, content_hash}, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{press, type_text};
+
+                #[test]
+                fn test_new_code_history() {
+                    let code = Code::new();
+                    let history = CodeHistory::new(code.clone());
+    
+                    assert_eq!(history.get_current_code(), code);
+                    assert_eq!(history.get_version(), 0);
+                }
+    
+                #[test]
+                fn test_add_change() {
+                    let mut code = Code::from("Old Text");
+                    let mut code_history = CodeHistory::new(code.clone());
+                    code.insert(0, "New ");
+                    let change = Change::new(code.take_operations(), Point::new(0, 0), None, Point::new(0, 4), None);
+                    code_history.add_change(change.clone(), &code);
+    
+                    assert_eq!(code_history.get_changes(), vec![change]);
+                    assert_eq!(code_history.get_current_code().get_text(), "New Old Text");
+                    assert_eq!(code_history.get_code_at(0).get_text(), "Old Text");
+                }
+
+                #[test]
+                fn test_typing_is_undone_by_words() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "hello world");
+                    assert_eq!(code_component.get_history().get_changes().len(), 2);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "hello ");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 6));
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+
+                    press(&mut code_component, &mut context, KeyCode::Char('y'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Char('y'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "hello world");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 11));
+                }
+
+                #[test]
+                fn test_moving_the_cursor_breaks_the_group() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "ab");
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::empty());
+                    type_text(&mut code_component, &mut context, "c");
+                    assert_eq!(code_component.get_current().get_text(), "acb");
+
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "ab");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 1));
+                }
+
+                #[test]
+                fn test_undo_line_split_and_join() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("firstsecond");
+                    code_component.get_mut_current().get_mut_cursor().set_y(5);
+
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "first\nsecond");
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "firstsecond");
+
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "first\nsecond");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "firstsecond");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 5));
+                }
+
+                #[test]
+                fn test_undo_restores_selection() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("remove me");
+                    code_component.get_mut_current().create_selection(Point::new(0, 0), Point::new(0, 7));
+
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "me");
+                    assert!(code_component.get_current().get_selection().is_none());
+
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "remove me");
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("remove ".to_string()));
+                }
+
+                #[test]
+                fn test_new_change_keeps_redo_branch() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "one ");
+                    type_text(&mut code_component, &mut context, "two");
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    type_text(&mut code_component, &mut context, "three");
+                    assert_eq!(code_component.get_history().get_changes().len(), 2);
+                    press(&mut code_component, &mut context, KeyCode::Char('y'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "one three");
+
+                    //the undone change is still reachable switching branch
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::ALT);
+                    press(&mut code_component, &mut context, KeyCode::Char('y'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "one two");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 7));
+                }
+
+                #[test]
+                fn test_jump_between_branches() {
+                    let mut code = Code::new();
+                    let mut code_history = CodeHistory::new(code.clone());
+                    let commit = |code: &mut Code, code_history: &mut CodeHistory, text: &str| {
+                        code.insert(code.len_chars(), text);
+                        let operations = code.take_operations();
+                        code_history.close_group();
+                        code_history.add_change(Change::new(operations, Point::default(), None, Point::default(), None), code);
+                    };
+                    commit(&mut code, &mut code_history, "a");
+                    commit(&mut code, &mut code_history, "b");
+                    code_history.undo(&mut code);
+                    commit(&mut code, &mut code_history, "c");
+                    commit(&mut code, &mut code_history, "d");
+                    assert_eq!(code.get_text(), "acd");
+                    assert_eq!(code_history.get_tree_rows(), vec![(0, 0), (1, 0), (2, 0), (3, 1), (4, 1)]);
+
+                    assert!(code_history.jump_to(2, &mut code));
+                    assert_eq!(code.get_text(), "ab");
+                    assert_eq!(code_history.get_current(), 2);
+                    assert!(code_history.jump_to(4, &mut code));
+                    assert_eq!(code.get_text(), "acd");
+                    assert_eq!(code_history.get_code_at(2).get_text(), "ab");
+                    assert!(!code_history.jump_to(10, &mut code));
+                }
+
+                #[test]
+                fn test_jump_to_time() {
+                    let mut code = Code::new();
+                    let mut code_history = CodeHistory::new(code.clone());
+                    for text in ["a", "b", "c"] {
+                        code.insert(code.len_chars(), text);
+                        let operations = code.take_operations();
+                        code_history.close_group();
+                        code_history.add_change(Change::new(operations, Point::default(), None, Point::default(), None), &code);
+                    }
+                    let time = code_history.get_node(1).unwrap().get_timestamp();
+                    code_history.jump_to_time(time, &mut code);
+                    assert_eq!(code.get_text(), "a");
+                    code_history.jump_to_time(time - Duration::from_secs(60), &mut code);
+                    assert_eq!(code.get_text(), "");
+                }
+
+                #[test]
+                fn test_parse_duration() {
+                    assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+                    assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+                    assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+                    assert_eq!(parse_duration("15"), Some(Duration::from_secs(15)));
+                    assert_eq!(parse_duration("5x"), None);
+                    assert_eq!(parse_duration("m"), None);
+                    assert_eq!(parse_duration("9999999999999999h"), None);
+                    assert_eq!(parse_duration("307445734561825861m"), None);
+                }
+
+                #[test]
+                fn test_history_popup_restores_selected_state() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "one two");
+                    press(&mut code_component, &mut context, KeyCode::Char('t'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_history_popup().as_ref().unwrap().get_selected(), 2);
+
+                    //typing while the popup is open does not edit the code
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "one two");
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert!(code_component.get_history_popup().is_none());
+                    assert_eq!(code_component.get_current().get_text(), "one ");
+
+                    press(&mut code_component, &mut context, KeyCode::Char('t'), KeyModifiers::CONTROL);
+                    type_text(&mut code_component, &mut context, "0s");
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "one two");
+                }
+
+                #[test]
+                fn test_store_and_restore_history() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = history_cache_path(temp_dir.path(), Path::new("/project"), Path::new("/project/main.rs"));
+                    assert!(path.starts_with(temp_dir.path()));
+                    assert_eq!(path.extension().unwrap(), "json");
+
+                    let mut code = Code::from("fn main() {}");
+                    let mut code_history = CodeHistory::new(code.clone());
+                    for i in 0..40 {
+                        code.insert(code.len_chars(), "\n");
+                        let operations = code.take_operations();
+                        code_history.close_group();
+                        code_history.add_change(Change::new(operations, Point::new(i, 0), None, Point::new(i + 1, 0), None), &code);
+                    }
+                    code_history.mark_saved();
+                    code_history.store(&path).unwrap();
+
+                    let mut restored = CodeHistory::restore(&path, &code).unwrap();
+                    assert_eq!(restored.get_version(), 40);
+                    assert_eq!(restored.get_code_at(32).get_text(), code_history.get_code_at(32).get_text());
+                    assert!(restored.undo(&mut code));
+                    assert_eq!(code.get_cursor().clone(), Point::new(39, 0));
+                    while restored.undo(&mut code) {}
+                    assert_eq!(code.get_text(), "fn main() {}");
+                }
+
+                #[test]
+                fn test_restore_needs_same_content() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("undo").join("history.json");
+                    let mut code = Code::from("first");
+                    let mut code_history = CodeHistory::new(code.clone());
+                    code.insert(5, " edit");
+                    code_history.add_change(Change::new(code.take_operations(), Point::new(0, 5), None, Point::new(0, 10), None), &code);
+
+                    //unsaved changes are kept as a redo of the text on disk
+                    code_history.store(&path).unwrap();
+                    let mut on_disk = Code::from("first");
+                    let mut restored = CodeHistory::restore(&path, &on_disk).unwrap();
+                    assert!(restored.redo(&mut on_disk));
+                    assert_eq!(on_disk.get_text(), "first edit");
+
+                    assert!(CodeHistory::restore(&path, &Code::from("changed elsewhere")).is_none());
+                    assert!(CodeHistory::restore(&temp_dir.path().join("missing.json"), &Code::from("first")).is_none());
+                    assert_eq!(content_hash("first"), content_hash("first"));
+                    assert_ne!(content_hash("first"), content_hash("First"));
+                }
+
+                #[test]
+                fn test_store_after_typing_past_the_save() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("history.json");
+                    let mut code = Code::from("x");
+                    let mut code_history = CodeHistory::new(code.clone());
+                    for (i, text) in ["a", "b"].iter().enumerate() {
+                        code.insert(1 + i, text);
+                        code_history.add_change(Change::new(code.take_operations(), Point::new(0, 1 + i), None, Point::new(0, 2 + i), None), &code);
+                    }
+                    code_history.mark_saved();
+
+                    //typing within the group timeout does not change the saved text
+                    code.insert(3, "c");
+                    code_history.add_change(Change::new(code.take_operations(), Point::new(0, 3), None, Point::new(0, 4), None), &code);
+                    assert!(!code_history.is_saved());
+                    assert_eq!(code_history.get_code_at(code_history.get_saved()).get_text(), "xab");
+
+                    code_history.store(&path).unwrap();
+                    let mut on_disk = Code::from("xab");
+                    let mut restored = CodeHistory::restore(&path, &on_disk).unwrap();
+                    assert!(restored.redo(&mut on_disk));
+                    assert_eq!(on_disk.get_text(), "xabc");
+                }
+
+                #[test]
+                fn test_checkpoints_rebuild_every_version() {
+                    let mut code = Code::new();
+                    let mut code_history = CodeHistory::new(code.clone());
+                    for i in 0..100 {
+                        code.insert(code.len_chars(), "\n");
+                        let operations = code.take_operations();
+                        code_history.close_group();
+                        code_history.add_change(Change::new(operations, Point::new(i, 0), None, Point::new(i + 1, 0), None), &code);
+                    }
+                    assert_eq!(code_history.get_version(), 100);
+                    assert_eq!(code_history.get_current_code().get_text(), code.get_text());
+                    assert_eq!(code_history.get_code_at(70).len_lines(), 71);
+
+                    let mut undone = code.clone();
+                    while code_history.undo(&mut undone) {}
+                    assert_eq!(undone.get_text(), "");
+                    assert!(code_history.get_changes().is_empty());
+                    let first = code_history.get_node(1).unwrap().get_change().clone().unwrap();
+                    assert_eq!(first.get_operations(), &vec![Operation::Insert { at: 0, text: "\n".to_string() }]);
+                }
+    
+            }
+
+            pub mod cursor_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code_cursor::CodeCursor, code_utils::{Point, paste_text}}};
+                use crate::unit_tests::unit_tests::{press, type_text};
+
+                #[test]
+                fn test_add_cursors_above_and_below() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("one\ntwo\nthree");
+                    code_component.get_mut_current().get_mut_cursor().set_x(1);
+                    code_component.get_mut_current().get_mut_cursor().set_y(2);
+
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::CONTROL | KeyModifiers::ALT);
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::CONTROL | KeyModifiers::ALT);
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::CONTROL | KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_cursors(), &vec![
+                        CodeCursor::new(Point::new(0, 2), None),
+                        CodeCursor::new(Point::new(2, 2), None),
+                    ]);
+
+                    type_text(&mut code_component, &mut context, "_");
+                    assert_eq!(code_component.get_current().get_text(), "on_e\ntw_o\nth_ree");
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "on_\ntw_\nth_ee");
+
+                    //all the cursors are undone in one step
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "on_e\ntw_o\nth_ree");
+                    assert!(code_component.get_current().get_cursors().is_empty());
+                }
+
+                #[test]
+                fn test_cursors_on_the_same_line() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("a b c");
+                    code_component.get_mut_current().add_cursor(CodeCursor::new(Point::new(0, 2), None));
+                    code_component.get_mut_current().add_cursor(CodeCursor::new(Point::new(0, 4), None));
+
+                    type_text(&mut code_component, &mut context, "-");
+                    assert_eq!(code_component.get_current().get_text(), "-a -b -c");
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "-\na -\nb -\nc");
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 0));
+                    assert_eq!(code_component.get_current().get_cursors().len(), 2);
+
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "a b c");
+
+                    //cursors meeting in the same place are merged
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursors(), &vec![CodeCursor::new(Point::new(0, 2), None)]);
+
+                    code_component.get_mut_current().add_cursor(CodeCursor::new(Point::new(0, 2), None));
+                    press(&mut code_component, &mut context, KeyCode::Esc, KeyModifiers::empty());
+                    assert!(code_component.get_current().get_cursors().is_empty());
+                }
+
+                #[test]
+                fn test_add_next_occurrence() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("let foo = foo + foo_bar;\nfoo");
+                    code_component.get_mut_current().get_mut_cursor().set_y(5);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('d'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("foo".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Char('d'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Char('d'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursors().len(), 2);
+
+                    type_text(&mut code_component, &mut context, "x");
+                    assert_eq!(code_component.get_current().get_text(), "let x = x + x_bar;\nfoo");
+                }
+
+                #[test]
+                fn test_add_all_occurrences() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("ab ab\nab");
+                    code_component.get_mut_current().get_mut_cursor().set_x(1);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('L'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 2));
+                    assert_eq!(code_component.get_current().get_cursors().len(), 2);
+
+                    paste_text(&mut code_component, "1\n2\n3".to_string());
+                    assert_eq!(code_component.get_current().get_text(), "1 2\n3");
+                    paste_text(&mut code_component, "-".to_string());
+                    assert_eq!(code_component.get_current().get_text(), "1- 2-\n3-");
+                }
+
+            }
+
+            pub mod save_tests {
+                use std::fs;
+
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, code::{CodeComponent, code_utils::write_atomic}};
+                use crate::unit_tests::unit_tests::press;
+
+                #[test]
+                fn test_write_atomic_replaces_the_file() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("file.txt");
+                    fs::write(&path, "old content that is longer").unwrap();
+
+                    write_atomic(&path, "città → 漢字\n".as_bytes()).unwrap();
+                    write_atomic(&path, "città → 漢字\n".as_bytes()).unwrap();
+                    assert_eq!(fs::read_to_string(&path).unwrap(), "città → 漢字\n");
+                    //the temporary file is renamed, nothing is left in the folder
+                    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+                }
+
+                #[cfg(unix)]
+                #[test]
+                fn test_write_atomic_keeps_permissions() {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("script.sh");
+                    fs::write(&path, "echo old").unwrap();
+                    fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+                    write_atomic(&path, b"echo new").unwrap();
+                    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o750);
+                }
+
+                #[test]
+                fn test_save_writes_the_buffer() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("main.rs");
+                    fs::write(&path, "fn main() {}\n").unwrap();
+
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.set_current(Some(path.clone()), temp_dir.path()).unwrap();
+                    press(&mut code_component, &mut context, KeyCode::Char('é'), KeyModifiers::empty());
+                    assert!(!code_component.get_history().is_saved());
+
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert_eq!(fs::read_to_string(&path).unwrap(), "éfn main() {}\n");
+                    assert_eq!(context.message().clone(), Some("Saved main.rs".to_string()));
+                    assert!(code_component.get_history().is_saved());
+                }
+
+                #[test]
+                fn test_typing_after_save_modifies_the_buffer() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("main.rs");
+                    fs::write(&path, "x").unwrap();
+
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.set_current(Some(path.clone()), temp_dir.path()).unwrap();
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('a'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('b'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert!(!code_component.is_modified());
+
+                    //keystrokes right after saving are not grouped with the saved change
+                    press(&mut code_component, &mut context, KeyCode::Char('c'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('d'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "xabcd");
+                    assert_eq!(fs::read_to_string(&path).unwrap(), "xab");
+                    assert!(code_component.is_modified());
+
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "xab");
+                    assert!(!code_component.is_modified());
+                }
+
+                #[test]
+                fn test_save_reports_errors() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("missing").join("main.rs");
+
+                    let mut context = AppContext::default();
+                    context.set_active_file(Some(path));
+                    let mut code_component = CodeComponent::new();
+                    press(&mut code_component, &mut context, KeyCode::Char('a'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert!(context.message().as_ref().unwrap().starts_with("Could not save main.rs"));
+                    assert!(!code_component.get_history().is_saved());
+                }
+
+            }
+
+            pub mod format_tests {
+                use std::fs;
+
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, App, code::{CodeComponent, code_format::{CodeFormat, LineEnding}}};
+                use crate::unit_tests::unit_tests::press;
+
+                #[test]
+                fn test_detect_format() {
+                    let (format, text) = CodeFormat::detect("\u{feff}one\r\ntwo\r\n");
+                    assert_eq!(format, CodeFormat::new(LineEnding::Crlf, true, true));
+                    assert_eq!(text, "one\ntwo");
+
+                    let (format, text) = CodeFormat::detect("one\ntwo");
+                    assert_eq!(format, CodeFormat::new(LineEnding::Lf, false, false));
+                    assert_eq!(text, "one\ntwo");
+
+                    //mixed files take the most used line ending
+                    let (format, _) = CodeFormat::detect("a\r\nb\r\nc\n");
+                    assert_eq!(format.get_line_ending(), LineEnding::Crlf);
+                    assert!(format.has_mixed_line_endings());
+                    assert_eq!(format.unified(), CodeFormat::new(LineEnding::Crlf, false, true));
+                    assert!(!CodeFormat::detect("a\r\nb\r\n").0.has_mixed_line_endings());
+                }
+
+                #[test]
+                fn test_apply_format() {
+                    for original in ["\u{feff}one\r\n\r\ntwo\r\n", "one\ntwo", "", "\n", "a\r\nb"] {
+                        let (format, text) = CodeFormat::detect(original);
+                        assert_eq!(format.apply(&text), original);
+                    }
+                }
+
+                #[test]
+                fn test_save_keeps_format_and_converts() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("windows.txt");
+                    fs::write(&path, "\u{feff}one\r\ntwo\r\n").unwrap();
+
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.set_current(Some(path.clone()), temp_dir.path()).unwrap();
+                    assert_eq!(code_component.get_current().get_text(), "one\ntwo");
+                    assert_eq!(code_component.get_current().len_lines(), 2);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert_eq!(fs::read(&path).unwrap(), "\u{feff}one\r\ntwo\r\n".as_bytes());
+
+                    press(&mut code_component, &mut context, KeyCode::Char('e'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_format().get_line_ending(), LineEnding::Lf);
+                    assert!(code_component.is_modified());
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert_eq!(fs::read(&path).unwrap(), "\u{feff}one\ntwo\n".as_bytes());
+                    assert!(!code_component.is_modified());
+                }
+
+                #[test]
+                fn test_mixed_line_endings_show_as_modified() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("mixed.txt");
+                    fs::write(&path, "one\r\ntwo\nthree\n").unwrap();
+
+                    let mut context = AppContext::default();
+                    let mut app = App::default();
+                    context.set_active_file(Some(path.clone()));
+                    app.open_active_file(&mut context);
+                    assert_eq!(context.message().clone(), Some("mixed.txt mixes line endings, saving converts them to LF".to_string()));
+                    assert!(app.get_code().has_mixed_line_endings());
+                    assert!(app.get_code().is_modified());
+
+                    //saving writes one line ending and the buffer matches the file again
+                    press(app.get_mut_code(), &mut context, KeyCode::Char('s'), KeyModifiers::CONTROL);
+                    assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+                    assert!(!app.get_code().is_modified());
+                    assert!(!app.get_code().has_mixed_line_endings());
+                }
+
+            }
+
+            pub mod search_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code::Code, code_search::CodeSearch, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{press, type_text};
+
+                #[test]
+                fn test_search_options() {
+                    let code = Code::from("Foo foo food\nfoo_bar città foo");
+                    let mut search = CodeSearch::new("foo".to_string(), 0);
+                    search.update(&code);
+                    assert_eq!(search.get_matches(), &[0..3, 4..7, 8..11, 13..16, 27..30]);
+
+                    search.toggle_case_sensitive();
+                    search.update(&code);
+                    assert_eq!(search.get_matches().len(), 4);
+
+                    search.toggle_whole_word();
+                    search.update(&code);
+                    assert_eq!(search.get_matches(), &[4..7, 27..30]);
+
+                    let mut search = CodeSearch::new(r"c\w+à".to_string(), 0);
+                    search.update(&code);
+                    assert!(search.get_matches().is_empty());
+                    search.toggle_regex();
+                    search.update(&code);
+                    assert_eq!(search.get_matches().len(), 1);
+                    assert_eq!(search.get_matches()[0], 21..26);
+
+                    search.set_query("(".to_string());
+                    search.update(&code);
+                    assert!(search.get_error().is_some());
+                    assert!(search.get_matches().is_empty());
+                }
+
+                #[test]
+                fn test_search_navigation() {
+                    let mut search = CodeSearch::new("a".to_string(), 3);
+                    search.update(&Code::from("a a a"));
+                    assert_eq!(search.get_current(), Some(2));
+                    search.next();
+                    assert_eq!(search.get_current(), Some(0));
+                    search.previous();
+                    search.previous();
+                    assert_eq!(search.get_current(), Some(1));
+                }
+
+                #[test]
+                fn test_search_prompt() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("let x = 1;\nlet y = x;");
+                    press(&mut code_component, &mut context, KeyCode::Char('f'), KeyModifiers::CONTROL);
+                    assert!(code_component.get_search().is_some());
+
+                    //typing goes to the query and selects the first match
+                    press(&mut code_component, &mut context, KeyCode::Char('x'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "let x = 1;\nlet y = x;");
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("x".to_string()));
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 5));
+
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 9));
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 5));
+
+                    press(&mut code_component, &mut context, KeyCode::Char('w'), KeyModifiers::ALT);
+                    assert!(code_component.get_search().as_ref().unwrap().is_whole_word());
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('l'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('e'), KeyModifiers::empty());
+                    assert!(code_component.get_search().as_ref().unwrap().get_matches().is_empty());
+
+                    press(&mut code_component, &mut context, KeyCode::Esc, KeyModifiers::empty());
+                    assert!(code_component.get_search().is_none());
+                }
+
+                #[test]
+                fn test_replace_one_and_all() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("a.b a.b\na.b");
+                    press(&mut code_component, &mut context, KeyCode::Char('r'), KeyModifiers::CONTROL);
+                    type_text(&mut code_component, &mut context, "a.b");
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    type_text(&mut code_component, &mut context, "$1x");
+
+                    //without regex the replacement is plain text
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "$1x a.b\na.b");
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("a.b".to_string()));
+                    assert_eq!(code_component.get_search().as_ref().unwrap().get_matches().len(), 2);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('a'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "$1x $1x\n$1x");
+                    assert!(code_component.get_search().as_ref().unwrap().get_matches().is_empty());
+
+                    //replace all is undone in one step
+                    press(&mut code_component, &mut context, KeyCode::Esc, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "$1x a.b\na.b");
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "a.b a.b\na.b");
+                }
+
+                #[test]
+                fn test_replace_all_with_longer_text() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("a a end");
+                    press(&mut code_component, &mut context, KeyCode::Char('r'), KeyModifiers::CONTROL);
+                    type_text(&mut code_component, &mut context, "a");
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    type_text(&mut code_component, &mut context, "bbb");
+                    press(&mut code_component, &mut context, KeyCode::Char('a'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "bbb bbb end");
+                    //the cursor is right after the last replacement
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 7));
+                }
+
+                #[test]
+                fn test_replace_capture_groups() {
+                    let code = Code::from("let x = foo(1);\nlet y = foo(22);");
+                    let mut search = CodeSearch::new(r"foo\((?P<arg>\d+)\)".to_string(), 0);
+                    search.toggle_regex();
+                    search.set_replacement(Some("bar(${arg}, $1)".to_string()));
+                    assert_eq!(search.get_replacements(&code), vec![
+                        (8..14, "bar(1, 1)".to_string()),
+                        (24..31, "bar(22, 22)".to_string()),
+                    ]);
+                }
+
+                #[test]
+                fn test_replace_within_selection() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("x\nx x\nx");
+                    code_component.get_mut_current().create_selection(Point::new(1, 0), Point::new(2, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('r'), KeyModifiers::CONTROL);
+                    assert!(code_component.get_search().as_ref().unwrap().get_scope().is_some());
+                    type_text(&mut code_component, &mut context, "x");
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    type_text(&mut code_component, &mut context, "yy");
+                    press(&mut code_component, &mut context, KeyCode::Char('a'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "x\nyy yy\nx");
+
+                    //the scope follows the replaced text and can be removed
+                    assert_eq!(code_component.get_search().as_ref().unwrap().get_scope().clone(), Some(2..8));
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_search().as_ref().unwrap().get_matches().len(), 2);
+                }
+
+                #[test]
+                fn test_search_starts_from_selection() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("one two one");
+                    code_component.get_mut_current().create_selection(Point::new(0, 8), Point::new(0, 11));
+                    press(&mut code_component, &mut context, KeyCode::Char('f'), KeyModifiers::CONTROL);
+                    let search = code_component.get_search().clone().unwrap();
+                    assert_eq!(search.get_query(), "one");
+                    assert_eq!(search.get_current(), Some(1));
+                }
+
+            }
+
+            pub mod highlight_tests {
+                use std::{fs, path::Path, sync::Arc};
+
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, code::{CodeComponent, code::Code, code_highlight::{CodeHighlighter, Grammar, GrammarRegistry, LineEdit, LineState, Token, TokenKind}, code_grammars::{RustGrammar, TomlGrammar, MarkdownGrammar, JsonGrammar, ShellGrammar}}};
+                use crate::unit_tests::unit_tests::press;
+
+                // Text and kind of every token of a line
+                fn tokens(grammar: &dyn Grammar, line: &str, state: LineState) -> (Vec<(String, TokenKind)>, LineState) {
+                    let (tokens, state) = grammar.highlight_line(line, state);
+                    (tokens.iter().map(|token| (line[token.get_range().clone()].to_string(), token.get_kind())).collect(), state)
+                }
+
+                fn kinds(grammar: &dyn Grammar, line: &str) -> Vec<(String, TokenKind)> {
+                    tokens(grammar, line, 0).0
+                }
+
+                fn token(text: &str, kind: TokenKind) -> (String, TokenKind) {
+                    (text.to_string(), kind)
+                }
+
+                #[test]
+                fn test_rust_grammar() {
+                    assert_eq!(kinds(&RustGrammar, r#"pub fn main() -> u8 { println!("a\"b", 'c', 1.5e3); } // done"#), vec![
+                        token("pub", TokenKind::Keyword),
+                        token("fn", TokenKind::Keyword),
+                        token("main", TokenKind::Function),
+                        token("u8", TokenKind::Type),
+                        token("println!", TokenKind::Function),
+                        token(r#""a\"b""#, TokenKind::String),
+                        token("'c'", TokenKind::String),
+                        token("1.5e3", TokenKind::Number),
+                        token("// done", TokenKind::Comment),
+                    ]);
+                    assert_eq!(kinds(&RustGrammar, "#[derive(Debug)] struct A<'a>(Vec<&'a str>, MAX);"), vec![
+                        token("#[derive(Debug)]", TokenKind::Attribute),
+                        token("struct", TokenKind::Keyword),
+                        token("A", TokenKind::Type),
+                        token("'a", TokenKind::Variable),
+                        token("Vec", TokenKind::Type),
+                        token("'a", TokenKind::Variable),
+                        token("str", TokenKind::Type),
+                        token("MAX", TokenKind::Constant),
+                    ]);
+
+                    //comments nest and strings span lines
+                    let (first, state) = tokens(&RustGrammar, "x /* a /* b */", 0);
+                    assert_eq!(first, vec![token("/* a /* b */", TokenKind::Comment)]);
+                    let (second, state) = tokens(&RustGrammar, "c */ let s = r#\"raw", state);
+                    assert_eq!(second, vec![token("c */", TokenKind::Comment), token("let", TokenKind::Keyword), token("r#\"raw", TokenKind::String)]);
+                    let (third, state) = tokens(&RustGrammar, "still \"# 2", state);
+                    assert_eq!((third, state), (vec![token("still \"#", TokenKind::String), token("2", TokenKind::Number)], 0));
+                }
+
+                #[test]
+                fn test_other_grammars() {
+                    assert_eq!(kinds(&TomlGrammar, "[[bin]] # target"), vec![token("[[bin]]", TokenKind::Heading), token("# target", TokenKind::Comment)]);
+                    assert_eq!(kinds(&TomlGrammar, r#"serde = { version = "1.0", optional = true }"#), vec![
+                        token("serde", TokenKind::Property),
+                        token("version", TokenKind::Property),
+                        token("\"1.0\"", TokenKind::String),
+                        token("optional", TokenKind::Property),
+                        token("true", TokenKind::Constant),
+                    ]);
+                    let (_, state) = tokens(&TomlGrammar, "text = \"\"\"first", 0);
+                    assert_eq!(tokens(&TomlGrammar, "last\"\"\" # end", state).0, vec![token("last\"\"\"", TokenKind::String), token("# end", TokenKind::Comment)]);
+
+                    assert_eq!(kinds(&MarkdownGrammar, "## Title"), vec![token("## Title", TokenKind::Heading)]);
+                    assert_eq!(kinds(&MarkdownGrammar, "- use `code` with **bold** and [a link](http://x)"), vec![
+                        token("-", TokenKind::Keyword),
+                        token("`code`", TokenKind::String),
+                        token("**bold**", TokenKind::Emphasis),
+                        token("[a link](http://x)", TokenKind::Link),
+                    ]);
+                    let (_, state) = tokens(&MarkdownGrammar, "```rust", 0);
+                    assert_eq!(tokens(&MarkdownGrammar, "# not a title", state), (vec![token("# not a title", TokenKind::String)], state));
+
+                    assert_eq!(kinds(&JsonGrammar, r#"{"name": "noce", "size": -1.5, "ok": null}"#), vec![
+                        token("\"name\"", TokenKind::Property),
+                        token("\"noce\"", TokenKind::String),
+                        token("\"size\"", TokenKind::Property),
+                        token("-1.5", TokenKind::Number),
+                        token("\"ok\"", TokenKind::Property),
+                        token("null", TokenKind::Constant),
+                    ]);
+
+                    assert_eq!(kinds(&ShellGrammar, "if [ -n \"$1\" ]; then NAME=${HOME} # home"), vec![
+                        token("if", TokenKind::Keyword),
+                        token("\"$1\"", TokenKind::String),
+                        token("then", TokenKind::Keyword),
+                        token("NAME", TokenKind::Variable),
+                        token("${HOME}", TokenKind::Variable),
+                        token("# home", TokenKind::Comment),
+                    ]);
+                }
+
+                #[derive(Debug)]
+                struct WordGrammar;
+
+                impl Grammar for WordGrammar {
+                    fn get_name(&self) -> &'static str {
+                        "Words"
+                    }
+
+                    fn get_extensions(&self) -> &'static [&'static str] {
+                        &["rs", "txt"]
+                    }
+
+                    fn highlight_line(&self, line: &str, _state: LineState) -> (Vec<Token>, LineState) {
+                        (vec![Token::new(0..line.len(), TokenKind::Keyword)], 0)
+                    }
+                }
+
+                #[test]
+                fn test_grammar_registry() {
+                    let mut registry = GrammarRegistry::default();
+                    let name = |registry: &GrammarRegistry, path: &str| registry.for_path(Path::new(path)).map(|grammar| grammar.get_name());
+                    assert_eq!(name(&registry, "src/main.rs"), Some("Rust"));
+                    assert_eq!(name(&registry, "Cargo.toml"), Some("TOML"));
+                    assert_eq!(name(&registry, "Cargo.lock"), Some("TOML"));
+                    assert_eq!(name(&registry, "README.MD"), Some("Markdown"));
+                    assert_eq!(name(&registry, "data.json"), Some("JSON"));
+                    assert_eq!(name(&registry, "/home/user/.bashrc"), Some("Shell"));
+                    assert_eq!(name(&registry, "notes.txt"), None);
+
+                    registry.register(Arc::new(WordGrammar));
+                    assert_eq!(name(&registry, "src/main.rs"), Some("Words"));
+                    assert_eq!(name(&registry, "notes.txt"), Some("Words"));
+                }
+
+                #[test]
+                fn test_incremental_highlight() {
+                    let mut code = Code::from("fn a() {}\nlet b = 1;\nlet c = 2;\nlet d = 3;");
+                    let mut highlighter = CodeHighlighter::new(Some(Arc::new(RustGrammar)));
+                    assert_eq!(highlighter.refresh(&code), 4);
+                    assert_eq!(highlighter.refresh(&code), 0);
+
+                    //an edit inside a line highlights only that line
+                    code.take_line_edits();
+                    code.insert(code.line_to_char(1) + 4, "bb");
+                    highlighter.apply_edits(&code.take_line_edits());
+                    assert_eq!(highlighter.refresh(&code), 1);
+
+                    //opening a comment highlights the next lines too, closing it stops there
+                    code.insert(code.line_to_char(2), "/*");
+                    highlighter.apply_edits(&code.take_line_edits());
+                    assert_eq!(highlighter.refresh(&code), 2);
+                    assert_eq!(highlighter.get_tokens(3)[0].get_kind(), TokenKind::Comment);
+                    code.insert(code.line_to_char(2) + 2, "*/");
+                    highlighter.apply_edits(&code.take_line_edits());
+                    assert_eq!(highlighter.refresh(&code), 2);
+                    assert_eq!(highlighter.get_tokens(3)[0].get_kind(), TokenKind::Keyword);
+
+                    //a new line shifts the tokens of the following ones
+                    code.insert(code.line_to_char(1), "// new\n");
+                    assert_eq!(code.take_line_edits(), vec![LineEdit::new(1, 1, 2)]);
+                    highlighter.apply_edits(&[LineEdit::new(1, 1, 2)]);
+                    assert_eq!(highlighter.refresh(&code), 2);
+                    assert_eq!(highlighter.get_tokens(1)[0].get_kind(), TokenKind::Comment);
+                    assert_eq!(highlighter.get_tokens(4)[0].get_kind(), TokenKind::Keyword);
+                }
+
+                #[test]
+                fn test_code_component_highlight() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("main.rs");
+                    fs::write(&path, "fn main() {}").unwrap();
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.set_current(Some(path), temp_dir.path()).unwrap();
+                    assert_eq!(code_component.get_highlighter().get_grammar_name(), Some("Rust"));
+                    assert_eq!(code_component.get_highlighter().get_tokens(0).len(), 2);
+
+                    press(&mut code_component, &mut context, KeyCode::Char('/'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('/'), KeyModifiers::empty());
+                    let tokens = code_component.get_highlighter().get_tokens(0);
+                    assert_eq!((tokens.len(), tokens[0].get_kind()), (1, TokenKind::Comment));
+
+                    code_component.set_grammar(None);
+                    assert!(code_component.get_highlighter().get_tokens(0).is_empty());
+                }
+            }
+
+            pub mod bracket_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code::Code, code_brackets::{find_match, get_bracket_match}, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{press, type_text};
+
+                #[test]
+                fn test_find_match() {
+                    let code = Code::from("fn a(b: [u8; 2]) {\n    (c)\n}");
+                    assert_eq!(find_match(&code, 4), Some(15));
+                    assert_eq!(find_match(&code, 15), Some(4));
+                    assert_eq!(find_match(&code, 8), Some(14));
+                    assert_eq!(find_match(&code, 17), Some(27));
+                    assert_eq!(find_match(&code, 27), Some(17));
+                    assert_eq!(find_match(&code, 0), None);
+                    assert_eq!(find_match(&Code::from("(()"), 0), None);
+
+                    //the cursor matches the bracket under it, or the one before it
+                    let mut code = Code::from("x(y)z");
+                    *code.get_mut_cursor() = Point::new(0, 1);
+                    assert_eq!(get_bracket_match(&code), Some((1, 3)));
+                    *code.get_mut_cursor() = Point::new(0, 4);
+                    assert_eq!(get_bracket_match(&code), Some((3, 1)));
+                    *code.get_mut_cursor() = Point::new(0, 0);
+                    assert_eq!(get_bracket_match(&code), None);
+                }
+
+                #[test]
+                fn test_jump_to_match() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.get_mut_current().set_text("if a {\n    b\n}");
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(0, 5);
+                    press(&mut code_component, &mut context, KeyCode::Char('b'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(2, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('b'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 5));
+                }
+
+                #[test]
+                fn test_auto_pairs() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "f(\"a");
+                    assert_eq!(code_component.get_current().get_text(), "f(\"a\")");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 4));
+
+                    //typing the closers moves over them
+                    type_text(&mut code_component, &mut context, "\")");
+                    assert_eq!(code_component.get_current().get_text(), "f(\"a\")");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 6));
+
+                    //no pair before a word, nor a quote after one
+                    code_component.get_mut_current().set_text("word");
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(0, 0);
+                    type_text(&mut code_component, &mut context, "[");
+                    assert_eq!(code_component.get_current().get_text(), "[word");
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(0, 5);
+                    type_text(&mut code_component, &mut context, "'");
+                    assert_eq!(code_component.get_current().get_text(), "[word'");
+                }
+
+                #[test]
+                fn test_backspace_deletes_empty_pair() {
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    type_text(&mut code_component, &mut context, "x{");
+                    assert_eq!(code_component.get_current().get_text(), "x{}");
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "x");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 1));
+
+                    //a pair with text inside is deleted one char at a time
+                    type_text(&mut code_component, &mut context, "(y");
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Backspace, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "x");
+                }
+            }
+
+            pub mod indent_tests {
+                use std::fs;
+
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, code::{CodeComponent, code_indent::IndentStyle, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                #[test]
+                fn test_detect_indent() {
+                    assert_eq!(IndentStyle::detect("fn a() {\n  if b {\n    c\n  }\n}"), Some(IndentStyle::Spaces(2)));
+                    assert_eq!(IndentStyle::detect("fn a() {\n    b\n    c\n}"), Some(IndentStyle::Spaces(4)));
+                    assert_eq!(IndentStyle::detect("fn a() {\n\tb\n\tc\n}"), Some(IndentStyle::Tabs));
+                    assert_eq!(IndentStyle::detect("a\nb\n\nc"), None);
+                    assert_eq!(IndentStyle::Spaces(2).unit(), "  ");
+                    assert_eq!(IndentStyle::Tabs.unit(), "\t");
+                }
+
+                #[test]
+                fn test_enter_keeps_indent() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("    let a = 1;", Point::new(0, 14));
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "    let a = 1;\n    ");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 4));
+
+                    //after an opener the new line goes one level deeper
+                    let mut code_component = component("match a {\n    B =>", Point::new(1, 8));
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "match a {\n    B =>\n        ");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(2, 8));
+
+                    //between a pair the closer goes on its own line
+                    let mut code_component = component("fn a() {}", Point::new(0, 8));
+                    code_component.set_indent(IndentStyle::Tabs);
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "fn a() {\n\t\n}");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 1));
+
+                    //the whole insertion is a single undo step
+                    press(&mut code_component, &mut context, KeyCode::Char('b'), KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "fn a() {}");
+                }
+
+                #[test]
+                fn test_closer_dedents() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("fn a() {\n    if b {\n        c\n        ", Point::new(3, 8));
+                    press(&mut code_component, &mut context, KeyCode::Char('}'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "fn a() {\n    if b {\n        c\n    }");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(3, 5));
+
+                    //without an opener the closer goes one level back
+                    let mut code_component = component("        ", Point::new(0, 8));
+                    press(&mut code_component, &mut context, KeyCode::Char(')'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "    )");
+
+                    //a closer after some text stays where it is typed
+                    let mut code_component = component("    a", Point::new(0, 5));
+                    press(&mut code_component, &mut context, KeyCode::Char(']'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "    a]");
+                }
+
+                #[test]
+                fn test_tab_and_back_tab() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("ab", Point::new(0, 1));
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "a    b");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 5));
+                    let mut code_component = component("      a", Point::new(0, 7));
+                    press(&mut code_component, &mut context, KeyCode::BackTab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "  a");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 3));
+
+                    //a selection indents and dedents all its lines, an empty line is left as it is
+                    let mut code_component = component("a\n\n  b\nc", Point::new(3, 0));
+                    code_component.get_mut_current().create_selection(Point::new(0, 1), Point::new(3, 0));
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "    a\n\n      b\nc");
+                    let selection = code_component.get_current().get_selection().clone().unwrap();
+                    assert_eq!((selection.get_start(), selection.get_end()), (&Point::new(0, 5), &Point::new(3, 0)));
+                    press(&mut code_component, &mut context, KeyCode::BackTab, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::BackTab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "a\n\nb\nc");
+
+                    //the change of every line is a single undo step
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "a\n\n  b\nc");
+
+                    //with tabs a line indented with spaces loses the spaces of one tab
+                    let mut code_component = component("          a", Point::new(0, 10));
+                    code_component.set_indent(IndentStyle::Tabs);
+                    code_component.set_tab_width(8);
+                    press(&mut code_component, &mut context, KeyCode::BackTab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "  a");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 2));
+                    assert_eq!(IndentStyle::Tabs.width(3), 3);
+                    assert_eq!(IndentStyle::Spaces(2).width(8), 2);
+                }
+
+                #[test]
+                fn test_indent_detected_on_load() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let path = temp_dir.path().join("main.rs");
+                    fs::write(&path, "fn main() {\n\tlet a = 1;\n}").unwrap();
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    code_component.set_current(Some(path.clone()), temp_dir.path()).unwrap();
+                    assert_eq!(code_component.get_indent(), IndentStyle::Tabs);
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(1, 0);
+                    press(&mut code_component, &mut context, KeyCode::Tab, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "fn main() {\n\t\tlet a = 1;\n}");
+
+                    //a file without indented lines uses the default one
+                    let other = temp_dir.path().join("notes.txt");
+                    fs::write(&other, "a\nb").unwrap();
+                    code_component.set_default_indent(IndentStyle::Spaces(2));
+                    code_component.set_current(Some(other), temp_dir.path()).unwrap();
+                    assert_eq!(code_component.get_indent(), IndentStyle::Spaces(2));
+                }
+            }
+
+            pub mod lines_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                fn undo(code_component: &mut CodeComponent, context: &mut AppContext) {
+                    press(code_component, context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                }
+
+                #[test]
+                fn test_duplicate() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("one\ntwo", Point::new(1, 1));
+                    press(&mut code_component, &mut context, KeyCode::Char('D'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_text(), "one\ntwo\ntwo");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(2, 1));
+
+                    //a selection is copied after itself and the copy is selected
+                    code_component.get_mut_current().create_selection(Point::new(0, 0), Point::new(0, 2));
+                    press(&mut code_component, &mut context, KeyCode::Char('D'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_text(), "onone\ntwo\ntwo");
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("on".to_string()));
+
+                    undo(&mut code_component, &mut context);
+                    assert_eq!(code_component.get_current().get_text(), "one\ntwo\ntwo");
+                    undo(&mut code_component, &mut context);
+                    assert_eq!(code_component.get_current().get_text(), "one\ntwo");
+                }
+
+                #[test]
+                fn test_move_lines() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("a\nb\nc\nd", Point::new(1, 0));
+                    code_component.get_mut_current().create_selection(Point::new(1, 0), Point::new(2, 1));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "a\nd\nb\nc");
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("b\nc".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "a\nd\nb\nc");
+
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::ALT);
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "b\nc\na\nd");
+                    undo(&mut code_component, &mut context);
+                    assert_eq!(code_component.get_current().get_text(), "a\nb\nc\nd");
+                }
+
+                #[test]
+                fn test_delete_and_join_lines() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("a\nb\nc", Point::new(1, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('K'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_text(), "a\nc");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('K'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_text(), "a");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 0));
+
+                    let mut code_component = component("let a =\n    1\n    + 2;", Point::new(0, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('j'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "let a = 1\n    + 2;");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 7));
+                    code_component.get_mut_current().create_selection(Point::new(0, 0), Point::new(1, 2));
+                    press(&mut code_component, &mut context, KeyCode::Char('j'), KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "let a = 1 + 2;");
+                    undo(&mut code_component, &mut context);
+                    assert_eq!(code_component.get_current().get_text(), "let a = 1\n    + 2;");
+                }
+
+                #[test]
+                fn test_insert_line() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("fn a() {\n    b(c)\n}", Point::new(1, 6));
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "fn a() {\n    b(c)\n    \n}");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(2, 4));
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(1, 6);
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_text(), "fn a() {\n    \n    b(c)\n    \n}");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 4));
+                }
+
+                #[test]
+                fn test_sort_and_reverse_lines() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("c\nb\na\nz", Point::new(3, 0));
+                    code_component.get_mut_current().create_selection(Point::new(0, 0), Point::new(3, 0));
+                    press(&mut code_component, &mut context, KeyCode::Char('s'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "a\nb\nc\nz");
+                    press(&mut code_component, &mut context, KeyCode::Char('r'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "c\nb\na\nz");
+                    undo(&mut code_component, &mut context);
+                    assert_eq!(code_component.get_current().get_text(), "a\nb\nc\nz");
+
+                    //without a selection on more lines nothing changes
+                    code_component.get_mut_current().flush_selection();
+                    press(&mut code_component, &mut context, KeyCode::Char('r'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_current().get_text(), "a\nb\nc\nz");
+                }
+            }
+
+            pub mod navigation_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{code::Code, code_navigation::{next_word, previous_word}, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                #[test]
+                fn test_word_boundaries() {
+                    let code = Code::from("let a_b = c.d();\n  next");
+                    assert_eq!(next_word(&code, 0), 3);
+                    assert_eq!(next_word(&code, 3), 7);
+                    assert_eq!(next_word(&code, 7), 9);
+                    assert_eq!(next_word(&code, 12), 13);
+                    assert_eq!(next_word(&code, 13), 16);
+                    assert_eq!(next_word(&code, 16), 23);
+                    assert_eq!(next_word(&code, 23), 23);
+                    assert_eq!(previous_word(&code, 23), 19);
+                    assert_eq!(previous_word(&code, 19), 13);
+                    assert_eq!(previous_word(&code, 7), 4);
+                    assert_eq!(previous_word(&code, 0), 0);
+                }
+
+                #[test]
+                fn test_word_movement() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("one two", Point::new(0, 0));
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 3));
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_selected_text(), Some(" two".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_selected_text(), Some(" ".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("one".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Left, KeyModifiers::CONTROL);
+                    assert!(code_component.get_current().get_selection().is_none());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 0));
+                }
+
+                #[test]
+                fn test_home_and_end() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("a\n    let b;\nc", Point::new(1, 8));
+                    press(&mut code_component, &mut context, KeyCode::Home, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 4));
+                    press(&mut code_component, &mut context, KeyCode::Home, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 0));
+                    press(&mut code_component, &mut context, KeyCode::Home, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 4));
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("let b;".to_string()));
+
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(2, 1));
+                    assert!(code_component.get_current().get_selection().is_none());
+                    press(&mut code_component, &mut context, KeyCode::Home, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 0));
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("a\n    let b;\nc".to_string()));
+                }
+
+                #[test]
+                fn test_page_up_and_down() {
+                    let mut context = AppContext::default();
+                    let text: Vec<String> = (0..10).map(|number| "x".repeat(number)).collect();
+                    let mut code_component = component(&text.join("\n"), Point::new(5, 3));
+                    code_component.set_viewport_size(80, 3);
+                    press(&mut code_component, &mut context, KeyCode::PageDown, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(8, 3));
+                    press(&mut code_component, &mut context, KeyCode::PageDown, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(9, 3));
+                    press(&mut code_component, &mut context, KeyCode::PageDown, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(9, 9));
+
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(6, 6));
+                    assert_eq!(code_component.get_current().get_selected_text().map(|text| text.len()), Some(27));
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 0));
+                    assert!(code_component.get_current().get_selection().is_none());
+                }
+
+                #[test]
+                fn test_forward_delete() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("ab\ncd", Point::new(0, 1));
+                    press(&mut code_component, &mut context, KeyCode::Delete, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "a\ncd");
+                    press(&mut code_component, &mut context, KeyCode::Delete, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "acd");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 1));
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Delete, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "acd");
+
+                    code_component.get_mut_current().create_selection(Point::new(0, 0), Point::new(0, 2));
+                    press(&mut code_component, &mut context, KeyCode::Delete, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "d");
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 0));
+                }
+            }
+
+            pub mod viewport_tests {
+                use crossterm::event::{KeyCode, KeyModifiers};
+
+                use crate::state::{AppContext, code::{CodeComponent, code_utils::Point, code_viewport::{display_graphemes, wrap_columns}}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                fn sized(text: &str, width: usize, height: usize) -> CodeComponent {
+                    let mut code_component = component(text, Point::default());
+                    code_component.set_viewport_size(width, height);
+                    code_component
+                }
+
+                #[test]
+                fn test_display_and_wrap() {
+                    assert_eq!(display_graphemes("a\tb", 4), vec!["a", "   ", "b"]);
+                    assert_eq!(display_graphemes("\tb", 2), vec!["  ", "b"]);
+                    let graphemes = display_graphemes("abcdefg", 4);
+                    assert_eq!(wrap_columns(&graphemes, 3), vec![0, 3, 6]);
+                    assert_eq!(wrap_columns(&display_graphemes("", 4), 3), vec![0]);
+                    //a wide grapheme that does not fit goes on the next row
+                    assert_eq!(wrap_columns(&display_graphemes("ab界", 4), 3), vec![0, 2]);
+                }
+
+                #[test]
+                fn test_vertical_scroll_off() {
+                    let mut context = AppContext::default();
+                    let text: Vec<String> = (0..30).map(|number| number.to_string()).collect();
+                    let mut code_component = sized(&text.join("\n"), 10, 10);
+                    code_component.set_scroll_off(2);
+                    for _ in 0..7 {
+                        press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    }
+                    assert_eq!(code_component.get_viewport().get_top(), 0);
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_top(), 1);
+
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_viewport().get_top(), 20);
+                    for _ in 0..7 {
+                        press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::empty());
+                    }
+                    assert_eq!(code_component.get_viewport().get_top(), 20);
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_top(), 19);
+
+                    code_component.go_to(Point::new(0, 0));
+                    assert_eq!(code_component.get_viewport().get_top(), 0);
+                }
+
+                #[test]
+                fn test_horizontal_scroll() {
+                    let mut context = AppContext::default();
+                    let mut code_component = sized(&"x".repeat(30), 10, 5);
+                    code_component.set_scroll_off(2);
+                    for _ in 0..7 {
+                        press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    }
+                    assert_eq!(code_component.get_viewport().get_left(), 0);
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_left(), 1);
+                    press(&mut code_component, &mut context, KeyCode::End, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_left(), 23);
+                    let graphemes = display_graphemes(&"x".repeat(30), 4);
+                    assert_eq!(code_component.get_viewport().rows(&graphemes), vec![23..30]);
+                    press(&mut code_component, &mut context, KeyCode::Home, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_left(), 0);
+                }
+
                 #[test]
-                fn test_move_left() {
-                    let mut point = Point::new(3, 4);
-                    point.move_left(false, 5); // Not exceeding the limit
-                    assert_eq!(point.get_x(), 3);
-                    assert_eq!(point.get_y(), 3);
-    
-                    let mut point = Point::new(1, 0);
-                    point.move_left(true, 5); // Exceeding the limit
-                    assert_eq!(point.get_x(), 0);
-                    assert_eq!(point.get_y(), 4);
+                fn test_soft_wrap() {
+                    let mut context = AppContext::default();
+                    let mut code_component = sized("abcdefgh\nij", 3, 5);
+                    code_component.set_scroll_off(0);
+                    press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::ALT);
+                    assert!(code_component.get_viewport().is_wrapping());
+                    let graphemes = display_graphemes("abcdefgh", 4);
+                    assert_eq!(code_component.get_viewport().rows(&graphemes), vec![0..3, 3..6, 6..8]);
+
+                    //Up and Down move by rows keeping the on screen column
+                    *code_component.get_mut_current().get_mut_cursor() = Point::new(0, 1);
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 4));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 7));
+                    assert_eq!(code_component.get_current().get_selected_text(), Some("efg".to_string()));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(1, 1));
+                    press(&mut code_component, &mut context, KeyCode::Up, KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_cursor(), &Point::new(0, 7));
+
+                    //the viewport scrolls by rows too
+                    code_component.set_viewport_size(3, 2);
+                    assert_eq!((code_component.get_viewport().get_top(), code_component.get_viewport().get_top_row()), (0, 1));
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    assert_eq!((code_component.get_viewport().get_top(), code_component.get_viewport().get_top_row()), (0, 2));
                 }
-    
+            }
+
+            pub mod buffer_tests {
+                use std::fs;
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, code::CodeComponent};
+                use crate::unit_tests::unit_tests::press;
+
+                // A component with three files open, the last one shown
+                fn setup_test() -> (AppContext, CodeComponent, TempDir) {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let mut code_component = CodeComponent::new();
+                    for name in ["a.txt", "b.txt", "c.txt"] {
+                        let path = temp_dir.path().join(name);
+                        fs::write(&path, name).unwrap();
+                        code_component.set_current(Some(path), temp_dir.path()).unwrap();
+                    }
+                    (AppContext::default(), code_component, temp_dir)
+                }
+
+                fn names(code_component: &CodeComponent) -> Vec<String> {
+                    code_component.get_buffer_names()
+                }
+
                 #[test]
-                fn test_move_right() {
-                    let mut point = Point::new(3, 4);
-                    point.move_right(false, 5); // Not exceeding the limit
-                    assert_eq!(point.get_x(), 3);
-                    assert_eq!(point.get_y(), 5);
-    
-                    let mut point = Point::new(4, 4);
-                    point.move_right(true, 4); // Exceeding the limit
-                    assert_eq!(point.get_x(), 5);
-                    assert_eq!(point.get_y(), 0);
+                fn test_open_and_switch() {
+                    let (mut context, mut code_component, temp_dir) = setup_test();
+                    assert_eq!(names(&code_component), vec!["a.txt", "b.txt", "c.txt"]);
+                    assert_eq!(code_component.get_active_buffer(), 2);
+
+                    //every buffer keeps its own text and cursor
+                    press(&mut code_component, &mut context, KeyCode::Right, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "b.txt");
+                    assert_eq!(context.active_file().clone(), Some(temp_dir.path().join("b.txt")));
+                    press(&mut code_component, &mut context, KeyCode::PageDown, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_cursor().get_y(), 1);
+
+                    //going past the last buffer comes back to the first one
+                    press(&mut code_component, &mut context, KeyCode::PageDown, KeyModifiers::CONTROL);
+                    assert_eq!(code_component.get_current().get_text(), "a.txt");
+
+                    //opening a file already open shows its buffer
+                    code_component.set_current(Some(temp_dir.path().join("c.txt")), temp_dir.path()).unwrap();
+                    assert_eq!(names(&code_component).len(), 3);
+                    assert_eq!(code_component.get_active_buffer(), 2);
                 }
-    
+
                 #[test]
-                fn test_move_down() {
-                    let mut point = Point::new(3, 4);
-                    point.move_down(false, 5, 4); // Not exceeding the limit
-                    assert_eq!(point.get_x(), 4);
-                    assert_eq!(point.get_y(), 4);
-    
-                    let mut point = Point::new(3, 5);
-                    point.move_down(true, 5, 4); // Exceeding the limit
-                    assert_eq!(point.get_x(), 3);
-                    assert_eq!(point.get_y(), 5);
+                fn test_reorder() {
+                    let (mut context, mut code_component, _temp_dir) = setup_test();
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(names(&code_component), vec!["c.txt", "a.txt", "b.txt"]);
+                    assert_eq!(code_component.get_active_buffer(), 0);
+                    assert_eq!(code_component.get_current().get_text(), "c.txt");
+
+                    //the first buffer can't go further left
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+                    assert_eq!(names(&code_component), vec!["c.txt", "a.txt", "b.txt"]);
                 }
-    
+
                 #[test]
-                fn test_set_x_and_set_y() {
-                    let mut point = Point::new(3, 4);
-                    point.set_x(5);
-                    point.set_y(6);
-                    assert_eq!(point.get_x(), 5);
-                    assert_eq!(point.get_y(), 6);
+                fn test_close() {
+                    let (mut context, mut code_component, _temp_dir) = setup_test();
+                    press(&mut code_component, &mut context, KeyCode::Char('!'), KeyModifiers::empty());
+                    assert!(code_component.has_modified_buffers());
+                    press(&mut code_component, &mut context, KeyCode::PageUp, KeyModifiers::CONTROL);
+                    assert!(!code_component.is_modified());
+                    assert!(code_component.is_buffer_modified(2));
+
+                    code_component.close_buffer();
+                    assert_eq!(names(&code_component), vec!["a.txt", "c.txt"]);
+                    assert_eq!(code_component.get_current().get_text(), "!c.txt");
+                    code_component.close_buffer();
+                    code_component.close_buffer();
+                    assert_eq!(names(&code_component), vec!["untitled"]);
+                    assert!(!code_component.has_modified_buffers());
                 }
-    
-            }
-    
-            pub mod selection_tests {
-                use crate::state::code::{code_selection::CodeSelection, code_utils::Point};
-    
-    
+
                 #[test]
-                fn test_set_start_and_set_end() {
-                    let mut selection = CodeSelection::new(Point::new(1, 2), Point::new(3, 4));
-            
-                    let new_start = Point::new(5, 6);
-                    let new_end = Point::new(7, 8);
-            
-                    selection.set_start(new_start.clone());
-                    selection.set_end(new_end.clone());
-            
-                    assert_eq!(selection.get_start(), &new_start);
-                    assert_eq!(selection.get_end(), &new_end);
+                fn test_picker() {
+                    let (mut context, mut code_component, _temp_dir) = setup_test();
+                    press(&mut code_component, &mut context, KeyCode::Char('p'), KeyModifiers::CONTROL);
+                    assert!(code_component.get_buffer_picker().is_some());
+                    press(&mut code_component, &mut context, KeyCode::Char('B'), KeyModifiers::SHIFT);
+                    let picker = code_component.get_buffer_picker().clone().unwrap();
+                    assert_eq!(picker.matches(&names(&code_component)), vec![1]);
+
+                    press(&mut code_component, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                    assert!(code_component.get_buffer_picker().is_none());
+                    assert_eq!(code_component.get_current().get_text(), "b.txt");
+
+                    //Esc leaves the buffer shown
+                    press(&mut code_component, &mut context, KeyCode::Char('p'), KeyModifiers::CONTROL);
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Esc, KeyModifiers::empty());
+                    assert_eq!(code_component.get_active_buffer(), 1);
                 }
-    
             }
-    
-            pub mod change_tests {
-    
-                use crate::state::code::{code_history::Change, code::Line};
-    
+
+            pub mod panes_tests {
+                use std::fs;
+                use crossterm::event::{KeyCode, KeyModifiers};
+                use tempfile::TempDir;
+
+                use crate::state::{AppContext, code::{CodeComponent, code_panes::{PaneLayout, SplitDirection}, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                fn focus(code_component: &mut CodeComponent, context: &mut AppContext, code: KeyCode) {
+                    press(code_component, context, code, KeyModifiers::ALT | KeyModifiers::SHIFT);
+                }
+
                 #[test]
-                fn test_create_change_with_strings() {
-                    let number = 42;
-                    let from = "Old Text".to_string();
-                    let to = "New Text".to_string();
-            
-                    let change = Change::create_change_with_strings(number, from.clone(), to.clone());
-            
-                    assert_eq!(change.get_number(), number);
-                    assert_eq!(change.get_from(), from);
-                    assert_eq!(change.get_to(), to);
+                fn test_split_keeps_a_cursor_for_every_pane() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("first\nsecond\nthird", Point::default());
+                    press(&mut code_component, &mut context, KeyCode::Char('|'), KeyModifiers::ALT | KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_panes().len(), 2);
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+
+                    //the new pane moves and edits on its own
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Down, KeyModifiers::empty());
+                    press(&mut code_component, &mut context, KeyCode::Char('!'), KeyModifiers::empty());
+                    let (code, _, _) = code_component.get_pane_view(0);
+                    assert_eq!(code.get_text(), "first\nsecond\n!third");
+                    assert_eq!(code.get_cursor().clone(), Point::new(0, 0));
+
+                    focus(&mut code_component, &mut context, KeyCode::Left);
+                    assert_eq!(code_component.get_panes().get_focused(), 0);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+                    focus(&mut code_component, &mut context, KeyCode::Right);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(2, 1));
                 }
-            
+
                 #[test]
-                fn test_create_change_with_line() {
-                    let line = Line::new(42, "Old Text".to_string());
-                    let to = "New Text".to_string();
-            
-                    let change = Change::create_change_with_line(line.clone(), to.clone());
-            
-                    assert_eq!(change.get_number(), line.get_number());
-                    assert_eq!(change.get_from(), line.get_string());
-                    assert_eq!(change.get_to(), to);
+                fn test_focus_moves_across_nested_splits() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("code", Point::default());
+                    //pane 0 on the left, pane 1 on the top right and pane 2 under it
+                    press(&mut code_component, &mut context, KeyCode::Char('|'), KeyModifiers::ALT);
+                    press(&mut code_component, &mut context, KeyCode::Char('-'), KeyModifiers::ALT);
+                    assert!(matches!(code_component.get_panes().get_layout(), PaneLayout::Split { direction: SplitDirection::Horizontal, .. }));
+                    assert_eq!(code_component.get_panes().get_focused(), 2);
+
+                    focus(&mut code_component, &mut context, KeyCode::Up);
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+                    focus(&mut code_component, &mut context, KeyCode::Up);
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+                    focus(&mut code_component, &mut context, KeyCode::Left);
+                    assert_eq!(code_component.get_panes().get_focused(), 0);
+                    focus(&mut code_component, &mut context, KeyCode::Right);
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+                    focus(&mut code_component, &mut context, KeyCode::Down);
+                    assert_eq!(code_component.get_panes().get_focused(), 2);
+
+                    //closing a pane gives its space to the one next to it
+                    press(&mut code_component, &mut context, KeyCode::Char('w'), KeyModifiers::ALT);
+                    assert_eq!(code_component.get_panes().len(), 2);
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+                    assert!(matches!(code_component.get_panes().get_layout(), PaneLayout::Split { direction: SplitDirection::Horizontal, .. }));
                 }
-            
+
                 #[test]
-                fn test_get_old_line() {
-                    let number = 42;
-                    let from = "Old Text".to_string();
-                    let to = "New Text".to_string();
-            
-                    let change = Change::create_change_with_strings(number, from.clone(), to.clone());
-                    let old_line = change.get_old_line();
-            
-                    assert_eq!(old_line.get_number(), number);
-                    assert_eq!(old_line.get_string(), from);
+                fn test_resize() {
+                    let mut context = AppContext::default();
+                    let mut code_component = component("code", Point::default());
+                    press(&mut code_component, &mut context, KeyCode::Char('|'), KeyModifiers::ALT);
+                    let resize = KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+                    press(&mut code_component, &mut context, KeyCode::Left, resize);
+                    press(&mut code_component, &mut context, KeyCode::Left, resize);
+                    assert!(matches!(code_component.get_panes().get_layout(), PaneLayout::Split { ratio: 40, .. }));
+
+                    //there is no stacked split to resize, and a pane never goes below a tenth of the space
+                    press(&mut code_component, &mut context, KeyCode::Up, resize);
+                    for _ in 0..20 {
+                        press(&mut code_component, &mut context, KeyCode::Right, resize);
+                    }
+                    assert!(matches!(code_component.get_panes().get_layout(), PaneLayout::Split { ratio: 90, .. }));
                 }
-            
+
                 #[test]
-                fn test_get_new_line() {
-                    let number = 42;
-                    let from = "Old Text".to_string();
-                    let to = "New Text".to_string();
-            
-                    let change = Change::create_change_with_strings(number, from.clone(), to.clone());
-                    let new_line = change.get_new_line();
-            
-                    assert_eq!(new_line.get_number(), number);
-                    assert_eq!(new_line.get_string(), to);
+                fn test_panes_show_different_buffers() {
+                    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                    let mut context = AppContext::default();
+                    let mut code_component = CodeComponent::new();
+                    for name in ["a.txt", "b.txt"] {
+                        fs::write(temp_dir.path().join(name), name).unwrap();
+                    }
+                    code_component.set_current(Some(temp_dir.path().join("a.txt")), temp_dir.path()).unwrap();
+                    press(&mut code_component, &mut context, KeyCode::Char('-'), KeyModifiers::ALT);
+                    code_component.set_current(Some(temp_dir.path().join("b.txt")), temp_dir.path()).unwrap();
+                    assert_eq!(code_component.get_pane_name(0), "a.txt");
+                    assert_eq!(code_component.get_pane_name(1), "b.txt");
+                    assert_eq!(code_component.get_pane_view(0).0.get_text(), "a.txt");
+
+                    focus(&mut code_component, &mut context, KeyCode::Up);
+                    assert_eq!(code_component.get_current().get_text(), "a.txt");
+                    assert_eq!(context.active_file().clone(), Some(temp_dir.path().join("a.txt")));
+
+                    //closing a buffer shows another one in the panes that showed it
+                    code_component.close_buffer();
+                    assert_eq!(code_component.get_pane_name(0), "b.txt");
+                    assert_eq!(code_component.get_pane_name(1), "b.txt");
                 }
-                
-    
             }
-    
-            pub mod history_tests {
-                use crate::state::code::{code::Code, code_history::{CodeHistory, Change}};
-    
+
+            pub mod mouse_tests {
+                use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEvent, MouseEventKind, MouseButton};
+
+                use crate::state::{AppContext, Component, ScreenArea, code::{CodeComponent, code_utils::Point}};
+                use crate::unit_tests::unit_tests::{component, press};
+
+                fn mouse(code_component: &mut CodeComponent, context: &mut AppContext, kind: MouseEventKind, column: u16, row: u16, modifiers: KeyModifiers) {
+                    code_component.handle_event(context, Event::Mouse(MouseEvent { kind, column, row, modifiers }));
+                }
+
+                fn click(code_component: &mut CodeComponent, context: &mut AppContext, column: u16, row: u16) {
+                    mouse(code_component, context, MouseEventKind::Down(MouseButton::Left), column, row, KeyModifiers::empty());
+                }
+
+                // The lines of the only pane start at column 5 and row 1
+                fn in_pane(text: &str) -> CodeComponent {
+                    let mut code_component = component(text, Point::default());
+                    code_component.set_pane_area(0, ScreenArea::new(0, 0, 40, 12), ScreenArea::new(5, 1, 30, 10));
+                    code_component
+                }
+
+                fn selection(code_component: &CodeComponent) -> Option<(Point, Point)> {
+                    code_component.get_current().get_selection().as_ref().map(|selection| (selection.get_start().clone(), selection.get_end().clone()))
+                }
+
                 #[test]
-                fn test_new_code_history() {
-                    let code = Code::new();
-                    let history = CodeHistory::new(code.clone());
-    
-                    assert_eq!(history.get_current_code(), code);
+                fn test_click_places_the_cursor_and_shift_click_selects() {
+                    let mut context = AppContext::default();
+                    let mut code_component = in_pane("first line\nsecond line\nthird");
+                    click(&mut code_component, &mut context, 8, 2);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 3));
+                    assert_eq!(selection(&code_component), None);
+
+                    //past the end of a line the cursor goes to its end, below the last line to the end of the code
+                    click(&mut code_component, &mut context, 30, 3);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(2, 5));
+                    click(&mut code_component, &mut context, 6, 9);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(2, 5));
+
+                    mouse(&mut code_component, &mut context, MouseEventKind::Down(MouseButton::Left), 7, 1, KeyModifiers::SHIFT);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 2));
+                    assert_eq!(selection(&code_component), Some((Point::new(2, 5), Point::new(0, 2))));
                 }
-    
+
                 #[test]
-                fn test_new_code_history_with_changes() {
-                    let code = Code::new();
-                    let changes = vec![Change::create_change_with_strings(1, "Old Text".to_string(), "New Text".to_string())];
-                    let history = CodeHistory::new_with_changes(code.clone(), changes.clone());
-    
-                    assert_eq!(history.get_current_code(), code);
-                    assert_eq!(history.get_changes(), changes);
+                fn test_double_and_triple_click_select_word_and_line() {
+                    let mut context = AppContext::default();
+                    let mut code_component = in_pane("first line\nsecond line\nthird");
+                    click(&mut code_component, &mut context, 13, 2);
+                    click(&mut code_component, &mut context, 13, 2);
+                    assert_eq!(selection(&code_component), Some((Point::new(1, 7), Point::new(1, 11))));
+                    click(&mut code_component, &mut context, 13, 2);
+                    assert_eq!(selection(&code_component), Some((Point::new(1, 0), Point::new(2, 0))));
+
+                    //the next click starts over
+                    click(&mut code_component, &mut context, 13, 2);
+                    assert_eq!(selection(&code_component), None);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(1, 8));
                 }
-    
+
                 #[test]
-                fn test_add_change() {
-                    let mut code_history = CodeHistory::new(Code::new());
-                    let change = Change::create_change_with_strings(1, "Old Text".to_string(), "New Text".to_string());
-                    code_history.add_change(change.clone());
-    
-                    assert_eq!(code_history.get_changes().len(), 1);
-                    assert_eq!(code_history.get_changes()[0], change);
+                fn test_drag_selects_from_the_click() {
+                    let mut context = AppContext::default();
+                    let mut code_component = in_pane("first line\nsecond line\nthird");
+                    click(&mut code_component, &mut context, 6, 1);
+                    mouse(&mut code_component, &mut context, MouseEventKind::Drag(MouseButton::Left), 9, 2, KeyModifiers::empty());
+                    mouse(&mut code_component, &mut context, MouseEventKind::Drag(MouseButton::Left), 8, 3, KeyModifiers::empty());
+                    assert_eq!(selection(&code_component), Some((Point::new(0, 1), Point::new(2, 3))));
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(2, 3));
+
+                    //typing replaces what was selected
+                    press(&mut code_component, &mut context, KeyCode::Char('x'), KeyModifiers::empty());
+                    assert_eq!(code_component.get_current().get_text(), "fxrd");
                 }
-    
+
                 #[test]
-                fn test_add_change_at_position() {
-                    let mut code_history = CodeHistory::new(Code::new());
-                    let change1 = Change::create_change_with_strings(1, "Old Text 1".to_string(), "New Text 1".to_string());
-                    let change2 = Change::create_change_with_strings(2, "Old Text 2".to_string(), "New Text 2".to_string());
-                    code_history.add_change(change1.clone());
-                    code_history.add_change_at_position(0, change2.clone());
-    
-                    assert_eq!(code_history.get_changes().len(), 2);
-                    assert_eq!(code_history.get_changes()[0], change2);
-                    assert_eq!(code_history.get_changes()[1], change1);
+                fn test_wheel_scrolls_without_moving_the_cursor() {
+                    let mut context = AppContext::default();
+                    let text = (0..30).map(|line| line.to_string()).collect::<Vec<_>>().join("\n");
+                    let mut code_component = in_pane(&text);
+                    mouse(&mut code_component, &mut context, MouseEventKind::ScrollDown, 10, 5, KeyModifiers::empty());
+                    mouse(&mut code_component, &mut context, MouseEventKind::ScrollDown, 10, 5, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_top(), 6);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(0, 0));
+                    mouse(&mut code_component, &mut context, MouseEventKind::ScrollUp, 10, 5, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_top(), 3);
+
+                    //outside of the panes the wheel does nothing
+                    mouse(&mut code_component, &mut context, MouseEventKind::ScrollDown, 60, 5, KeyModifiers::empty());
+                    assert_eq!(code_component.get_viewport().get_top(), 3);
+
+                    //a click on the scrolled lines places the cursor there
+                    click(&mut code_component, &mut context, 5, 1);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(3, 0));
+                }
+
+                #[test]
+                fn test_click_focuses_the_pane_under_the_mouse() {
+                    let mut context = AppContext::default();
+                    let mut code_component = in_pane("first\nsecond\nthird");
+                    press(&mut code_component, &mut context, KeyCode::Char('|'), KeyModifiers::ALT);
+                    code_component.set_pane_area(0, ScreenArea::new(0, 0, 20, 12), ScreenArea::new(3, 1, 16, 10));
+                    code_component.set_pane_area(1, ScreenArea::new(20, 0, 20, 12), ScreenArea::new(23, 1, 16, 10));
+                    assert_eq!(code_component.get_panes().get_focused(), 1);
+
+                    click(&mut code_component, &mut context, 5, 3);
+                    assert_eq!(code_component.get_panes().get_focused(), 0);
+                    assert_eq!(code_component.get_current().get_cursor().clone(), Point::new(2, 2));
+                    //the other pane keeps its cursor
+                    let (code, _, _) = code_component.get_pane_view(1);
+                    assert_eq!(code.get_cursor().clone(), Point::new(0, 0));
                 }
-    
             }
     
     
@@ -345,6 +2220,153 @@ mod unit_tests {
             
         }
 
+        pub mod project_search_tests {
+            use std::fs;
+
+            use regex::Regex;
+            use tempfile::TempDir;
+
+            use crate::state::project::project_search::{list_files, search_files};
+
+            #[test]
+            fn test_search_files_skips_ignored_and_binary() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path();
+                fs::create_dir_all(root.join("src")).unwrap();
+                fs::create_dir_all(root.join("target/debug")).unwrap();
+                fs::create_dir_all(root.join(".git")).unwrap();
+                fs::write(root.join(".gitignore"), "# build output\ntarget/\n*.log\n!keep.log\n").unwrap();
+                fs::write(root.join("src/main.rs"), "fn main() {\n    let needle = 1;\n}").unwrap();
+                fs::write(root.join("notes.txt"), "needle needle").unwrap();
+                fs::write(root.join("debug.log"), "needle").unwrap();
+                fs::write(root.join("keep.log"), "needle").unwrap();
+                fs::write(root.join("target/debug/out.txt"), "needle").unwrap();
+                fs::write(root.join(".git/HEAD"), "needle").unwrap();
+                fs::write(root.join("image.bin"), b"needle\0\x01").unwrap();
+
+                let results = search_files(root, &Regex::new("needle").unwrap());
+                let labels: Vec<String> = results.iter().map(|result| result.label(root)).collect();
+                assert_eq!(labels, vec![
+                    "keep.log:1: needle",
+                    "notes.txt:1: needle needle",
+                    "notes.txt:1: needle needle",
+                    "src/main.rs:2: let needle = 1;",
+                ]);
+                assert_eq!((results[2].get_line(), results[2].get_column()), (0, 7));
+                assert_eq!((results[3].get_line(), results[3].get_column()), (1, 8));
+            }
+
+            #[test]
+            fn test_list_files_ignore_patterns() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path();
+                fs::create_dir_all(root.join("a/build")).unwrap();
+                fs::create_dir_all(root.join("build")).unwrap();
+                fs::create_dir_all(root.join("a/gen")).unwrap();
+                fs::write(root.join(".gitignore"), "/build\n**/gen/*.rs\n").unwrap();
+                fs::write(root.join("a/.gitignore"), "file[0-9].txt\n").unwrap();
+                fs::write(root.join("build/x.txt"), "").unwrap();
+                fs::write(root.join("a/build/y.txt"), "").unwrap();
+                fs::write(root.join("a/gen/z.rs"), "").unwrap();
+                fs::write(root.join("a/gen/z.md"), "").unwrap();
+                fs::write(root.join("a/file1.txt"), "").unwrap();
+                fs::write(root.join("a/fileA.txt"), "").unwrap();
+
+                let files: Vec<String> = list_files(root)
+                .iter()
+                .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().to_string())
+                .collect();
+                assert_eq!(files, vec![".gitignore", "a/.gitignore", "a/build/y.txt", "a/fileA.txt", "a/gen/z.md"]);
+            }
+
+            #[cfg(unix)]
+            #[test]
+            fn test_list_files_skips_linked_folders() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path();
+                fs::create_dir_all(root.join("src")).unwrap();
+                fs::write(root.join("src/main.rs"), "").unwrap();
+                //a link to a parent folder does not loop forever, linked files are still listed
+                std::os::unix::fs::symlink(root, root.join("src/loop")).unwrap();
+                std::os::unix::fs::symlink(root.join("src/main.rs"), root.join("link.rs")).unwrap();
+
+                let files: Vec<String> = list_files(root)
+                .iter()
+                .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().to_string())
+                .collect();
+                assert_eq!(files, vec!["link.rs", "src/main.rs"]);
+            }
+        }
+
+        pub mod project_replace_tests {
+            use std::{fs, path::Path};
+
+            use crossterm::event::{KeyCode, KeyModifiers};
+            use tempfile::TempDir;
+
+            use crate::state::{AppContext, ComponentType, project::{ProjectComponent, project_search::apply_replacements}, code::CodeComponent};
+            use crate::unit_tests::unit_tests::{press, type_text};
+
+            fn open_replace(root: &Path, query: &str, replacement: &str, regex: bool) -> (ProjectComponent, AppContext) {
+                let mut context = AppContext::new(root.to_path_buf(), None, Some(ComponentType::Project), ComponentType::Project);
+                let mut project = ProjectComponent::new(root.to_path_buf());
+                press(&mut project, &mut context, KeyCode::Char('g'), KeyModifiers::CONTROL);
+                type_text(&mut project, &mut context, query);
+                if regex {
+                    press(&mut project, &mut context, KeyCode::Char('r'), KeyModifiers::ALT);
+                }
+                press(&mut project, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                press(&mut project, &mut context, KeyCode::Char('r'), KeyModifiers::CONTROL);
+                type_text(&mut project, &mut context, replacement);
+                (project, context)
+            }
+
+            #[test]
+            fn test_replace_preview_with_capture_groups() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path();
+                fs::write(root.join("a.rs"), "call(1); call(22);").unwrap();
+                let (project, _) = open_replace(root, r"call\((\d+)\)", "run($1)", true);
+                let search = project.get_search().as_ref().unwrap();
+
+                let previews: Vec<Option<String>> = search.get_results().iter().map(|result| result.get_preview()).collect();
+                assert_eq!(previews, vec![Some("run(1); call(22);".to_string()), Some("call(1); run(22);".to_string())]);
+                assert_eq!(search.get_accepted().len(), 2);
+            }
+
+            #[test]
+            fn test_replace_files_and_open_code() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path();
+                fs::write(root.join("a.txt"), "foo\r\nkeep foo\r\nfoo foo\r\n").unwrap();
+                fs::write(root.join("b.txt"), "open foo").unwrap();
+                fs::write(root.join("c.txt"), "changed foo").unwrap();
+                let mut code_component = CodeComponent::new();
+                code_component.set_current(Some(root.join("b.txt")), root).unwrap();
+
+                let (mut project, _) = open_replace(root, "foo", "bar", false);
+                //the second hunk of a.txt is rejected
+                press(&mut project, &mut AppContext::default(), KeyCode::Down, KeyModifiers::empty());
+                press(&mut project, &mut AppContext::default(), KeyCode::Char('t'), KeyModifiers::ALT);
+                //c.txt changes after the search
+                fs::write(root.join("c.txt"), "changed again foo").unwrap();
+
+                let report = apply_replacements(project.get_search().as_ref().unwrap(), &mut code_component);
+                assert_eq!((report.get_replaced(), report.get_files(), report.get_skipped()), (4, 2, 1));
+                assert_eq!(report.get_message(), "Replaced 4 occurrences in 2 files, 1 skipped");
+                assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "bar\r\nkeep foo\r\nbar bar\r\n");
+                assert_eq!(fs::read_to_string(root.join("c.txt")).unwrap(), "changed again foo");
+
+                //the open file changes only in the code, and it is undone in one step
+                assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "open foo");
+                assert_eq!(code_component.get_current().get_text(), "open bar");
+                assert!(code_component.is_modified());
+                let mut context = AppContext::default();
+                press(&mut code_component, &mut context, KeyCode::Char('z'), KeyModifiers::CONTROL);
+                assert_eq!(code_component.get_current().get_text(), "open foo");
+            }
+        }
+
         pub mod terminal_tests {
             use std::path::{Path, PathBuf};
 
@@ -531,6 +2553,186 @@ mod unit_tests {
 
         }
 
+        pub mod keymap_tests {
+            use crossterm::event::{Event, KeyEvent, KeyEventKind, KeyEventState, KeyCode, KeyModifiers};
+
+            use crate::state::{AppContext, App, Component, ComponentType, code::CodeComponent, keymap::{Keymap, KeyChord, KeyMatch, KeyScope, parse_sequence, keymap_defaults::Action}};
+
+            fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+                KeyEvent::new(code, modifiers)
+            }
+
+            fn chord(text: &str) -> KeyChord {
+                KeyChord::parse(text).unwrap()
+            }
+
+            #[test]
+            fn test_chords_parse_like_the_keys_of_the_terminal() {
+                assert_eq!(chord("ctrl+shift+d"), KeyChord::from(key(KeyCode::Char('D'), KeyModifiers::CONTROL)));
+                assert_eq!(chord("Ctrl+Shift+D"), KeyChord::from(key(KeyCode::Char('d'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)));
+                assert_eq!(chord("alt+|"), KeyChord::from(key(KeyCode::Char('|'), KeyModifiers::ALT | KeyModifiers::SHIFT)));
+                assert_eq!(chord("shift+tab"), KeyChord::from(key(KeyCode::BackTab, KeyModifiers::SHIFT)));
+                assert_eq!(chord("ctrl++"), KeyChord::new(KeyCode::Char('+'), KeyModifiers::CONTROL));
+                assert_eq!(chord("space"), KeyChord::new(KeyCode::Char(' '), KeyModifiers::NONE));
+                assert_eq!(chord("f5"), KeyChord::new(KeyCode::F(5), KeyModifiers::NONE));
+                assert_eq!(chord("ctrl+alt+shift+pageup").to_string(), "ctrl+alt+shift+pageup");
+                assert_eq!(parse_sequence("ctrl+k   ctrl+c").unwrap(), vec![chord("ctrl+k"), chord("ctrl+c")]);
+
+                assert!(KeyChord::parse("hyper+x").is_err());
+                assert!(KeyChord::parse("ctrl+foo").is_err());
+                assert!(KeyChord::parse("f13").is_err());
+                assert!(parse_sequence("  ").is_err());
+            }
+
+            #[test]
+            fn test_default_keymap_has_no_conflicts() {
+                let mut keymap = Keymap::default();
+                assert_eq!(keymap.conflicts(), Vec::<String>::new());
+                for binding in keymap.get_bindings() {
+                    assert_eq!(Action::from_name(binding.get_action().get_name()), Some(binding.get_action()));
+                }
+
+                //a key bound in several panels means the same thing in all of them
+                let cut = key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+                assert_eq!(keymap.resolve(KeyScope::Code, cut), KeyMatch::Action(Action::Cut));
+                assert_eq!(keymap.resolve(KeyScope::Terminal, cut), KeyMatch::Action(Action::Cut));
+                assert_eq!(keymap.resolve(KeyScope::Project, cut), KeyMatch::Unbound);
+                assert_eq!(keymap.resolve(KeyScope::Project, key(KeyCode::Delete, KeyModifiers::NONE)), KeyMatch::Action(Action::DeleteEntry));
+                assert_eq!(keymap.resolve(KeyScope::Global, cut), KeyMatch::Unbound);
+            }
+
+            #[test]
+            fn test_keymap_file_replaces_the_defaults_of_its_actions() {
+                let text = "
+                    [code]
+                    duplicate_lines = \"ctrl+k ctrl+d\"
+                    undo = [\"ctrl+z\", \"alt+u\"]
+                    redo = []
+                    unknown = \"ctrl+q\"
+                    copy = \"ctrl+nope\"
+                    open = \"ctrl+o\"
+
+                    [panel]
+                    save = \"ctrl+s\"
+                ";
+                let (keymap, problems) = Keymap::with_overrides(text);
+                assert_eq!(problems.len(), 4);
+                assert!(problems.contains(&"unknown action unknown in [code]".to_string()));
+                assert!(problems.contains(&"unknown action open in [code]".to_string()));
+                assert!(problems.contains(&"copy in [code]: unknown key 'nope' in 'ctrl+nope'".to_string()));
+                assert!(problems.contains(&"unknown scope [panel]".to_string()));
+
+                assert_eq!(keymap.keys_of(KeyScope::Code, Action::DuplicateLines), vec![&vec![chord("ctrl+k"), chord("ctrl+d")]]);
+                assert_eq!(keymap.keys_of(KeyScope::Code, Action::Undo), vec![&vec![chord("ctrl+z")], &vec![chord("alt+u")]]);
+                assert!(keymap.keys_of(KeyScope::Code, Action::Redo).is_empty());
+                //a wrong key unbinds nothing
+                assert_eq!(keymap.keys_of(KeyScope::Code, Action::Copy), vec![&vec![chord("ctrl+c")]]);
+                assert_eq!(keymap.keys_of(KeyScope::Code, Action::Save), vec![&vec![chord("ctrl+s")]]);
+
+                let (keymap, problems) = Keymap::with_overrides("[code");
+                assert_eq!(keymap, Keymap::default());
+                assert_eq!(problems.len(), 1);
+            }
+
+            #[test]
+            fn test_conflicting_bindings_are_reported() {
+                let (keymap, problems) = Keymap::with_overrides("
+                    [code]
+                    cut = \"ctrl+d\"
+                    save = \"ctrl+k\"
+                    copy = \"ctrl+k ctrl+c\"
+
+                    [project]
+                    cut = \"ctrl+d\"
+                ");
+                assert_eq!(problems, vec!["unknown action cut in [project]".to_string()]);
+                assert_eq!(keymap.conflicts(), vec![
+                    "ctrl+d runs both add_next_occurrence and cut in [code]".to_string(),
+                    "ctrl+k (save) hides ctrl+k ctrl+c (copy) in [code]".to_string(),
+                ]);
+            }
+
+            #[test]
+            fn test_sequences_wait_for_their_last_key() {
+                let (mut keymap, _) = Keymap::with_overrides("[code]\njoin_lines = \"ctrl+k ctrl+j\"");
+                let first = key(KeyCode::Char('k'), KeyModifiers::CONTROL);
+                let last = key(KeyCode::Char('j'), KeyModifiers::CONTROL);
+                assert_eq!(keymap.resolve(KeyScope::Code, first), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Code, last), KeyMatch::Action(Action::JoinLines));
+                assert!(keymap.get_pending().is_empty());
+
+                //a key not going on with the sequence counts on its own
+                assert_eq!(keymap.resolve(KeyScope::Code, first), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Code, key(KeyCode::Char('s'), KeyModifiers::CONTROL)), KeyMatch::Action(Action::Save));
+                assert_eq!(keymap.resolve(KeyScope::Code, first), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Code, key(KeyCode::Char('a'), KeyModifiers::NONE)), KeyMatch::Unbound);
+
+                //a sequence started in a panel does not go on in another one
+                assert_eq!(keymap.resolve(KeyScope::Code, first), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Project, last), KeyMatch::Unbound);
+                assert_eq!(keymap.resolve(KeyScope::Code, last), KeyMatch::Unbound);
+
+                //held keys repeat only the moves and the edits
+                let repeat = |code: KeyCode, modifiers: KeyModifiers| KeyEvent::new_with_kind_and_state(code, modifiers, KeyEventKind::Repeat, KeyEventState::NONE);
+                assert_eq!(keymap.resolve(KeyScope::Code, repeat(KeyCode::Backspace, KeyModifiers::NONE)), KeyMatch::Action(Action::DeleteBackward));
+                assert_eq!(keymap.resolve(KeyScope::Code, repeat(KeyCode::Char('s'), KeyModifiers::CONTROL)), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Code, repeat(KeyCode::Char('k'), KeyModifiers::CONTROL)), KeyMatch::Pending);
+                assert_eq!(keymap.resolve(KeyScope::Code, repeat(KeyCode::Char('a'), KeyModifiers::NONE)), KeyMatch::Unbound);
+            }
+
+            #[test]
+            fn test_components_run_the_actions_of_the_keymap() {
+                let mut context = AppContext::default();
+                let (keymap, _) = Keymap::with_overrides("[code]\nduplicate_lines = \"ctrl+k ctrl+d\"\n[global]\nnext_panel = \"ctrl+n\"");
+                context.set_keymap(keymap);
+
+                let mut code_component = CodeComponent::new();
+                code_component.get_mut_current().set_text("line");
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('k'), KeyModifiers::CONTROL)));
+                assert_eq!(code_component.get_current().get_text(), "line");
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('d'), KeyModifiers::CONTROL)));
+                assert_eq!(code_component.get_current().get_text(), "line\nline");
+                //keys without an action are typed
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('!'), KeyModifiers::NONE)));
+                assert_eq!(code_component.get_current().get_text(), "line\n!line");
+                //chords held with ctrl or alt never type, even without an action
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('e'), KeyModifiers::ALT)));
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('E'), KeyModifiers::SHIFT)));
+                assert_eq!(code_component.get_current().get_text(), "line\n!Eline");
+
+                let mut app = App::default();
+                context.set_hover(ComponentType::Project);
+                assert!(app.handle_event(&mut context, None, Event::Key(key(KeyCode::Tab, KeyModifiers::NONE))));
+                assert_eq!(context.hover().clone(), ComponentType::Project);
+                assert!(app.handle_event(&mut context, None, Event::Key(key(KeyCode::Char('n'), KeyModifiers::CONTROL))));
+                assert_eq!(context.hover().clone(), ComponentType::Code);
+            }
+
+            #[test]
+            fn test_prompts_resolve_their_keys_through_the_keymap() {
+                let mut context = AppContext::default();
+                let (keymap, problems) = Keymap::with_overrides("[code_search]\nnext_match = \"ctrl+n\"\ntoggle_case = \"alt+w\"");
+                assert!(problems.is_empty());
+                assert_eq!(keymap.conflicts(), vec!["alt+w runs both toggle_whole_word and toggle_case in [code_search]".to_string()]);
+                context.set_keymap(Keymap::with_overrides("[code_search]\nnext_match = \"ctrl+n\"").0);
+
+                let mut code_component = CodeComponent::new();
+                code_component.get_mut_current().set_text("one two one");
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('f'), KeyModifiers::CONTROL)));
+                for char in "one".chars() {
+                    code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char(char), KeyModifiers::NONE)));
+                }
+                assert_eq!(code_component.get_search().as_ref().unwrap().get_current(), Some(0));
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::Char('n'), KeyModifiers::CONTROL)));
+                assert_eq!(code_component.get_search().as_ref().unwrap().get_current(), Some(1));
+                //the default keys of the action are gone
+                code_component.handle_event(&mut context, Event::Key(key(KeyCode::F(3), KeyModifiers::NONE)));
+                assert_eq!(code_component.get_search().as_ref().unwrap().get_current(), Some(1));
+                assert_eq!(code_component.get_search().as_ref().unwrap().get_query(), "one");
+            }
+        }
+
     }
 
 
@@ -597,7 +2799,7 @@ mod unit_tests {
 
         pub mod project_events_tests {
             use crossterm::event::{KeyEvent, KeyCode, KeyModifiers, Event};
-            use crate::state::{AppContext, App, project::ProjectComponent, code::CodeComponent, terminal::TerminalComponent, ComponentType};
+            use crate::state::{AppContext, App, project::ProjectComponent, code::{CodeComponent, code_utils::Point}, terminal::TerminalComponent, ComponentType};
             use std::{fs::File, path::Path};
             use std::io::Write;
             use tempfile::TempDir;
@@ -619,6 +2821,37 @@ mod unit_tests {
     
 
 
+            #[test]
+            pub fn project_search_opens_result_test() {
+                let temp_dir = TempDir::new().expect("Failed to create temp directory");
+                let root = temp_dir.path().to_path_buf();
+                std::fs::write(root.join("a.txt"), "nothing here").unwrap();
+                std::fs::write(root.join("b.txt"), "first\n  the word\nword again").unwrap();
+                let mut context = AppContext::new(root.clone(), None, Some(ComponentType::Project), ComponentType::Project);
+                let mut app = App::new(ProjectComponent::new(root.clone()), CodeComponent::new(), TerminalComponent::new(), root.clone());
+                let press = |app: &mut App, context: &mut AppContext, code: KeyCode, modifiers: KeyModifiers| {
+                    let focus = context.focus().clone();
+                    app.handle_event(context, focus, Event::Key(KeyEvent::new(code, modifiers)));
+                };
+
+                press(&mut app, &mut context, KeyCode::Char('g'), KeyModifiers::CONTROL);
+                for char in "word".chars() {
+                    press(&mut app, &mut context, KeyCode::Char(char), KeyModifiers::empty());
+                }
+                assert!(app.get_project().get_search().as_ref().unwrap().get_results().is_empty());
+                press(&mut app, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                assert_eq!(app.get_project().get_search().as_ref().unwrap().get_results().len(), 2);
+
+                press(&mut app, &mut context, KeyCode::Down, KeyModifiers::empty());
+                press(&mut app, &mut context, KeyCode::Enter, KeyModifiers::empty());
+                assert_eq!(context.active_file(), &Some(root.join("b.txt")));
+                assert_eq!(context.focus(), &Some(ComponentType::Code));
+
+                app.open_active_file(&mut context);
+                assert_eq!(app.get_code().get_current().get_cursor(), &Point::new(2, 0));
+                assert_eq!(context.active_position(), &None);
+            }
+
             pub fn scroll_to(context: &mut AppContext, app: &mut App, prefix: &str) {
                 let contents = app.get_project().get_contents().clone();
                 let contents_with_prefix: Vec<usize> = contents.clone().into_iter().enumerate().filter(|(_i,content)| {
@@ -723,14 +2956,13 @@ mod unit_tests {
                 //set the new file if the active file changed
                 if context.active_file_changed() {
                     app.get_mut_code().get_mut_current().flush();
-                    app.get_mut_code().set_current(context.active_file().clone());
+                    app.get_mut_code().set_current(context.active_file().clone(), context.project_root()).unwrap();
                     context.set_active_file_changed(false);
                 }
                 app.handle_event(&mut context, Some(ComponentType::Project), fake_esc_event.clone());
                 app.handle_event(&mut context, None, fake_tab_event.clone());
                 app.handle_event(&mut context, None, fake_enter_event.clone());
                 app.handle_event(&mut context, Some(ComponentType::Code), fake_char_event.clone());
-                app.get_mut_code().get_mut_current().remove_cursor();
                 let code_content = app.get_code().get_current().get_content().get(0).unwrap().get_string();
                 assert_eq!(code_content.as_str(),"a temporary file!");
             }
@@ -758,7 +2990,7 @@ mod unit_tests {
                 //set the new file if the active file changed
                 if context.active_file_changed() {
                     app.get_mut_code().get_mut_current().flush();
-                    app.get_mut_code().set_current(context.active_file().clone());
+                    app.get_mut_code().set_current(context.active_file().clone(), context.project_root()).unwrap();
                     context.set_active_file_changed(false);
                 }
                 app.handle_event(&mut context, Some(ComponentType::Project), fake_esc_event.clone());
@@ -767,8 +2999,8 @@ mod unit_tests {
                 assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(0, 0));                
 
                 //test right arrow
-                for line in app.get_code().get_current().get_content().clone() {
-                    for _ in 1..line.get_string().len() {
+                for line in app.get_code().get_current().get_content() {
+                    for _ in 0..line.get_string().len() {
                         let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
                         app.handle_event(&mut context, Some(ComponentType::Code), fake_right_event.clone());
                         assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-1, previous_cursor_position.get_y()+1));
@@ -782,14 +3014,15 @@ mod unit_tests {
                     }
                 }
                 
-                //note that now the cursor should be on the last char of the last line
-                assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(app.get_code().get_current().get_content().len()-1, app.get_code().get_current().get_content().get(app.get_code().get_current().get_content().len()-1).unwrap().get_string().len()-1));
+                //note that now the cursor should be after the last char of the last line
+                let last = app.get_code().get_current().len_lines()-1;
+                assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(last, app.get_code().get_current().line_len(last)));
 
                 //test left arrow
-                let mut reversed_lines = app.get_code().get_current().get_content().clone();
+                let mut reversed_lines = app.get_code().get_current().get_content();
                 reversed_lines.reverse();
                 for line in reversed_lines.into_iter() {
-                    for _ in 1..line.get_string().len() {
+                    for _ in 0..line.get_string().len() {
                         let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
                         app.handle_event(&mut context, Some(ComponentType::Code), fake_left_event.clone());
                         assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-1, previous_cursor_position.get_y()-1));
@@ -799,8 +3032,8 @@ mod unit_tests {
                     if line.get_number() == 1 {
                         assert_eq!(app.get_code().get_current().get_cursor().clone(),previous_cursor_position);
                     } else {
-                        let upper_line = app.get_code().get_current().get_content().get(line.get_number()-2).unwrap();
-                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-2, upper_line.get_string().len()-2));
+                        let upper_line = app.get_code().get_current().get_line(line.get_number()-2).unwrap();
+                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-2, upper_line.get_string().len()));
                     }
                 }
 
@@ -808,47 +3041,29 @@ mod unit_tests {
                 assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(0,0));
 
                 //test down arrow
-                for line in app.get_code().get_current().get_content().clone() {
-                    for _ in 1..line.get_string().len() {
-                        let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
-                        app.handle_event(&mut context, Some(ComponentType::Code), fake_down_event.clone());
-                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number(), previous_cursor_position.get_y()));
-                    }
+                for line in app.get_code().get_current().get_content() {
                     let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
                     app.handle_event(&mut context, Some(ComponentType::Code), fake_down_event.clone());
                     if line.get_number() == app.get_code().get_current().get_content().len() {
-                        assert_eq!(app.get_code().get_current().get_cursor().clone(),previous_cursor_position);
+                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-1, line.get_string().len()));
                     } else {
-                        let last_line = app.get_code().get_current().get_content().get(app.get_code().get_current().get_content().len()-1).unwrap().clone();
-                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number(), last_line.get_string().len()-1));
+                        let lower_size = app.get_code().get_current().line_len(line.get_number());
+                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number(), previous_cursor_position.get_y().min(lower_size)));
                     }
                 }
 
-                //note that now the cursor should be on the last char of the last line
-                assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(app.get_code().get_current().get_content().len()-1, app.get_code().get_current().get_content().get(app.get_code().get_current().get_content().len()-1).unwrap().get_string().len()-1));
+                //note that now the cursor should be after the last char of the last line
+                assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(last, app.get_code().get_current().line_len(last)));
 
-                
                 //test up arrow
-                let mut reversed_lines = app.get_code().get_current().get_content().clone();
+                let mut reversed_lines = app.get_code().get_current().get_content();
                 reversed_lines.reverse();
                 for line in reversed_lines.into_iter() {
-                    for _ in 1..line.get_string().len() {
-                        let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
-                        app.handle_event(&mut context, Some(ComponentType::Code), fake_up_event.clone());
-                        let mut upper_size = 0;
-                        if let Some(upper_line) = app.get_code().get_current().get_content().get(line.get_number()-1) {
-                            upper_size = upper_line.get_string().len();
-                        }
-                        if upper_size < previous_cursor_position.get_y() {
-                            assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-1, upper_size-1))
-                        } else {
-                            assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-1, previous_cursor_position.get_y()));
-                        }
-                    }
                     let previous_cursor_position = app.get_code().get_current().get_cursor().clone();
                     app.handle_event(&mut context, Some(ComponentType::Code), fake_up_event.clone());
                     if line.get_number() != 1 {
-                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(previous_cursor_position.get_x()-1, previous_cursor_position.get_y()));
+                        let upper_size = app.get_code().get_current().line_len(line.get_number()-2);
+                        assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(line.get_number()-2, previous_cursor_position.get_y().min(upper_size)));
                     } else {
                         assert_eq!(app.get_code().get_current().get_cursor().clone(),Point::new(0,0));
                     }
@@ -882,7 +3097,7 @@ mod unit_tests {
                 //set the new file if the active file changed
                 if context.active_file_changed() {
                     app.get_mut_code().get_mut_current().flush();
-                    app.get_mut_code().set_current(context.active_file().clone());
+                    app.get_mut_code().set_current(context.active_file().clone(), context.project_root()).unwrap();
                     context.set_active_file_changed(false);
                 }
                 app.handle_event(&mut context, Some(ComponentType::Project), fake_esc_event.clone());
@@ -928,6 +3143,175 @@ mod unit_tests {
 
         }
 
+        pub mod confirm_events_tests {
+            use std::fs;
+            use crossterm::event::{KeyEvent, KeyModifiers, Event, KeyCode, MouseEvent, MouseEventKind, MouseButton};
+            use tempfile::TempDir;
+
+            use crate::state::{AppContext, App, Component, ComponentType, ConfirmChoice, PendingAction, ScreenArea};
+
+            fn key(code: KeyCode) -> Event {
+                Event::Key(KeyEvent::new(code, KeyModifiers::empty()))
+            }
+
+            fn click(column: u16, row: u16) -> Event {
+                Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, modifiers: KeyModifiers::empty() })
+            }
+
+            pub fn setup_test() -> (AppContext, App, TempDir) {
+                let tmp_dir = TempDir::new().expect("Failed to create temp directory");
+                fs::write(tmp_dir.path().join("first.txt"), "first").unwrap();
+                fs::write(tmp_dir.path().join("second.txt"), "second").unwrap();
+                let mut context = AppContext::default();
+                let mut app = App::default();
+                context.set_active_file(Some(tmp_dir.path().join("first.txt")));
+                app.get_mut_code().set_current(context.active_file().clone(), tmp_dir.path()).unwrap();
+                context.set_active_file_changed(false);
+                (context, app, tmp_dir)
+            }
+
+            #[test]
+            pub fn quit_without_changes_test() {
+                let (mut context, mut app, _tmp_dir) = setup_test();
+                assert!(!app.get_code().is_modified());
+                assert!(!app.handle_event(&mut context, None, key(KeyCode::Esc)));
+            }
+
+            #[test]
+            pub fn quit_with_changes_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('!')));
+                assert!(app.get_code().is_modified());
+
+                //cancel keeps the editor open
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Esc)));
+                assert_eq!(app.get_pending().clone(), Some(PendingAction::Quit));
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Esc)));
+                assert!(app.get_pending().is_none());
+
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Esc)));
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Right)));
+                assert_eq!(app.get_confirm_choice(), ConfirmChoice::Discard);
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Left)));
+                assert!(!app.handle_event(&mut context, None, key(KeyCode::Enter)));
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("first.txt")).unwrap(), "!first");
+            }
+
+            #[test]
+            pub fn switch_file_with_changes_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('!')));
+
+                //the project opens another file in a buffer of its own, the changes stay in the first one
+                context.set_active_file(Some(tmp_dir.path().join("second.txt")));
+                app.handle_event(&mut context, Some(ComponentType::Project), key(KeyCode::Null));
+                app.open_active_file(&mut context);
+                assert!(app.get_pending().is_none());
+                assert_eq!(app.get_code().get_buffer_names(), vec!["first.txt".to_string(), "second.txt".to_string()]);
+                assert_eq!(app.get_code().get_current().get_text(), "second");
+                assert!(app.get_code().is_buffer_modified(0));
+
+                context.set_active_file(Some(tmp_dir.path().join("first.txt")));
+                app.open_active_file(&mut context);
+                assert_eq!(app.get_code().get_current().get_text(), "!first");
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("first.txt")).unwrap(), "first");
+            }
+
+            #[test]
+            pub fn unreadable_file_is_not_opened_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                fs::write(tmp_dir.path().join("image.bin"), [0xff, 0xfe, 0x00, 0x9f]).unwrap();
+
+                //a file that is not valid UTF-8 is refused instead of opening as an empty buffer
+                context.set_active_file(Some(tmp_dir.path().join("image.bin")));
+                app.open_active_file(&mut context);
+                assert!(context.message().as_ref().unwrap().starts_with("Could not open image.bin"));
+                assert_eq!(app.get_code().get_buffer_names(), vec!["first.txt".to_string()]);
+                assert_eq!(app.get_code().get_current().get_text(), "first");
+                assert_eq!(context.active_file().clone(), Some(tmp_dir.path().join("first.txt")));
+                assert!(!context.active_file_changed());
+
+                //saving still writes the file that was open
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('!')));
+                app.get_mut_code().handle_event(&mut context, Event::Key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)));
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("first.txt")).unwrap(), "!first");
+                assert_eq!(fs::read(tmp_dir.path().join("image.bin")).unwrap(), vec![0xff, 0xfe, 0x00, 0x9f]);
+
+                context.set_active_file(Some(tmp_dir.path().join("missing.txt")));
+                app.open_active_file(&mut context);
+                assert!(context.message().as_ref().unwrap().starts_with("Could not open missing.txt"));
+                assert_eq!(app.get_code().get_path().clone(), Some(tmp_dir.path().join("first.txt")));
+            }
+
+            #[test]
+            pub fn close_buffer_with_changes_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                context.set_active_file(Some(tmp_dir.path().join("second.txt")));
+                app.open_active_file(&mut context);
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('!')));
+
+                let close = Event::Key(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+                assert!(app.handle_event(&mut context, Some(ComponentType::Code), close.clone()));
+                assert_eq!(app.get_pending().clone(), Some(PendingAction::Close));
+                assert!(app.handle_event(&mut context, Some(ComponentType::Code), key(KeyCode::Char('c'))));
+                assert_eq!(app.get_code().get_buffer_names().len(), 2);
+
+                //discarding closes the buffer and shows the first file again
+                app.handle_event(&mut context, Some(ComponentType::Code), close.clone());
+                assert!(app.handle_event(&mut context, Some(ComponentType::Code), key(KeyCode::Char('d'))));
+                assert_eq!(app.get_code().get_buffer_names(), vec!["first.txt".to_string()]);
+                assert_eq!(context.active_file().clone(), Some(tmp_dir.path().join("first.txt")));
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("second.txt")).unwrap(), "second");
+
+                //a buffer without changes closes right away
+                app.handle_event(&mut context, Some(ComponentType::Code), close);
+                assert!(app.get_pending().is_none());
+                assert_eq!(app.get_code().get_buffer_names(), vec!["untitled".to_string()]);
+                assert_eq!(context.active_file().clone(), None);
+            }
+
+            #[test]
+            pub fn quit_saves_every_buffer_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('!')));
+                context.set_active_file(Some(tmp_dir.path().join("second.txt")));
+                app.open_active_file(&mut context);
+                app.get_mut_code().handle_event(&mut context, key(KeyCode::Char('?')));
+
+                assert!(app.handle_event(&mut context, None, key(KeyCode::Esc)));
+                assert_eq!(app.get_pending().clone(), Some(PendingAction::Quit));
+                assert!(!app.handle_event(&mut context, None, key(KeyCode::Char('s'))));
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("first.txt")).unwrap(), "!first");
+                assert_eq!(fs::read_to_string(tmp_dir.path().join("second.txt")).unwrap(), "?second");
+            }
+
+            #[test]
+            pub fn mouse_focuses_panels_and_opens_project_entries_test() {
+                let (mut context, mut app, tmp_dir) = setup_test();
+                app.set_panel_areas(vec![
+                    (ComponentType::Project, ScreenArea::new(0, 1, 20, 20)),
+                    (ComponentType::Code, ScreenArea::new(20, 1, 60, 20)),
+                    (ComponentType::Terminal, ScreenArea::new(0, 21, 80, 5)),
+                ]);
+                app.get_mut_project().update_contents(&tmp_dir.path().to_path_buf());
+                app.get_mut_project().set_list_area(ScreenArea::new(1, 2, 18, 18));
+                let second = app.get_project().get_contents().iter().position(|path| path.ends_with("second.txt")).unwrap() as u16;
+
+                assert!(app.handle_event(&mut context, None, click(5, 2 + second)));
+                assert_eq!(context.focus().clone(), Some(ComponentType::Project));
+                assert_eq!(context.active_file().clone(), Some(tmp_dir.path().join("second.txt")));
+
+                //the wheel goes to the panel under the mouse without moving the focus
+                assert!(app.handle_event(&mut context, None, Event::Mouse(MouseEvent { kind: MouseEventKind::ScrollUp, column: 5, row: 23, modifiers: KeyModifiers::empty() })));
+                assert_eq!(context.focus().clone(), Some(ComponentType::Project));
+
+                assert!(app.handle_event(&mut context, None, click(30, 10)));
+                assert_eq!(context.focus().clone(), Some(ComponentType::Code));
+                assert_eq!(context.hover().clone(), ComponentType::Code);
+            }
+
+        }
+
     }
 
 }
\ No newline at end of file